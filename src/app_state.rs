@@ -0,0 +1,478 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::config::{Broadcaster, Channel, LiveHistory, Platform, TitleChange, MAX_TITLE_HISTORY};
+use crate::platform;
+
+pub const APP_STATE_FILE: &str = "state.json";
+
+// The version of the exported app data, raised when an older version couldn't import it anymore.
+const EXPORT_FORMAT: u64 = 1;
+
+/// Information that we keep between sessions, which doesn't belong in the config file.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AppState {
+    // The config file of the last profile that was selected.
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    // When the application was last started (seconds since the epoch), to know if it crashed since then.
+    #[serde(default)]
+    pub last_start: Option<u64>,
+
+    // The last titles of each channel.
+    #[serde(default)]
+    pub title_history: BTreeMap<String, Vec<TitleChange>>,
+
+    // The snoozed channels, until when (seconds since the epoch).
+    #[serde(default)]
+    pub snoozed: BTreeMap<String, u64>,
+
+    // The broadcaster types of the Twitch channels, so that their badges are shown before the first lookup.
+    #[serde(default)]
+    pub broadcasters: BTreeMap<String, Broadcaster>,
+
+    // When each channel was last seen live, to find the inactive ones.
+    #[serde(default)]
+    pub live_history: BTreeMap<String, LiveHistory>,
+}
+
+/// The app data of the channels, to move it to another machine ("Export app data…").
+/// The credentials and the tokens are in the config file, they are never exported.
+#[derive(Debug, Deserialize, Serialize)]
+struct Export {
+    format: u64,
+    // Seconds since the epoch.
+    exported_at: u64,
+    app_state: AppState,
+}
+
+/// What an import changed: the channels that got their data back, and the ones that aren't in the config.
+pub struct Imported {
+    pub channels: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+/// The directory where we keep our own files, falls back to the current directory if it's not available.
+pub fn directory() -> PathBuf {
+    if let Some(directory) = platform::data_directory() {
+        if std::fs::create_dir_all(&directory).is_ok() {
+            return directory;
+        }
+    }
+
+    PathBuf::from(".")
+}
+
+/// Reads the app state, if it's missing or invalid we just start from scratch.
+pub fn read() -> AppState {
+    std::fs::File::open(directory().join(APP_STATE_FILE))
+        .ok()
+        .and_then(|file| serde_json::from_reader(std::io::BufReader::new(file)).ok())
+        .unwrap_or_default()
+}
+
+pub fn write(app_state: &AppState) -> std::io::Result<()> {
+    let value = serde_json::to_value(app_state)?;
+
+    config::write_file(directory().join(APP_STATE_FILE), &value)
+}
+
+/// Gives the channels the titles that they had in the previous sessions.
+pub fn restore_title_history(channels: &mut [Channel]) {
+    let mut title_history = read().title_history;
+
+    for channel in channels {
+        if let Some(history) = title_history.remove(&channel.name) {
+            channel.title_history = history;
+        }
+    }
+}
+
+/// Gives the channels the broadcaster types that we looked up in the previous sessions.
+pub fn restore_broadcasters(channels: &mut [Channel]) {
+    let broadcasters = read().broadcasters;
+
+    for channel in channels
+        .iter_mut()
+        .filter(|channel| channel.platform == Platform::Twitch)
+    {
+        channel.broadcaster = broadcasters.get(&channel.name).cloned();
+    }
+}
+
+/// Replaces the saved broadcaster types with the ones of the channels.
+pub fn write_broadcasters(channels: &[Channel]) -> std::io::Result<()> {
+    let broadcasters = channels
+        .iter()
+        .filter_map(|channel| Some((channel.name.clone(), channel.broadcaster.clone()?)))
+        .collect();
+
+    write(&AppState {
+        broadcasters,
+        ..read()
+    })
+}
+
+/// Gives the channels their live history, the ones that don't have one start being tracked at their next update
+///  (see `provider::apply_streams`).
+pub fn restore_live_history(channels: &mut [Channel]) {
+    let live_history = read().live_history;
+
+    for channel in channels {
+        channel.live_history = live_history.get(&channel.name).copied();
+    }
+}
+
+/// Replaces the saved live history with the one of the channels.
+pub fn write_live_history(channels: &[Channel]) -> std::io::Result<()> {
+    let live_history = channels
+        .iter()
+        .filter_map(|channel| Some((channel.name.clone(), channel.live_history?)))
+        .collect();
+
+    write(&AppState {
+        live_history,
+        ..read()
+    })
+}
+
+/// Only the snoozes that haven't ended yet, of the channels that are still in the config.
+fn active_snoozes(snoozed: &BTreeMap<String, u64>, channels: &[Channel]) -> BTreeMap<String, u64> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    snoozed
+        .iter()
+        .filter(|(name, until)| **until > now && channels.iter().any(|c| &c.name == *name))
+        .map(|(name, until)| (name.clone(), *until))
+        .collect()
+}
+
+/// The snoozes of the previous sessions that are still active.
+pub fn restore_snoozed(channels: &[Channel]) -> BTreeMap<String, u64> {
+    active_snoozes(&read().snoozed, channels)
+}
+
+/// Replaces the saved snoozes, the ones that ended (or of channels that were removed) are dropped.
+pub fn write_snoozed(snoozed: &BTreeMap<String, u64>, channels: &[Channel]) -> std::io::Result<()> {
+    write(&AppState {
+        snoozed: active_snoozes(snoozed, channels),
+        ..read()
+    })
+}
+
+/// Replaces the saved title history with the one of the channels, the ones that were removed
+///  from the config are dropped.
+pub fn write_title_history(channels: &[Channel]) -> std::io::Result<()> {
+    let title_history = channels
+        .iter()
+        .filter(|channel| !channel.title_history.is_empty())
+        .map(|channel| (channel.name.clone(), channel.title_history.clone()))
+        .collect();
+
+    write(&AppState {
+        title_history,
+        ..read()
+    })
+}
+
+/// Writes the app data to a file, without what only makes sense on this machine (e.g. the profile).
+pub fn export(path: &Path) -> std::io::Result<()> {
+    let exported_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    config::write_file(path, &serde_json::to_value(exported(read(), exported_at))?)
+}
+
+fn exported(app_state: AppState, exported_at: u64) -> Export {
+    Export {
+        format: EXPORT_FORMAT,
+        exported_at,
+        app_state: AppState {
+            profile: None,
+            last_start: None,
+            ..app_state
+        },
+    }
+}
+
+/// The name of a channel in the config, not case sensitive.
+fn channel_name<'a>(channels: &'a [Channel], name: &str) -> Option<&'a str> {
+    channels
+        .iter()
+        .find(|channel| channel.name.eq_ignore_ascii_case(name))
+        .map(|channel| channel.name.as_str())
+}
+
+/// Merges the app data of an export with ours, see `merge`.
+pub fn import(path: &Path, channels: &[Channel]) -> Result<Imported, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+    let imported = read_export(&text)?;
+    let mut app_state = read();
+
+    let result = merge(&mut app_state, imported, channels);
+
+    write(&app_state).map_err(|e| e.to_string())?;
+
+    Ok(result)
+}
+
+/// The app data of an export, unless it's from a newer version.
+fn read_export(text: &str) -> Result<AppState, String> {
+    let value: serde_json::Value = serde_json::from_str(text)
+        .map_err(|e| format!("It isn't an export of the app data ({}).", e))?;
+
+    // Before the rest, a newer format might not be readable at all.
+    let format = value
+        .get("format")
+        .and_then(serde_json::Value::as_u64)
+        .ok_or_else(|| String::from("It isn't an export of the app data (no 'format')."))?;
+
+    if format > EXPORT_FORMAT {
+        return Err(format!(
+            "It was exported by a newer version (format {}, this one reads up to {}), please update first.",
+            format, EXPORT_FORMAT
+        ));
+    }
+
+    let export: Export =
+        serde_json::from_value(value).map_err(|e| format!("The export is invalid: {}", e))?;
+
+    Ok(export.app_state)
+}
+
+/// The channels are matched by their login (the other machine might have another list).
+/// What is in both is merged: the titles are combined, and the most recent of the rest is kept.
+fn merge(app_state: &mut AppState, imported: AppState, channels: &[Channel]) -> Imported {
+    let names: BTreeSet<String> = imported
+        .title_history
+        .keys()
+        .chain(imported.snoozed.keys())
+        .chain(imported.broadcasters.keys())
+        .chain(imported.live_history.keys())
+        .cloned()
+        .collect();
+
+    let mut result = Imported {
+        channels: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    for name in names {
+        match channel_name(channels, &name) {
+            Some(name) => result.channels.push(String::from(name)),
+            None => result.skipped.push(name),
+        }
+    }
+
+    for (name, titles) in imported.title_history {
+        if let Some(name) = channel_name(channels, &name) {
+            let history = app_state
+                .title_history
+                .entry(String::from(name))
+                .or_default();
+
+            history.extend(titles);
+            history.sort_by_key(|change| change.at);
+            history.dedup();
+
+            if history.len() > MAX_TITLE_HISTORY {
+                history.drain(..history.len() - MAX_TITLE_HISTORY);
+            }
+        }
+    }
+
+    for (name, until) in imported.snoozed {
+        if let Some(name) = channel_name(channels, &name) {
+            let snoozed = app_state.snoozed.entry(String::from(name)).or_default();
+
+            *snoozed = until.max(*snoozed);
+        }
+    }
+
+    for (name, broadcaster) in imported.broadcasters {
+        if let Some(name) = channel_name(channels, &name) {
+            let newer = app_state
+                .broadcasters
+                .get(name)
+                .is_none_or(|ours| broadcaster.checked_at > ours.checked_at);
+
+            if newer {
+                app_state
+                    .broadcasters
+                    .insert(String::from(name), broadcaster);
+            }
+        }
+    }
+
+    for (name, history) in imported.live_history {
+        if let Some(name) = channel_name(channels, &name) {
+            let merged = match app_state.live_history.get(name) {
+                Some(ours) => LiveHistory {
+                    tracked_since: ours.tracked_since.min(history.tracked_since),
+                    last_live: ours.last_live.max(history.last_live),
+                },
+                None => history,
+            };
+
+            app_state.live_history.insert(String::from(name), merged);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::{test_state, BroadcasterType};
+
+    fn channels(names: &[&str]) -> Vec<Channel> {
+        test_state(serde_json::json!({ "channels": names })).channels
+    }
+
+    fn app_state() -> AppState {
+        AppState {
+            profile: Some(String::from("work.json")),
+            last_start: Some(1_700_000_000),
+            title_history: BTreeMap::from([(
+                String::from("j_blow"),
+                vec![
+                    TitleChange {
+                        title: String::from("Programming a game"),
+                        at: 1_700_000_100,
+                    },
+                    TitleChange {
+                        title: String::from("Compiler work"),
+                        at: 1_700_000_200,
+                    },
+                ],
+            )]),
+            snoozed: BTreeMap::from([(String::from("museun"), 1_700_003_600)]),
+            broadcasters: BTreeMap::from([(
+                String::from("j_blow"),
+                Broadcaster {
+                    broadcaster_type: BroadcasterType::Partner,
+                    checked_at: 1_700_000_000,
+                    id: Some(String::from("12345")),
+                    not_found: 0,
+                },
+            )]),
+            live_history: BTreeMap::from([(
+                String::from("museun"),
+                LiveHistory {
+                    tracked_since: 1_600_000_000,
+                    last_live: Some(1_700_000_000),
+                },
+            )]),
+        }
+    }
+
+    /// Through a file, the same way as "Export app data…" and "Import app data…".
+    fn round_trip(app_state: AppState) -> String {
+        let path =
+            std::env::temp_dir().join(format!("taskbar-twitch-export-{}.json", std::process::id()));
+
+        config::write_file(
+            &path,
+            &serde_json::to_value(exported(app_state, 1_700_000_300)).unwrap(),
+        )
+        .unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        text
+    }
+
+    #[test]
+    fn export_and_import() {
+        let text = round_trip(app_state());
+
+        let mut imported = AppState::default();
+        let result = merge(
+            &mut imported,
+            read_export(&text).unwrap(),
+            &channels(&["j_blow", "museun"]),
+        );
+
+        assert_eq!(result.channels, ["j_blow", "museun"]);
+        assert!(result.skipped.is_empty());
+
+        // What only makes sense on the other machine isn't exported.
+        let expected = AppState {
+            profile: None,
+            last_start: None,
+            ..app_state()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&imported).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn import_merges_with_our_data() {
+        let text = round_trip(app_state());
+
+        let mut ours = AppState {
+            title_history: BTreeMap::from([(
+                String::from("J_Blow"),
+                vec![TitleChange {
+                    title: String::from("Newer title"),
+                    at: 1_700_000_500,
+                }],
+            )]),
+            snoozed: BTreeMap::from([(String::from("J_Blow"), 1_700_007_200)]),
+            ..AppState::default()
+        };
+
+        // The names are matched case-insensitively, and the channels that we don't have are skipped.
+        let result = merge(
+            &mut ours,
+            read_export(&text).unwrap(),
+            &channels(&["J_Blow"]),
+        );
+
+        assert_eq!(result.channels, ["J_Blow"]);
+        assert_eq!(result.skipped, ["museun"]);
+
+        let titles: Vec<&str> = ours.title_history["J_Blow"]
+            .iter()
+            .map(|change| change.title.as_str())
+            .collect();
+
+        assert_eq!(
+            titles,
+            ["Programming a game", "Compiler work", "Newer title"]
+        );
+        // The longest snooze is kept.
+        assert_eq!(ours.snoozed["J_Blow"], 1_700_007_200);
+        assert!(!ours.snoozed.contains_key("museun"));
+        assert!(ours.broadcasters.contains_key("J_Blow"));
+    }
+
+    #[test]
+    fn invalid_exports() {
+        assert!(read_export("").is_err());
+        assert!(read_export(r#"{"app_state": {}}"#).is_err());
+        assert!(read_export(r#"{"format": 1, "exported_at": 0}"#).is_err());
+
+        let newer = read_export(r#"{"format": 2, "exported_at": 0, "app_state": {}}"#);
+        assert!(newer.unwrap_err().contains("newer version"));
+
+        assert!(read_export(r#"{"format": 1, "exported_at": 0, "app_state": {}}"#).is_ok());
+    }
+}
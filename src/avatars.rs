@@ -0,0 +1,56 @@
+//! The avatars of the Twitch channels, they replace the icon of their notifications (where the platform
+//!  can show them, see `platform::NOTIFICATION_AVATARS`).
+//! They are downloaded in the data directory when the channels are looked up (see `twitch::update_users`),
+//!  a notification never waits for one: until it's there, the notification has the usual icon.
+
+use std::path::PathBuf;
+
+use log::debug;
+
+use crate::app_state;
+use crate::updates::USER_AGENT;
+
+const AVATARS_DIRECTORY: &str = "avatars";
+
+/// Where the avatar of a channel is (or would be), the logins of Twitch are safe as file names.
+fn path(channel: &str) -> PathBuf {
+    app_state::directory()
+        .join(AVATARS_DIRECTORY)
+        .join(format!("{}.png", channel))
+}
+
+/// The avatar of a channel, if it was downloaded (and wasn't removed since then).
+pub fn cached(channel: &str) -> Option<PathBuf> {
+    Some(path(channel)).filter(|path| path.is_file())
+}
+
+/// Downloads the avatar of a channel, replacing the previous one (e.g. it changed).
+/// It's written next to its place first, so that a notification never gets half of it.
+pub async fn download(client: &reqwest::Client, channel: &str, url: &str) -> std::io::Result<()> {
+    let to_io = std::io::Error::other;
+
+    let response = client
+        .get(url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(to_io)?;
+
+    let image = response.bytes().await.map_err(to_io)?;
+
+    let path = path(channel);
+
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+
+    let temporary = path.with_extension("png.tmp");
+
+    std::fs::write(&temporary, &image)?;
+    std::fs::rename(&temporary, &path)?;
+
+    debug!("Downloaded the avatar of {}", channel);
+
+    Ok(())
+}
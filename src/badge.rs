@@ -0,0 +1,209 @@
+//! Draws the number of unseen channels on top of the tray icon, the way chat applications do.
+//! The badge is drawn into the uncompressed (32 bits) images of the '.ico' file, the PNG ones are left as they are.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::lock::RecoverLock;
+
+// Higher counts are shown as "9+".
+pub const MAX_BADGE_COUNT: usize = 10;
+
+// The size of the ICONDIR header and of each ICONDIRENTRY.
+const ICON_DIR_SIZE: usize = 6;
+const ICON_DIR_ENTRY_SIZE: usize = 16;
+
+// The size of the BITMAPINFOHEADER that starts each uncompressed image.
+const BITMAP_HEADER_SIZE: usize = 40;
+
+// The colors are in the order of the image data (BGRA).
+const BADGE_COLOR: [u8; 4] = [0x16, 0x19, 0xe9, 0xff];
+const TEXT_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+// Each glyph is 3 pixels wide and 5 pixels tall, a row per number (the bits are the pixels, from left to right).
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+
+const DIGITS: [[u8; GLYPH_HEIGHT]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+const PLUS: [u8; GLYPH_HEIGHT] = [0b000, 0b010, 0b111, 0b010, 0b000];
+
+// The tray icon needs a static buffer, so the icons with a badge are leaked.
+// There are only a few of them (one per icon and count), so they are kept around.
+type BadgedIcons = HashMap<(usize, usize), &'static [u8]>;
+
+static ICONS: Mutex<Option<BadgedIcons>> = Mutex::new(None);
+
+/// An uncompressed image of the icon, its rows are stored from the bottom to the top.
+struct Bitmap<'a> {
+    data: &'a mut [u8],
+    width: usize,
+    height: usize,
+}
+
+impl Bitmap<'_> {
+    fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let row = self.height - 1 - y;
+
+        let pixel = BITMAP_HEADER_SIZE + (row * self.width + x) * 4;
+        self.data[pixel..pixel + 4].copy_from_slice(&color);
+
+        // The AND mask follows the pixels (one bit per pixel, every row is padded to 4 bytes),
+        //  a cleared bit is opaque.
+        let mask_stride = self.width.div_ceil(32) * 4;
+        let mask = BITMAP_HEADER_SIZE + self.width * self.height * 4 + row * mask_stride + x / 8;
+
+        if let Some(byte) = self.data.get_mut(mask) {
+            *byte &= !(0x80 >> (x % 8));
+        }
+    }
+}
+
+/// The icon with the count in a circle at its bottom right corner, `None` if it couldn't be drawn.
+pub fn with_badge(icon: &'static [u8], count: usize) -> Option<&'static [u8]> {
+    let count = count.min(MAX_BADGE_COUNT);
+    let key = (icon.as_ptr() as usize, count);
+
+    let mut icons = ICONS.lock_or_recover();
+    let icons = icons.get_or_insert_with(HashMap::new);
+
+    if let Some(badged) = icons.get(&key) {
+        return Some(badged);
+    }
+
+    let badged: &'static [u8] = Box::leak(draw_badge(icon, count)?.into_boxed_slice());
+
+    icons.insert(key, badged);
+
+    Some(badged)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 2)?;
+
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 4)?;
+
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+fn draw_badge(icon: &[u8], count: usize) -> Option<Vec<u8>> {
+    let mut result = icon.to_vec();
+
+    let images = read_u16(icon, 4)?;
+    let mut drawn = false;
+
+    for index in 0..images {
+        let entry = ICON_DIR_SIZE + index * ICON_DIR_ENTRY_SIZE;
+
+        let size = read_u32(icon, entry + 8)?;
+        let offset = read_u32(icon, entry + 12)?;
+
+        let data = result.get_mut(offset..offset + size)?;
+
+        // Only the uncompressed images with an alpha channel.
+        let header_size = read_u32(data, 0)?;
+        let bits = read_u16(data, 14)?;
+        let compression = read_u32(data, 16)?;
+
+        if header_size != BITMAP_HEADER_SIZE || bits != 32 || compression != 0 {
+            continue;
+        }
+
+        // The height includes the AND mask.
+        let width = read_u32(data, 4)?;
+        let height = read_u32(data, 8)? / 2;
+
+        if data.len() < BITMAP_HEADER_SIZE + width * height * 4 {
+            continue;
+        }
+
+        draw(
+            &mut Bitmap {
+                data,
+                width,
+                height,
+            },
+            count,
+        );
+        drawn = true;
+    }
+
+    if drawn {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+fn draw(bitmap: &mut Bitmap, count: usize) {
+    let diameter = (bitmap.width * 5 / 8).max(GLYPH_WIDTH * 2 + 2);
+    let radius = diameter as f32 / 2.0;
+
+    let center_x = bitmap.width as f32 - radius;
+    let center_y = bitmap.height as f32 - radius;
+
+    for y in bitmap.height.saturating_sub(diameter)..bitmap.height {
+        for x in bitmap.width.saturating_sub(diameter)..bitmap.width {
+            let dx = x as f32 + 0.5 - center_x;
+            let dy = y as f32 + 0.5 - center_y;
+
+            if dx * dx + dy * dy <= radius * radius {
+                bitmap.set_pixel(x, y, BADGE_COLOR);
+            }
+        }
+    }
+
+    let glyphs: Vec<[u8; GLYPH_HEIGHT]> = if count >= MAX_BADGE_COUNT {
+        vec![DIGITS[9], PLUS]
+    } else {
+        vec![DIGITS[count]]
+    };
+
+    // The glyphs are scaled with the icon, with a pixel between them.
+    let scale = (diameter / 10).max(1);
+
+    let text_width = glyphs.len() * (GLYPH_WIDTH + 1) * scale - scale;
+    let text_height = GLYPH_HEIGHT * scale;
+
+    let left = (center_x - text_width as f32 / 2.0).round().max(0.0) as usize;
+    let top = (center_y - text_height as f32 / 2.0).round().max(0.0) as usize;
+
+    for (index, glyph) in glyphs.iter().enumerate() {
+        let glyph_left = left + index * (GLYPH_WIDTH + 1) * scale;
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = glyph_left + column * scale + dx;
+                        let y = top + row * scale + dy;
+
+                        bitmap.set_pixel(x, y, TEXT_COLOR);
+                    }
+                }
+            }
+        }
+    }
+}
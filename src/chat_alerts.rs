@@ -0,0 +1,292 @@
+//! Notifies when some keywords (e.g. our name, "giveaway") are said in the chat of a live channel.
+//! The chats are read anonymously over IRC, there's one connection per channel of the "chat_alerts",
+//!  and only while the channel is live. Nothing is connected unless the "chat_alerts" are set.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use serde::Deserialize;
+
+use crate::config::State;
+use crate::lock::RecoverLock;
+use crate::platform::send_notification;
+
+pub const IRC_SERVER: &str = "irc.chat.twitch.tv:6667";
+
+// The anonymous users of the chat, they can read it but not write in it.
+pub const ANONYMOUS_NICK: &str = "justinfan31337";
+
+// How often we check which channels are live (and if the alerts changed).
+pub const CHECK_CHANNELS_TIME: Duration = Duration::from_secs(5);
+
+// So that the connections notice when they have to stop.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(1);
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Doubles after every failed connection.
+pub const MIN_RECONNECT_TIME: Duration = Duration::from_secs(5);
+pub const MAX_RECONNECT_TIME: Duration = Duration::from_secs(5 * 60);
+
+// A keyword only notifies once in this time, the chat usually repeats it a lot.
+pub const KEYWORD_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// An entry of the "chat_alerts" of the config, e.g. { "channel": "j_blow", "keywords": ["giveaway"] }
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ChatAlert {
+    pub channel: String,
+    pub keywords: Vec<String>,
+}
+
+/// A message of the chat, e.g. ":name!name@name.tmi.twitch.tv PRIVMSG #channel :hello"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChatMessage<'a> {
+    pub user: &'a str,
+    pub channel: &'a str,
+    pub text: &'a str,
+}
+
+/// Only the chat messages are parsed, the other lines return `None`.
+pub fn parse_message(line: &str) -> Option<ChatMessage<'_>> {
+    let line = line.trim_end_matches(['\r', '\n']);
+
+    // The tags are only sent if they were requested, they come first (e.g. "@badges=... :name!...").
+    let line = match line.strip_prefix('@') {
+        Some(tagged) => tagged.split_once(' ')?.1,
+        None => line,
+    };
+
+    let (prefix, rest) = line.strip_prefix(':')?.split_once(' ')?;
+    let (command, rest) = rest.split_once(' ')?;
+
+    if command != "PRIVMSG" {
+        return None;
+    }
+
+    let (channel, text) = rest.split_once(" :")?;
+
+    Some(ChatMessage {
+        user: prefix.split('!').next()?,
+        channel: channel.strip_prefix('#')?,
+        text,
+    })
+}
+
+/// The keywords that are in a message, ignoring the case.
+pub fn matching_keywords<'a>(keywords: &'a [String], text: &str) -> Vec<&'a str> {
+    let text = text.to_lowercase();
+
+    keywords
+        .iter()
+        .filter(|keyword| !keyword.is_empty() && text.contains(&keyword.to_lowercase()))
+        .map(|keyword| keyword.as_str())
+        .collect()
+}
+
+/// The connection to the chat of a channel, it reconnects until it's stopped.
+struct Connection {
+    alert: ChatAlert,
+    thread: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Connection {
+    fn spawn(alert: ChatAlert) -> Connection {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_alert = alert.clone();
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || read_chat(thread_alert, thread_stop));
+
+        Connection {
+            alert,
+            thread: Some(thread),
+            stop,
+        }
+    }
+
+    /// Waits for the thread to finish, it notices it within `READ_TIMEOUT` (or right away while reconnecting).
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            thread.join().ok();
+        }
+    }
+}
+
+/// Keeps a connection to the chat of each live channel of the "chat_alerts", runs forever.
+pub fn watch(config: Arc<Mutex<State>>) {
+    let mut connections: HashMap<String, Connection> = HashMap::new();
+
+    loop {
+        // The alerts of the channels that are live, by channel.
+        let wanted: HashMap<String, ChatAlert> = {
+            let config = config.lock_or_recover();
+
+            config
+                .chat_alerts
+                .iter()
+                .filter(|alert| {
+                    config
+                        .find_channel(&alert.channel)
+                        .is_some_and(|channel| channel.is_online())
+                })
+                .map(|alert| (alert.channel.to_lowercase(), alert.clone()))
+                .collect()
+        };
+
+        // The channels that went offline, or whose keywords changed.
+        let outdated: Vec<String> = connections
+            .iter()
+            .filter(|(channel, connection)| wanted.get(*channel) != Some(&connection.alert))
+            .map(|(channel, _)| channel.clone())
+            .collect();
+
+        for channel in outdated {
+            if let Some(mut connection) = connections.remove(&channel) {
+                info!("Leaving the chat of {}", channel);
+                connection.stop();
+            }
+        }
+
+        for (channel, alert) in wanted {
+            connections.entry(channel).or_insert_with_key(|channel| {
+                info!("Joining the chat of {}", channel);
+                Connection::spawn(alert)
+            });
+        }
+
+        std::thread::sleep(CHECK_CHANNELS_TIME);
+    }
+}
+
+/// Runs until `stop` is set, reconnecting (with a backoff) when the connection is lost.
+fn read_chat(alert: ChatAlert, stop: Arc<AtomicBool>) {
+    let mut reconnect_time = MIN_RECONNECT_TIME;
+    let mut notified: HashMap<String, Instant> = HashMap::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        let connected_at = Instant::now();
+
+        match read_connection(&alert, &stop, &mut notified) {
+            Ok(()) => return,
+            Err(e) => warn!(
+                "Lost the connection to the chat of {}: {}",
+                alert.channel, e
+            ),
+        }
+
+        // A connection that stayed up for a while starts the backoff over.
+        if connected_at.elapsed() > MAX_RECONNECT_TIME {
+            reconnect_time = MIN_RECONNECT_TIME;
+        }
+
+        std::thread::park_timeout(reconnect_time);
+
+        reconnect_time = (reconnect_time * 2).min(MAX_RECONNECT_TIME);
+    }
+}
+
+/// Returns once `stop` is set, or with the error that ended the connection.
+fn read_connection(
+    alert: &ChatAlert,
+    stop: &AtomicBool,
+    notified: &mut HashMap<String, Instant>,
+) -> std::io::Result<()> {
+    let address = IRC_SERVER.to_socket_addrs()?.next().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Couldn't resolve the chat server.",
+        )
+    })?;
+
+    let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    write!(
+        stream,
+        "NICK {}\r\nJOIN #{}\r\n",
+        ANONYMOUS_NICK,
+        alert.channel.to_lowercase()
+    )?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    // What was read of the current line, it's kept when the read times out.
+    let mut line: Vec<u8> = Vec::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "The server closed the connection.",
+                ))
+            }
+            Ok(_) => {}
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        }
+
+        if !line.ends_with(b"\n") {
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&line).into_owned();
+        line.clear();
+
+        // The server closes the connections that don't answer.
+        if let Some(server) = text.strip_prefix("PING ") {
+            write!(stream, "PONG {}\r\n", server.trim_end())?;
+            continue;
+        }
+
+        if let Some(message) = parse_message(&text) {
+            notify_keywords(alert, &message, notified);
+        } else {
+            debug!("Chat of {}: {}", alert.channel, text.trim_end());
+        }
+    }
+
+    Ok(())
+}
+
+fn notify_keywords(
+    alert: &ChatAlert,
+    message: &ChatMessage,
+    notified: &mut HashMap<String, Instant>,
+) {
+    for keyword in matching_keywords(&alert.keywords, message.text) {
+        let key = keyword.to_lowercase();
+
+        if let Some(at) = notified.get(&key) {
+            if at.elapsed() < KEYWORD_COOLDOWN {
+                continue;
+            }
+        }
+
+        notified.insert(key, Instant::now());
+
+        info!(
+            "\"{}\" was said in the chat of {}",
+            keyword, message.channel
+        );
+
+        send_notification(
+            &format!("\"{}\" in the chat of {}", keyword, message.channel),
+            &format!("{}: {}", message.user, message.text),
+        );
+    }
+}
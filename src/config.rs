@@ -1,31 +1,333 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use enum_iterator::IntoEnumIterator;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 use structopt::StructOpt;
 
-pub const DEFAULT_CONFIG_FILE: &'static str = "config.json";
+use crate::app_state;
+use crate::chat_alerts::ChatAlert;
+use crate::lock::RecoverLock;
+use crate::platform::send_notification;
+use crate::summary::Activity;
+use crate::template;
+use crate::text;
+use crate::twitch;
+use crate::twitch::TokenInfo;
+use crate::twitch::UpdateFailure;
+use crate::updates::Release;
+use crate::youtube;
+
+pub const DEFAULT_CONFIG_FILE: &str = "config.json";
+
+// The placeholders of "menu_format" and "menu_format_offline", see the `template` module.
+pub const MENU_PLACEHOLDERS: [&str; 9] = [
+    "name", "title", "viewers", "game", "uptime", "since", "note", "trend", "badge",
+];
+
+// How many of the last titles of each channel are kept.
+pub const MAX_TITLE_HISTORY: usize = 10;
+
+// The length of the Twitch logins.
+pub const MIN_CHANNEL_NAME_LENGTH: usize = 4;
+pub const MAX_CHANNEL_NAME_LENGTH: usize = 25;
+
+// The Kick channels can be a bit shorter.
+pub const MIN_KICK_CHANNEL_NAME_LENGTH: usize = 3;
+pub const PROFILES_DIRECTORY: &str = "profiles";
+
+/// Where a channel streams, each platform has its own `StreamProvider` (see the `provider` module).
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    #[default]
+    Twitch,
+    Kick,
+    YouTube,
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Platform::Twitch => write!(f, "Twitch"),
+            Platform::Kick => write!(f, "Kick"),
+            Platform::YouTube => write!(f, "YouTube"),
+        }
+    }
+}
+
+/// How often a Twitch channel is updated, the low priority ones only every "low_priority_every" updates.
+/// The high priority ones are updated at every update, same as the normal ones.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+/// Whether a channel is live, as far as we know.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub enum ChannelStatus {
+    // We haven't heard from Twitch yet, or we couldn't reach it for a while.
+    #[default]
+    Unknown,
+    Online,
+    Offline {
+        since: Instant,
+    },
+}
+
+/// Since when we know about a channel, and when we last saw it live (seconds since the epoch).
+/// It's kept between sessions (see the `app_state` module), to find the channels that don't stream anymore.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, PartialOrd)]
+pub struct LiveHistory {
+    pub tracked_since: u64,
+    pub last_live: Option<u64>,
+}
+
+/// One of the last titles of a channel, they are kept between sessions (see the `app_state` module).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, PartialOrd)]
+pub struct TitleChange {
+    pub title: String,
+    // Seconds since the epoch.
+    pub at: u64,
+}
+
+/// What Twitch says a channel is ("broadcaster_type" of "helix/users"), the regular ones are neither.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum BroadcasterType {
+    Partner,
+    Affiliate,
+    #[default]
+    Regular,
+}
+
+impl BroadcasterType {
+    /// Anything that we don't know about (or an empty type) is a regular channel.
+    pub fn from_twitch(broadcaster_type: &str) -> Self {
+        match broadcaster_type {
+            "partner" => BroadcasterType::Partner,
+            "affiliate" => BroadcasterType::Affiliate,
+            _ => BroadcasterType::Regular,
+        }
+    }
+
+    /// The marker next to the name of the channel in the menu, with "broadcaster_badges".
+    pub fn badge(self) -> Option<&'static str> {
+        match self {
+            BroadcasterType::Partner => Some("✓"),
+            BroadcasterType::Affiliate => Some("●"),
+            BroadcasterType::Regular => None,
+        }
+    }
+}
+
+/// An entry of the "tag_alerts", a tag (e.g. "DropsEnabled") for every channel or only for some of them:
+///  { "tag": "Charity", "channels": ["j_blow"] }
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(from = "TagAlertEntry")]
+pub struct TagAlert {
+    pub tag: String,
+    // Empty for every channel.
+    pub channels: Vec<String>,
+}
+
+impl TagAlert {
+    pub fn applies_to(&self, channel: &str) -> bool {
+        self.channels.is_empty()
+            || self
+                .channels
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(channel))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TagAlertEntry {
+    Tag(String),
+    Scoped {
+        tag: String,
+        #[serde(default)]
+        channels: Vec<String>,
+    },
+}
+
+impl From<TagAlertEntry> for TagAlert {
+    fn from(entry: TagAlertEntry) -> Self {
+        match entry {
+            TagAlertEntry::Tag(tag) => TagAlert {
+                tag,
+                channels: Vec::new(),
+            },
+            TagAlertEntry::Scoped { tag, channels } => TagAlert { tag, channels },
+        }
+    }
+}
+
+/// The tags are compared without the case and the spaces, Twitch shows "DropsEnabled" as "Drops Enabled".
+pub fn same_tag(a: &str, b: &str) -> bool {
+    let normalized = |tag: &str| -> String {
+        tag.chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+
+    normalized(a) == normalized(b)
+}
+
+/// What we know about a Twitch channel from "helix/users" and when we looked it up, it's kept between sessions
+///  (see the `app_state` module) and looked up again once in a while (see `twitch::update_users`).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, PartialOrd)]
+pub struct Broadcaster {
+    pub broadcaster_type: BroadcasterType,
+    // Seconds since the epoch.
+    pub checked_at: u64,
+    // Stays the same when the channel is renamed.
+    #[serde(default)]
+    pub id: Option<String>,
+    // The lookups in a row that didn't find the channel.
+    #[serde(default)]
+    pub not_found: u32,
+}
 
 #[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
 pub struct Channel {
     pub name: String,
-    pub is_online: bool,
+    pub platform: Platform,
+    pub status: ChannelStatus,
+    // Only set while the channel is live.
+    pub started_at: Option<SystemTime>,
+    // When we saw it go live, in case the platform doesn't tell when the stream started.
+    pub live_since: Option<Instant>,
     pub title: Option<String>,
     pub viewers: Option<u64>,
+    // Compared to the viewers of the previous update, only while the channel stays live.
+    pub viewer_trend: ViewerTrend,
+    // Only set while the channel is live, if the platform tells.
+    pub game: Option<String>,
+    // From the last time it was live (during this session).
+    pub last_title: Option<String>,
+    pub last_viewers: Option<u64>,
+    // Written by the user, e.g. why it's in the list.
+    pub note: Option<String>,
+    // From the oldest to the newest, without repeating the same title twice in a row.
+    pub title_history: Vec<TitleChange>,
+    // It went live and the user hasn't opened it yet (or marked it as seen).
+    pub unseen: bool,
+    // Opening the stream also opens its chat.
+    pub also_open_chat: bool,
+    pub priority: Priority,
+    // Only for the Twitch channels, once we looked it up.
+    pub broadcaster: Option<Broadcaster>,
+    // Only set while the channel is live.
+    pub tags: Vec<String>,
+    // The "tag_alerts" that notified during the current stream, each one only notifies once per stream.
+    pub alerted_tags: Vec<String>,
+    pub live_history: Option<LiveHistory>,
 }
 
 impl Channel {
     fn from(name: String) -> Self {
         Channel {
             name,
-            is_online: false,
+            platform: Platform::Twitch,
+            status: ChannelStatus::Unknown,
+            started_at: None,
+            live_since: None,
             title: None,
             viewers: None,
+            viewer_trend: ViewerTrend::Flat,
+            last_title: None,
+            last_viewers: None,
+            note: None,
+            title_history: Vec::new(),
+            unseen: false,
+            also_open_chat: false,
+            priority: Priority::Normal,
+            broadcaster: None,
+            tags: Vec::new(),
+            game: None,
+            alerted_tags: Vec::new(),
+            live_history: None,
+        }
+    }
+
+    /// Called with the title of every update, only a different title is added.
+    /// Returns whether the history changed.
+    pub fn record_title(&mut self, title: &str, at: u64) -> bool {
+        if self
+            .title_history
+            .last()
+            .map(|change| change.title.as_str())
+            == Some(title)
+        {
+            return false;
+        }
+
+        self.title_history.push(TitleChange {
+            title: String::from(title),
+            at,
+        });
+
+        if self.title_history.len() > MAX_TITLE_HISTORY {
+            self.title_history.remove(0);
+        }
+
+        true
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.status == ChannelStatus::Online
+    }
+
+    /// It wasn't live for `days` (at least since we know about it), a new channel is never inactive.
+    pub fn is_inactive(&self, days: u64, now: u64) -> bool {
+        let history = match self.live_history {
+            Some(history) if days > 0 => history,
+            _ => return false,
+        };
+
+        let since = history.last_live.unwrap_or(history.tracked_since);
+
+        now.saturating_sub(since) >= days * 24 * 60 * 60
+    }
+
+    /// Twitch didn't find it a few times in a row, it was probably renamed (or banned).
+    pub fn not_found(&self) -> bool {
+        self.broadcaster
+            .as_ref()
+            .is_some_and(|broadcaster| broadcaster.not_found >= twitch::NOT_FOUND_CHECKS)
+    }
+
+    /// The live fields only describe the current stream, they are kept around as the last stream.
+    pub fn set_unknown(&mut self) {
+        if self.is_online() {
+            self.last_title = self.title.take();
+            self.last_viewers = self.viewers.take();
         }
+
+        self.viewer_trend = ViewerTrend::Flat;
+        self.started_at = None;
+        self.live_since = None;
+        self.tags.clear();
+        self.game = None;
+
+        self.status = ChannelStatus::Unknown;
     }
 }
 
@@ -34,12 +336,22 @@ impl FromStr for Channel {
     type Err = structopt::clap::Error;
 
     fn from_str(name: &str) -> Result<Self, Self::Err> {
-        Ok(Channel::from(name.into()))
+        // The channels of the command line are on Twitch.
+        match validate_channel_name(name, Platform::Twitch) {
+            Ok(name) => Ok(Channel::from(name)),
+            Err(e) => Err(structopt::clap::Error {
+                message: format!("Invalid channel \"{}\": {}.", name, e),
+                kind: structopt::clap::ErrorKind::ValueValidation,
+                info: None,
+            }),
+        }
     }
 }
 
-// When we read the channels, we only have the name,
-//  so we just read the name and fill the other fields.
+// When we read the channels, we only have the name (and maybe a note and some options),
+//  so we just read those and fill the other fields.
+// e.g. "j_blow" or { "name": "j_blow", "note": "only for tournaments", "also_open_chat": true, "priority": "low" }
+// The channels are on Twitch, unless they have a "platform".
 impl<'a> Deserialize<'a> for Channel {
     fn deserialize<D>(deserializer: D) -> Result<Channel, D::Error>
     where
@@ -47,11 +359,104 @@ impl<'a> Deserialize<'a> for Channel {
     {
         let value: serde_json::Value = serde::Deserialize::deserialize(deserializer)?;
 
-        let name = value
-            .as_str()
-            .ok_or(serde::de::Error::custom("Expected a string"))?;
+        let (name, note, also_open_chat, platform, priority) = match &value {
+            serde_json::Value::String(name) => (
+                name.as_str(),
+                None,
+                false,
+                Platform::Twitch,
+                Priority::Normal,
+            ),
+            serde_json::Value::Object(object) => (
+                object
+                    .get("name")
+                    .and_then(|name| name.as_str())
+                    .ok_or(serde::de::Error::custom("Expected a 'name' string"))?,
+                object.get("note").and_then(|note| note.as_str()),
+                object
+                    .get("also_open_chat")
+                    .and_then(|value| value.as_bool())
+                    .unwrap_or_default(),
+                match object.get("platform") {
+                    Some(platform) => Platform::deserialize(platform).map_err(|e| {
+                        serde::de::Error::custom(format!("Unknown 'platform' ({})", e))
+                    })?,
+                    None => Platform::Twitch,
+                },
+                match object.get("priority") {
+                    Some(priority) => Priority::deserialize(priority).map_err(|e| {
+                        serde::de::Error::custom(format!("Unknown 'priority' ({})", e))
+                    })?,
+                    None => Priority::Normal,
+                },
+            ),
+            _ => return Err(serde::de::Error::custom("Expected a string or an object")),
+        };
+
+        // A link to a Kick (or YouTube) channel is on that platform, even without a "platform".
+        let platform = match platform {
+            Platform::Twitch => platform_of_link(name).unwrap_or(platform),
+            platform => platform,
+        };
+
+        let mut channel = Channel::from(String::from(name));
+
+        // Like the titles, so that a note with line breaks doesn't break the menu.
+        channel.note = note
+            .map(|note| text::sanitize(note, text::MAX_TITLE_LENGTH))
+            .filter(|note| !note.is_empty());
+        channel.also_open_chat = also_open_chat;
+        channel.platform = platform;
+        channel.priority = priority;
+
+        Ok(channel)
+    }
+}
+
+/// A credential (the client secret, or the token) that should never show up in the logs or in a bug report.
+/// It's printed as "••••" followed by its last 4 characters, the value itself is only available through `expose`.
+#[derive(Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Secret(value)
+    }
+
+    /// Only used when building the requests that need it.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let length = self.0.chars().count();
+
+        // Short values would be almost entirely visible.
+        if length <= 8 {
+            return write!(f, "••••");
+        }
+
+        let last: String = self.0.chars().skip(length - 4).collect();
+
+        write!(f, "••••{}", last)
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret({})", self)
+    }
+}
+
+// Deserializing from the command line
+impl FromStr for Secret {
+    type Err = structopt::clap::Error;
 
-        Ok(Channel::from(String::from(name)))
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(Secret(String::from(value)))
     }
 }
 
@@ -61,6 +466,11 @@ pub enum OpenStreamUsing {
     Browser,
     Mpv,
     Streamlink,
+    // The web player without the rest of the Twitch site.
+    Popout,
+    Vlc,
+    // The command of "custom_player".
+    Custom,
 }
 
 // Used when printing the available players in the GUI.
@@ -70,6 +480,9 @@ impl Display for OpenStreamUsing {
             OpenStreamUsing::Browser => write!(f, "Browser"),
             OpenStreamUsing::Mpv => write!(f, "Mpv"),
             OpenStreamUsing::Streamlink => write!(f, "Streamlink"),
+            OpenStreamUsing::Popout => write!(f, "Popout player"),
+            OpenStreamUsing::Vlc => write!(f, "VLC"),
+            OpenStreamUsing::Custom => write!(f, "Custom player"),
         }
     }
 }
@@ -83,6 +496,9 @@ impl FromStr for OpenStreamUsing {
             "browser" => Ok(OpenStreamUsing::Browser),
             "mpv" => Ok(OpenStreamUsing::Mpv),
             "streamlink" => Ok(OpenStreamUsing::Streamlink),
+            "popout" => Ok(OpenStreamUsing::Popout),
+            "vlc" => Ok(OpenStreamUsing::Vlc),
+            "custom" => Ok(OpenStreamUsing::Custom),
             _ => Err(structopt::clap::Error {
                 message: "Couldn't parse the player option.".into(),
                 kind: structopt::clap::ErrorKind::ValueValidation,
@@ -92,145 +508,2102 @@ impl FromStr for OpenStreamUsing {
     }
 }
 
-#[derive(Clone, Debug, StructOpt)]
-#[structopt(name = "options")]
-struct Arguments {
-    #[structopt(short = "c", long = "client")]
-    client: Option<String>,
+#[derive(Copy, Clone, Debug, PartialEq, Eq, IntoEnumIterator)]
+pub enum NotificationKind {
+    Live,
+    TitleChanged,
+    GameChanged,
+    Offline,
+}
 
-    #[structopt(short = "s", long = "secret")]
-    secret: Option<String>,
+// Used when printing the per-channel notification options in the GUI.
+impl Display for NotificationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            NotificationKind::Live => write!(f, "Notify when live"),
+            NotificationKind::TitleChanged => write!(f, "Notify on title change"),
+            NotificationKind::GameChanged => write!(f, "Notify on game change"),
+            NotificationKind::Offline => write!(f, "Notify when offline"),
+        }
+    }
+}
 
-    #[structopt(short = "p", long = "player")]
-    player: Option<OpenStreamUsing>,
+/// How the notifications of a kind are shown, for every channel.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationStyle {
+    #[default]
+    Popup,
+    // Only shown in the notification center (Action Center on Windows).
+    Quiet,
+    Off,
+}
 
-    #[structopt(short = "f", long = "file")]
-    config_file: Option<String>,
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct NotificationStyles {
+    #[serde(default)]
+    pub live: NotificationStyle,
 
-    #[structopt(short = "u", long = "channels", use_delimiter = true)]
-    channels: Option<Vec<Channel>>,
+    #[serde(default)]
+    pub title_changed: NotificationStyle,
 
-    #[structopt(short = "n", long = "notify-titles", use_delimiter = true)]
-    notify_title_changed: Option<Vec<String>>,
+    #[serde(default)]
+    pub game_changed: NotificationStyle,
+
+    #[serde(default)]
+    pub offline: NotificationStyle,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct State {
-    pub client: String,
+/// How long the notifications stay on screen (on Windows, "long" is about 25 seconds).
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationDuration {
+    #[default]
+    Short,
+    Long,
+}
 
-    pub secret: String,
+/// "notification_duration", the same one for every kind (e.g. "long") or one per kind (e.g. { "live": "long" }).
+/// The notifications about errors are always long.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NotificationDurations {
+    pub live: NotificationDuration,
+    pub title_changed: NotificationDuration,
+    pub game_changed: NotificationDuration,
+    pub offline: NotificationDuration,
+}
 
-    pub player: OpenStreamUsing,
+impl NotificationDurations {
+    pub fn of(&self, kind: NotificationKind) -> NotificationDuration {
+        match kind {
+            NotificationKind::Live => self.live,
+            NotificationKind::TitleChanged => self.title_changed,
+            NotificationKind::GameChanged => self.game_changed,
+            NotificationKind::Offline => self.offline,
+        }
+    }
+}
 
-    #[serde(skip)]
-    pub session_player: Option<OpenStreamUsing>,
+impl<'a> Deserialize<'a> for NotificationDurations {
+    fn deserialize<D>(deserializer: D) -> Result<NotificationDurations, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let value: serde_json::Value = serde::Deserialize::deserialize(deserializer)?;
 
-    #[serde(skip)]
-    pub config_file: String,
+        let duration = |value: &serde_json::Value| {
+            NotificationDuration::deserialize(value).map_err(|e| {
+                serde::de::Error::custom(format!(
+                    "Unknown 'notification_duration' ({}), expected \"short\" or \"long\"",
+                    e
+                ))
+            })
+        };
 
-    pub channels: Vec<Channel>,
+        match &value {
+            serde_json::Value::String(_) => {
+                let all = duration(&value)?;
 
-    #[serde(default)]
-    pub notify_title_changed: Vec<String>,
-}
+                Ok(NotificationDurations {
+                    live: all,
+                    title_changed: all,
+                    game_changed: all,
+                    offline: all,
+                })
+            }
+            serde_json::Value::Object(object) => {
+                let of = |name: &str| match object.get(name) {
+                    Some(value) => duration(value),
+                    None => Ok(NotificationDuration::Short),
+                };
 
-impl PartialEq for State {
-    fn eq(&self, other: &Self) -> bool {
-        if self.client != other.client || self.secret != other.secret {
-            return false;
+                Ok(NotificationDurations {
+                    live: of("live")?,
+                    title_changed: of("title_changed")?,
+                    game_changed: of("game_changed")?,
+                    offline: of("offline")?,
+                })
+            }
+            _ => Err(serde::de::Error::custom(
+                "Expected a string or an object for 'notification_duration'",
+            )),
         }
+    }
+}
 
-        if self.player != other.player || self.config_file != other.config_file {
-            return false;
+/// What happens to the updates while the session is locked.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnLock {
+    // Stop updating the channels until the session is unlocked.
+    Pause,
+    // Keep updating the channels, but less often.
+    Slow,
+    // Keep updating the channels as usual.
+    #[default]
+    Normal,
+}
+
+/// What happens to the updates while the connection is metered (e.g. tethering to a phone), see the `connectivity` module.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MeteredBehavior {
+    // Update the channels less often, and skip what isn't needed (e.g. the update check).
+    #[default]
+    Reduce,
+    // Keep updating the channels as usual.
+    Normal,
+    // Stop updating the channels until the connection isn't metered anymore.
+    Pause,
+}
+
+/// The most detailed logs that are written, see the `logging` module.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    // Also includes the details of every request.
+    Debug,
+}
+
+/// Whether the viewers of a live channel went up or down since the previous update, see `ViewerTrend::of`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd)]
+pub enum ViewerTrend {
+    Up,
+    Down,
+    // Roughly the same, or we don't know yet (e.g. the first update of a stream).
+    #[default]
+    Flat,
+}
+
+impl ViewerTrend {
+    // Smaller changes (in percent of the previous viewers) are flat, the viewers always move a little.
+    const THRESHOLD_PERCENT: u64 = 5;
+
+    pub fn of(previous: u64, viewers: u64) -> ViewerTrend {
+        // At least one viewer, so that a small stream isn't always going up or down.
+        let threshold = (previous * Self::THRESHOLD_PERCENT / 100).max(1);
+
+        if viewers > previous + threshold {
+            ViewerTrend::Up
+        } else if viewers + threshold < previous {
+            ViewerTrend::Down
+        } else {
+            ViewerTrend::Flat
         }
+    }
 
-        if self.notify_title_changed != other.notify_title_changed {
-            return false;
+    pub fn symbol(self) -> Option<&'static str> {
+        match self {
+            ViewerTrend::Up => Some("↑"),
+            ViewerTrend::Down => Some("↓"),
+            ViewerTrend::Flat => None,
         }
+    }
+}
 
-        if self.channels.len() != other.channels.len() {
-            return false;
+/// Where the chat of a channel is opened.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatClient {
+    // The popout chat of Twitch.
+    #[default]
+    Browser,
+    Chatterino,
+}
+
+/// How the viewer counts are shown, in the menu and in the notifications.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewerFormat {
+    // e.g. "12437"
+    #[default]
+    Full,
+    // e.g. "12.4k", for narrow menus.
+    Compact,
+}
+
+impl ViewerFormat {
+    pub fn format(self, viewers: u64) -> String {
+        if self == ViewerFormat::Full || viewers < 1000 {
+            return viewers.to_string();
         }
 
-        self.channels
-            .iter()
-            .zip(other.channels.iter())
-            .filter(|(a, b)| *a.name != *b.name)
-            .count()
-            == 0
+        // Rounded to one decimal, which might carry over to the next unit (e.g. 999950 is "1M").
+        let thousands = (viewers + 50) / 100;
+
+        let (tenths, unit) = if thousands < 10000 {
+            (thousands, "k")
+        } else {
+            ((viewers + 50_000) / 100_000, "M")
+        };
+
+        if tenths % 10 == 0 {
+            format!("{}{}", tenths / 10, unit)
+        } else {
+            format!("{}.{}{}", tenths / 10, tenths % 10, unit)
+        }
     }
 }
 
-pub fn migrate(config: &Arc<Mutex<State>>, new_config: State) {
-    let mut local_config = config.lock().unwrap();
+/// How the menu shows since when a channel is live.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveTimeDisplay {
+    // e.g. "live 2h 13m"
+    #[default]
+    Uptime,
+    // e.g. "since 19:05", in the local time.
+    StartTime,
+    Both,
+}
 
-    local_config.client = new_config.client.clone();
-    local_config.secret = new_config.secret.clone();
-    local_config.player = new_config.player;
-    local_config.config_file = new_config.config_file.clone();
-    local_config.notify_title_changed = new_config.notify_title_changed.clone();
+/// A time of the day, it's written as "21:00" in the config.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
 
-    // We want to keep the same player that was selected by the user in the current session.
-    // local_config.session_player = new_config.session_player;
+impl FromStr for TimeOfDay {
+    type Err = String;
 
-    // Merge the existing channel information with the new one.
-    let old_channels = local_config.channels.clone();
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Expected a time like \"21:00\", got \"{}\"", value);
 
-    local_config.channels = new_config.channels;
+        let (hour, minute) = value.trim().split_once(':').ok_or_else(invalid)?;
 
-    for channel in &mut local_config.channels {
-        for old_channel in &old_channels {
-            if channel.name == old_channel.name {
-                // Save the old data.
-                *channel = old_channel.clone();
-            }
+        let hour: u8 = hour.parse().map_err(|_| invalid())?;
+        let minute: u8 = minute.parse().map_err(|_| invalid())?;
+
+        if hour > 23 || minute > 59 {
+            return Err(invalid());
         }
+
+        Ok(TimeOfDay { hour, minute })
     }
 }
 
-fn read_state(filename: &str) -> State {
-    let file = std::fs::File::open(filename)
-        .expect("Please ensure that there's a valid secret file in the same directory.");
-    let reader = std::io::BufReader::new(file);
+impl<'a> Deserialize<'a> for TimeOfDay {
+    fn deserialize<D>(deserializer: D) -> Result<TimeOfDay, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let value = String::deserialize(deserializer)?;
 
-    let mut state: State = serde_json::from_reader(reader).expect("Valid config format.");
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
 
-    // As the 'config_file' field is not serialized, we need to set it.
-    state.config_file = String::from(filename);
+/// A notification with what the channels did during the day, see the `summary` module.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct DailySummary {
+    pub time: TimeOfDay,
+}
 
-    state
+/// Polls less often while none of the channels are live, see `twitch::PollingInterval`.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct AdaptivePolling {
+    // The updates in a row without any live channel before the interval starts to double.
+    #[serde(default = "default_adaptive_polling_after")]
+    pub after: u32,
+    // The longest interval, in seconds.
+    #[serde(default = "default_adaptive_polling_max")]
+    pub max_interval: u64,
 }
 
-pub fn read() -> State {
-    if std::env::args().len() <= 1 {
-        // Didn't receive any arguments, read the default config file.
-        return read_state(DEFAULT_CONFIG_FILE);
-    }
+fn default_title_change_grace() -> u64 {
+    10 * 60
+}
 
-    // We have one or more arguments, if they include the 'config_file' field we have to read it,
-    //  but the command line arguments have priority over the file.
-    let args: Arguments = Arguments::from_args();
+fn default_notification_gap() -> u64 {
+    3
+}
 
-    let config: State;
-    if let Some(config_file) = &args.config_file {
-        config = read_state(config_file.as_str());
-    } else {
-        config = read_state(DEFAULT_CONFIG_FILE);
-    }
+fn default_update_interval_seconds() -> u64 {
+    twitch::UPDATE_CHANNELS_TIME
+}
 
-    State {
-        client: args.client.unwrap_or(config.client),
-        secret: args.secret.unwrap_or(config.secret),
-        player: args.player.unwrap_or(config.player),
+fn default_menu_title_length() -> usize {
+    80
+}
 
-        // The session player will be migrated from the old config,
-        //  so we can safely ignore it here.
-        session_player: None,
+fn default_low_priority_every() -> u32 {
+    5
+}
 
-        config_file: args.config_file.unwrap_or(config.config_file),
-        channels: args.channels.unwrap_or(config.channels),
-        notify_title_changed: args
-            .notify_title_changed
-            .unwrap_or(config.notify_title_changed),
+fn default_adaptive_polling_after() -> u32 {
+    10
+}
+
+fn default_adaptive_polling_max() -> u64 {
+    5 * 60
+}
+
+#[derive(Clone, Debug, StructOpt)]
+#[structopt(name = "options")]
+struct Arguments {
+    #[structopt(short = "c", long = "client")]
+    client: Option<String>,
+
+    #[structopt(short = "s", long = "secret")]
+    secret: Option<Secret>,
+
+    #[structopt(short = "p", long = "player")]
+    player: Option<OpenStreamUsing>,
+
+    #[structopt(short = "f", long = "file")]
+    config_file: Option<String>,
+
+    #[structopt(short = "u", long = "channels", use_delimiter = true)]
+    channels: Option<Vec<Channel>>,
+
+    #[structopt(short = "n", long = "notify-titles", use_delimiter = true)]
+    notify_title_changed: Option<Vec<String>>,
+
+    #[structopt(long = "notify-games", use_delimiter = true)]
+    notify_game_changed: Option<Vec<String>>,
+
+    // Same as setting the "log_level" to "debug".
+    #[structopt(short = "v", long = "verbose")]
+    verbose: bool,
+
+    // Prints the channels that are live and exits, without a tray icon.
+    #[structopt(long = "check")]
+    check: bool,
+
+    // Simulates the channels instead of requesting them (see the `demo` module).
+    #[structopt(long = "demo")]
+    demo: bool,
+
+    #[structopt(long = "demo-seed")]
+    demo_seed: Option<u64>,
+
+    // Same as "update_interval_seconds".
+    #[structopt(long = "interval")]
+    update_interval_seconds: Option<u64>,
+}
+
+/// The config file, and what we know of its channels while the app runs.
+///
+/// ```
+/// use taskbar_twitch::config::{Platform, State};
+///
+/// let state: State = serde_json::from_str(
+///     r#"{"player": "browser", "channels": ["j_blow", {"name": "https://kick.com/xqc"}]}"#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(state.channels[0].platform, Platform::Twitch);
+/// // A link is on its platform, even without a "platform".
+/// assert_eq!(state.channels[1].platform, Platform::Kick);
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct State {
+    // Only needed for the Twitch channels.
+    #[serde(default)]
+    pub client: String,
+
+    #[serde(default)]
+    pub secret: Secret,
+
+    pub player: OpenStreamUsing,
+
+    #[serde(skip)]
+    pub session_player: Option<OpenStreamUsing>,
+
+    // Same as the session player, overrides "app_window" until we exit.
+    #[serde(skip)]
+    pub session_app_window: Option<bool>,
+    #[serde(skip)]
+    pub session_incognito: Option<bool>,
+
+    #[serde(skip)]
+    pub config_file: String,
+
+    // Read from the system at startup, not from the config.
+    #[serde(skip)]
+    pub start_at_login: bool,
+
+    // Set by the network thread when it gets a new token.
+    #[serde(skip)]
+    pub token: Option<TokenInfo>,
+
+    // Set by the network thread while the updates are failing.
+    #[serde(skip)]
+    pub update_failure: Option<UpdateFailure>,
+
+    // When the channels were last updated successfully.
+    #[serde(skip)]
+    pub last_update: Option<Instant>,
+
+    // Set by the update check when there's a newer release.
+    #[serde(skip)]
+    pub available_update: Option<Release>,
+
+    // Can be empty with a "channels_file".
+    #[serde(default)]
+    pub channels: Vec<Channel>,
+
+    // A text file with more channels, one per line (see `read_channels_file`).
+    #[serde(default)]
+    pub channels_file: Option<String>,
+
+    // The entries of the channel list that were dropped, with their position (e.g. '#3 "my channel": ...').
+    #[serde(skip)]
+    pub invalid_channels: Vec<String>,
+
+    #[serde(default)]
+    pub notify_title_changed: Vec<String>,
+
+    // Notifies when these channels switch to another game (or category) while they are live.
+    #[serde(default)]
+    pub notify_game_changed: Vec<String>,
+
+    // Every channel notifies when it goes live, unless it's listed here.
+    #[serde(default)]
+    pub notify_live_except: Vec<String>,
+
+    #[serde(default)]
+    pub notify_offline: Vec<String>,
+
+    // The streams of these channels are opened (with the current player) as soon as they go live.
+    #[serde(default)]
+    pub auto_open: Vec<String>,
+
+    // The channels of "auto_open" that went live since the network thread last looked, see `provider::apply_streams`.
+    #[serde(skip)]
+    pub pending_auto_open: Vec<String>,
+
+    // Path to a custom '.ico' file for the tray icon.
+    #[serde(default)]
+    pub tray_icon: Option<String>,
+
+    #[serde(default)]
+    pub on_lock: OnLock,
+
+    #[serde(default)]
+    pub metered_behavior: MeteredBehavior,
+
+    #[serde(default)]
+    pub notification_style: NotificationStyles,
+
+    #[serde(default)]
+    pub notification_duration: NotificationDurations,
+
+    // Turns off every notification (it wins over "notification_style"), the menu and the tooltip still show everything.
+    #[serde(default = "enabled")]
+    pub notifications_enabled: bool,
+
+    // The title changes of the first seconds of a stream don't notify, the streamers often fix the title right after going live.
+    #[serde(default = "default_title_change_grace")]
+    pub title_change_grace: u64,
+
+    // The seconds between the notifications of the channels, see the `notification_queue` module.
+    #[serde(default = "default_notification_gap")]
+    pub notification_gap: u64,
+
+    #[serde(default)]
+    pub log_level: LogLevel,
+
+    // Whether to notify about the channels that were already live when we didn't know (e.g. at startup).
+    #[serde(default)]
+    pub notify_already_live: bool,
+
+    #[serde(default)]
+    pub daily_summary: Option<DailySummary>,
+
+    // Shows "↑" or "↓" next to the viewers when they changed since the previous update.
+    #[serde(default)]
+    pub viewer_trend: bool,
+
+    // How often the Twitch channels are updated, see `update_interval`.
+    #[serde(default = "default_update_interval_seconds")]
+    pub update_interval_seconds: u64,
+
+    // Without it, the channels are always updated every "update_interval_seconds".
+    #[serde(default)]
+    pub adaptive_polling: Option<AdaptivePolling>,
+
+    // The channels with "priority": "low" are only updated once every this many updates.
+    #[serde(default = "default_low_priority_every")]
+    pub low_priority_every: u32,
+
+    #[serde(default)]
+    pub viewer_format: ViewerFormat,
+
+    #[serde(default)]
+    pub live_time_display: LiveTimeDisplay,
+
+    // The text of the channels in the menu, e.g. "{name} - {title} ({viewers})".
+    // Without them, the channels are shown as they always were.
+    #[serde(default)]
+    pub menu_format: Option<String>,
+    #[serde(default)]
+    pub menu_format_offline: Option<String>,
+
+    // The channels that weren't live for this many days are marked as "(inactive)", 0 never marks them.
+    #[serde(default = "default_inactive_after_days")]
+    pub inactive_after_days: u64,
+
+    // Shows the number of unseen channels on the tray icon (it's also in the tooltip).
+    #[serde(default = "enabled")]
+    pub unseen_badge: bool,
+
+    // The titles of the live channels (with their game) are cut to this many characters in the menu.
+    #[serde(default = "default_menu_title_length")]
+    pub menu_title_length: usize,
+
+    // Prefixes the first nine channels of the menu with "&1" to "&9", so they can be opened with the keyboard.
+    #[serde(default)]
+    pub menu_accelerators: bool,
+
+    // Shows if the Twitch channels are partners ("✓") or affiliates ("●") next to their names.
+    #[serde(default)]
+    pub broadcaster_badges: bool,
+
+    // Used by the Browser and Popout players instead of the default browser (e.g. '%ProgramFiles%\Chromium\chrome.exe'),
+    //  the arguments can have a "{url}" placeholder.
+    #[serde(default)]
+    pub browser_path: Option<String>,
+    #[serde(default)]
+    pub browser_args: Vec<String>,
+
+    // Opens the Browser and Popout players as a window without tabs and toolbars (e.g. "--app" for Chromium),
+    //  with an optional size (e.g. [1280, 720]).
+    #[serde(default)]
+    pub app_window: bool,
+    #[serde(default)]
+    pub app_window_size: Option<(u32, u32)>,
+
+    #[serde(default)]
+    pub chat_client: ChatClient,
+
+    // Optional, the YouTube channels are found without it but it also gives their viewers.
+    #[serde(default)]
+    pub youtube_api_key: Option<Secret>,
+
+    // Where the Twitch requests are sent, e.g. to a local mock of the API (with or without a trailing slash).
+    #[serde(default = "default_api_base_url")]
+    pub api_base_url: String,
+    #[serde(default = "default_auth_base_url")]
+    pub auth_base_url: String,
+
+    // Once a day, we look for a newer release on GitHub (see the `updates` module).
+    #[serde(default = "enabled")]
+    pub check_for_updates: bool,
+
+    // Simulates the channels, it's only read at startup (see the `demo` module).
+    #[serde(default)]
+    pub demo: bool,
+    // The same seed always gives the same simulation.
+    #[serde(default)]
+    pub demo_seed: Option<u64>,
+
+    // Shows the counters of the session in a "Debug" submenu, it's also shown with "--verbose".
+    #[serde(default)]
+    pub debug_menu: bool,
+
+    // Whether the "Update available" menu item installs the release, instead of opening its page.
+    // It's off by default, e.g. for the installs from a package manager.
+    #[serde(default)]
+    pub self_update: bool,
+
+    // The keywords to look for in the chat of some channels, while they are live (see the `chat_alerts` module).
+    #[serde(default)]
+    pub chat_alerts: Vec<ChatAlert>,
+
+    // Channels that are opened together from the "Watch groups" submenu, e.g. { "Squad": ["a", "b", "c"] }.
+    #[serde(default)]
+    pub watch_groups: BTreeMap<String, Vec<String>>,
+
+    // Notifies when a live Twitch channel has one of these tags (e.g. "DropsEnabled"), once per stream.
+    #[serde(default)]
+    pub tag_alerts: Vec<TagAlert>,
+
+    // The command of the "custom" player, e.g. "C:\tools\mpv.exe {url} --profile=twitch" (see `launch::split_command`),
+    //  "{url}" is replaced by the link to the stream (otherwise it's added at the end).
+    #[serde(default)]
+    pub custom_player: Option<String>,
+
+    // Passed to mpv as "--geometry", e.g. "1280x720+0+0" so that it doesn't cover the chat.
+    #[serde(default)]
+    pub mpv_geometry: Option<String>,
+
+    // How far back the "Watch from the start" submenu can rewind the streams, in minutes (streamlink only).
+    #[serde(default = "default_rewind_offsets")]
+    pub rewind_offsets: Vec<u64>,
+
+    // Opens the browser of the config in a private window (it can't be done with the default browser).
+    #[serde(default)]
+    pub incognito: bool,
+
+    // What the channels did since the last daily summary, kept across config changes.
+    #[serde(skip)]
+    pub activity: Activity,
+
+    // The channels that don't notify until a time (seconds since the epoch), kept between sessions
+    //  (see the `app_state` module).
+    #[serde(skip)]
+    pub snoozed: BTreeMap<String, u64>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+pub const DEFAULT_API_BASE_URL: &str = "https://api.twitch.tv/helix";
+pub const DEFAULT_AUTH_BASE_URL: &str = "https://id.twitch.tv/oauth2";
+
+fn default_inactive_after_days() -> u64 {
+    90
+}
+
+fn default_rewind_offsets() -> Vec<u64> {
+    vec![10, 30]
+}
+
+fn default_api_base_url() -> String {
+    String::from(DEFAULT_API_BASE_URL)
+}
+
+fn default_auth_base_url() -> String {
+    String::from(DEFAULT_AUTH_BASE_URL)
+}
+
+/// Appends a path to a base URL, with exactly one slash between them (e.g. "http://localhost:8080/" and "/streams").
+pub fn join_url(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// The base URLs must be absolute (e.g. "http://localhost:8080", not "localhost:8080"), the paths are appended to them.
+fn validate_base_url(field: &str, url: &str) -> Result<(), String> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed)
+            if (parsed.scheme() == "https" || parsed.scheme() == "http")
+                && !parsed.cannot_be_a_base()
+                && parsed.query().is_none() =>
+        {
+            Ok(())
+        }
+        Ok(_) => Err(format!(
+            "Invalid {} ({}): expected an http(s) URL without a query.",
+            field, url
+        )),
+        Err(e) => Err(format!(
+            "Invalid {} ({}): {}, expected an absolute URL.",
+            field, url, e
+        )),
+    }
+}
+
+fn without_scheme(name: &str) -> &str {
+    ["https://", "http://"]
+        .iter()
+        .find_map(|scheme| name.strip_prefix(scheme))
+        .unwrap_or(name)
+}
+
+// The hosts of the links to the channels, the YouTube ones are only used by `validate_youtube_channel`.
+const KICK_HOSTS: [&str; 2] = ["www.kick.com/", "kick.com/"];
+const YOUTUBE_HOSTS: [&str; 3] = ["www.youtube.com/", "m.youtube.com/", "youtube.com/"];
+
+/// The platform of a link to a channel (e.g. 'https://kick.com/name'), if it isn't on Twitch.
+fn platform_of_link(name: &str) -> Option<Platform> {
+    let name = without_scheme(name.trim()).to_lowercase();
+
+    if KICK_HOSTS.iter().any(|host| name.starts_with(host)) {
+        Some(Platform::Kick)
+    } else if YOUTUBE_HOSTS.iter().any(|host| name.starts_with(host)) {
+        Some(Platform::YouTube)
+    } else {
+        None
+    }
+}
+
+/// The name that the platforms use for a channel, which can also be a link to it (e.g. 'https://www.twitch.tv/name').
+pub fn normalize_channel_name(name: &str) -> String {
+    let name = without_scheme(name.trim().trim_end_matches('/'));
+
+    let name = ["www.twitch.tv/", "twitch.tv/"]
+        .iter()
+        .chain(KICK_HOSTS.iter())
+        .find_map(|host| name.strip_prefix(host))
+        .unwrap_or(name);
+
+    name.to_lowercase()
+}
+
+/// Normalizes a channel name, Twitch logins only have letters, digits and underscores.
+/// The Kick channels can also have dashes.
+pub fn validate_channel_name(name: &str, platform: Platform) -> Result<String, String> {
+    if platform == Platform::YouTube {
+        return validate_youtube_channel(name);
+    }
+
+    let name = normalize_channel_name(name);
+
+    if name.is_empty() {
+        return Err(String::from("the name is empty"));
+    }
+
+    let allowed =
+        |c: char| c.is_ascii_alphanumeric() || c == '_' || (platform == Platform::Kick && c == '-');
+
+    if let Some(c) = name.chars().find(|c| !allowed(*c)) {
+        return Err(format!(
+            "'{}' isn't allowed in a {} channel name",
+            c, platform
+        ));
+    }
+
+    let length = name.chars().count();
+
+    let min_length = match platform {
+        Platform::Kick => MIN_KICK_CHANNEL_NAME_LENGTH,
+        _ => MIN_CHANNEL_NAME_LENGTH,
+    };
+
+    if !(min_length..=MAX_CHANNEL_NAME_LENGTH).contains(&length) {
+        return Err(format!(
+            "the name must have between {} and {} characters",
+            min_length, MAX_CHANNEL_NAME_LENGTH
+        ));
+    }
+
+    Ok(name)
+}
+
+/// A YouTube channel is a handle (e.g. "@name", the "@" can be left out) or a channel ID, which can also be a link.
+/// Unlike the other platforms, the case is kept: the channel IDs depend on it.
+fn validate_youtube_channel(name: &str) -> Result<String, String> {
+    let name = without_scheme(name.trim().trim_end_matches('/'));
+
+    let name = YOUTUBE_HOSTS
+        .iter()
+        .find_map(|host| name.strip_prefix(host))
+        .unwrap_or(name);
+
+    // e.g. "youtube.com/@name/live", "youtube.com/channel/UC.../videos"
+    let name = name.strip_prefix("channel/").unwrap_or(name);
+    let name = name.split(['/', '?']).next().unwrap_or_default();
+
+    if name.is_empty() {
+        return Err(String::from("the name is empty"));
+    }
+
+    if youtube::is_channel_id(name) {
+        return Ok(String::from(name));
+    }
+
+    let handle = name.strip_prefix('@').unwrap_or(name);
+
+    if let Some(c) = handle
+        .chars()
+        .find(|c| !c.is_alphanumeric() && !['_', '-', '.'].contains(c))
+    {
+        return Err(format!("'{}' isn't allowed in a YouTube handle", c));
+    }
+
+    let length = handle.chars().count();
+
+    if !(youtube::MIN_HANDLE_LENGTH..=youtube::MAX_HANDLE_LENGTH).contains(&length) {
+        return Err(format!(
+            "the handle must have between {} and {} characters (or it should be a channel ID)",
+            youtube::MIN_HANDLE_LENGTH,
+            youtube::MAX_HANDLE_LENGTH
+        ));
+    }
+
+    Ok(format!("@{}", handle))
+}
+
+/// Drops the channels that their platform would never find, so that they don't end up in the requests.
+/// Returns the valid channels (normalized), and a description of each invalid one.
+fn validate_channels(channels: Vec<Channel>) -> (Vec<Channel>, Vec<String>) {
+    let mut valid = Vec::new();
+    let mut invalid = Vec::new();
+
+    for (index, mut channel) in channels.into_iter().enumerate() {
+        match validate_channel_name(&channel.name, channel.platform) {
+            Ok(name) => {
+                channel.name = name;
+                valid.push(channel);
+            }
+            Err(e) => invalid.push(format!("#{} \"{}\": {}", index + 1, channel.name, e)),
+        }
+    }
+
+    (valid, invalid)
+}
+
+/// The channels of a "channels_file": one per line (a name or a link), the empty lines and the comments
+///  (after a '#') are skipped. The invalid ones are returned with their line, e.g. 'channels.txt:3 "a b": ...'.
+/// If the file can't be read, that's the only invalid entry.
+fn read_channels_file(path: &Path) -> (Vec<Channel>, Vec<String>) {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string());
+
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => return (Vec::new(), vec![format!("{}: {}", path.display(), e)]),
+    };
+
+    let mut valid = Vec::new();
+    let mut invalid = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let entry = line.split('#').next().unwrap_or_default().trim();
+
+        if entry.is_empty() {
+            continue;
+        }
+
+        let platform = platform_of_link(entry).unwrap_or(Platform::Twitch);
+
+        match validate_channel_name(entry, platform) {
+            Ok(name) => {
+                let mut channel = Channel::from(name);
+                channel.platform = platform;
+
+                valid.push(channel);
+            }
+            Err(e) => invalid.push(format!("{}:{} \"{}\": {}", file_name, index + 1, entry, e)),
+        }
+    }
+
+    (valid, invalid)
+}
+
+// The words that show up in the placeholders (e.g. "example secret", "your_client_id").
+const PLACEHOLDER_WORDS: [&str; 7] = [
+    "example",
+    "your",
+    "placeholder",
+    "changeme",
+    "client",
+    "secret",
+    "token",
+];
+
+/// Whether a credential was never filled in, the real ones only have letters and digits.
+fn is_placeholder(value: &str) -> bool {
+    let value = value.trim().to_lowercase();
+
+    let first = match value.chars().next() {
+        Some(first) => first,
+        None => return true,
+    };
+
+    // e.g. "<client id>", "xxxxxxxx"
+    if value.starts_with('<') && value.ends_with('>') || value.chars().all(|c| c == first) {
+        return true;
+    }
+
+    let has_word = PLACEHOLDER_WORDS.iter().any(|word| value.contains(word));
+    let is_credential = value.chars().all(|c| c.is_ascii_alphanumeric());
+
+    has_word && !is_credential || PLACEHOLDER_WORDS.contains(&value.as_str())
+}
+
+/// The entries of a notify list that don't match any of the channels.
+fn unmatched_entries<'a>(channels: &[Channel], list: &'a [String]) -> Vec<&'a str> {
+    let channels: Vec<String> = channels
+        .iter()
+        .map(|channel| normalize_channel_name(&channel.name))
+        .collect();
+
+    list.iter()
+        .filter(|name| !channels.contains(&normalize_channel_name(name)))
+        .map(String::as_str)
+        .collect()
+}
+
+impl State {
+    /// Which of the credentials are empty (or still the ones from the example config), if any.
+    pub fn missing_credentials(&self) -> Option<&'static str> {
+        match (
+            is_placeholder(&self.client),
+            is_placeholder(self.secret.expose()),
+        ) {
+            (true, true) => Some("client ID and secret"),
+            (true, false) => Some("client ID"),
+            (false, true) => Some("secret"),
+            (false, false) => None,
+        }
+    }
+
+    /// The command of "custom_player", if there's one.
+    pub fn custom_player(&self) -> Option<&str> {
+        self.custom_player
+            .as_deref()
+            .map(str::trim)
+            .filter(|command| !command.is_empty())
+    }
+
+    /// The browser of the config, a name without a directory (e.g. "firefox") is looked up in the PATH.
+    pub fn browser(&self) -> Option<PathBuf> {
+        let path = self.browser_path.as_deref()?.trim();
+
+        if path.is_empty() {
+            return None;
+        }
+
+        if path.contains(['/', '\\']) {
+            Some(resolve_path(&self.config_file, path))
+        } else {
+            Some(PathBuf::from(path))
+        }
+    }
+
+    /// Whether the streams are opened as an app window, for the current session.
+    pub fn app_window(&self) -> bool {
+        self.session_app_window.unwrap_or(self.app_window)
+    }
+
+    /// Whether the browser is opened in a private window, for the current session.
+    pub fn incognito(&self) -> bool {
+        self.session_incognito.unwrap_or(self.incognito)
+    }
+
+    /// The browser of the config, if it's a path to a file that doesn't exist.
+    pub fn missing_browser(&self) -> Option<PathBuf> {
+        self.browser()
+            .filter(|browser| browser.components().count() > 1 && !browser.is_file())
+    }
+
+    /// Until the credentials are set, we don't request anything from Twitch.
+    /// They are only needed for the Twitch channels, the other platforms (and the demo) don't have credentials.
+    pub fn needs_setup(&self) -> bool {
+        !self.demo && self.has_twitch_channels() && self.missing_credentials().is_some()
+    }
+
+    pub fn has_twitch_channels(&self) -> bool {
+        self.channels
+            .iter()
+            .any(|channel| channel.platform == Platform::Twitch)
+    }
+
+    /// The placeholders of the menu formats that we don't know about, e.g. "menu_format: {viewer}".
+    pub fn unknown_menu_placeholders(&self) -> Vec<String> {
+        let formats = [
+            ("menu_format", &self.menu_format),
+            ("menu_format_offline", &self.menu_format_offline),
+        ];
+
+        formats
+            .iter()
+            .filter_map(|(name, format)| {
+                let unknown =
+                    template::unknown_placeholders(format.as_deref()?, &MENU_PLACEHOLDERS);
+
+                if unknown.is_empty() {
+                    return None;
+                }
+
+                let unknown: Vec<String> = unknown.iter().map(|u| format!("{{{}}}", u)).collect();
+
+                Some(format!("{}: {}", name, unknown.join(", ")))
+            })
+            .collect()
+    }
+
+    /// Whether we are still waiting for the first update (since startup, or since switching profiles).
+    pub fn is_loading(&self) -> bool {
+        self.last_update.is_none() && self.update_failure.is_none() && !self.needs_setup()
+    }
+
+    /// The notify lists (and "auto_open") that have entries which aren't in `channels` (e.g. a typo, or a renamed channel),
+    ///  those would never be notified, so we tell the user.
+    pub fn unmatched_notify_entries(&self) -> Vec<String> {
+        let lists = [
+            ("notify_title_changed", &self.notify_title_changed),
+            ("notify_game_changed", &self.notify_game_changed),
+            ("notify_live_except", &self.notify_live_except),
+            ("notify_offline", &self.notify_offline),
+            ("auto_open", &self.auto_open),
+        ];
+
+        lists
+            .iter()
+            .filter_map(|(list, entries)| {
+                let unmatched = unmatched_entries(&self.channels, entries);
+
+                if unmatched.is_empty() {
+                    None
+                } else {
+                    Some(format!("{}: {}", list, unmatched.join(", ")))
+                }
+            })
+            .collect()
+    }
+
+    /// The members of the watch groups that aren't in the channels, e.g. "Squad: c".
+    pub fn unmatched_group_members(&self) -> Vec<String> {
+        self.watch_groups
+            .iter()
+            .filter_map(|(group, members)| {
+                let unmatched = unmatched_entries(&self.channels, members);
+
+                if unmatched.is_empty() {
+                    None
+                } else {
+                    Some(format!("{}: {}", group, unmatched.join(", ")))
+                }
+            })
+            .collect()
+    }
+
+    /// The menu events identify the channels by name, the list might have changed since the menu was built.
+    pub fn find_channel(&self, name: &str) -> Option<&Channel> {
+        self.channels
+            .iter()
+            .find(|channel| channel.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn find_channel_mut(&mut self, name: &str) -> Option<&mut Channel> {
+        self.channels
+            .iter_mut()
+            .find(|channel| channel.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The channels that weren't live for "inactive_after_days".
+    pub fn inactive_channels(&self) -> Vec<&Channel> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        self.channels
+            .iter()
+            .filter(|channel| channel.is_inactive(self.inactive_after_days, now))
+            .collect()
+    }
+
+    pub fn unseen_count(&self) -> usize {
+        self.channels
+            .iter()
+            .filter(|channel| channel.unseen)
+            .count()
+    }
+
+    pub fn is_notification_enabled(&self, channel: &str, kind: NotificationKind) -> bool {
+        // The channel names are normalized, but not the notify lists.
+        let contains =
+            |list: &Vec<String>| list.iter().any(|name| name.eq_ignore_ascii_case(channel));
+
+        match kind {
+            NotificationKind::Live => !contains(&self.notify_live_except),
+            NotificationKind::TitleChanged => contains(&self.notify_title_changed),
+            NotificationKind::GameChanged => contains(&self.notify_game_changed),
+            NotificationKind::Offline => contains(&self.notify_offline),
+        }
+    }
+
+    /// How many seconds between the updates of the Twitch channels, not less than `twitch::MIN_UPDATE_CHANNELS_TIME`
+    ///  so that a typo doesn't hammer the API.
+    pub fn update_interval(&self) -> u64 {
+        self.update_interval_seconds
+            .max(twitch::MIN_UPDATE_CHANNELS_TIME)
+    }
+
+    pub fn is_auto_opened(&self, channel: &str) -> bool {
+        self.auto_open
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(channel))
+    }
+
+    /// How a notification should be shown, it's `Off` if the channel doesn't want this kind of notification
+    ///  (or if it's snoozed).
+    pub fn notification_style(&self, channel: &str, kind: NotificationKind) -> NotificationStyle {
+        if !self.is_notification_enabled(channel, kind) || self.snoozed_until(channel).is_some() {
+            return NotificationStyle::Off;
+        }
+
+        match kind {
+            NotificationKind::Live => self.notification_style.live,
+            NotificationKind::TitleChanged => self.notification_style.title_changed,
+            NotificationKind::GameChanged => self.notification_style.game_changed,
+            NotificationKind::Offline => self.notification_style.offline,
+        }
+    }
+
+    /// When the snooze of a channel ends (seconds since the epoch), if it's snoozed.
+    pub fn snoozed_until(&self, channel: &str) -> Option<u64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        self.snoozed
+            .get(channel)
+            .copied()
+            .filter(|until| *until > now)
+    }
+
+    pub fn toggle_notification(&mut self, channel: &str, kind: NotificationKind) {
+        let list = match kind {
+            NotificationKind::Live => &mut self.notify_live_except,
+            NotificationKind::TitleChanged => &mut self.notify_title_changed,
+            NotificationKind::GameChanged => &mut self.notify_game_changed,
+            NotificationKind::Offline => &mut self.notify_offline,
+        };
+
+        if list.iter().any(|name| name.eq_ignore_ascii_case(channel)) {
+            list.retain(|name| !name.eq_ignore_ascii_case(channel));
+        } else {
+            list.push(String::from(channel));
+        }
+    }
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        if self.client != other.client || self.secret != other.secret {
+            return false;
+        }
+
+        if self.player != other.player || self.config_file != other.config_file {
+            return false;
+        }
+
+        if self.notify_title_changed != other.notify_title_changed
+            || self.notify_game_changed != other.notify_game_changed
+            || self.notify_live_except != other.notify_live_except
+            || self.notify_offline != other.notify_offline
+            || self.auto_open != other.auto_open
+        {
+            return false;
+        }
+
+        if self.tray_icon != other.tray_icon
+            || self.on_lock != other.on_lock
+            || self.metered_behavior != other.metered_behavior
+        {
+            return false;
+        }
+
+        if self.notification_style != other.notification_style
+            || self.notification_duration != other.notification_duration
+            || self.log_level != other.log_level
+            || self.notify_already_live != other.notify_already_live
+            || self.daily_summary != other.daily_summary
+            || self.viewer_trend != other.viewer_trend
+            || self.adaptive_polling != other.adaptive_polling
+            || self.update_interval_seconds != other.update_interval_seconds
+            || self.notification_gap != other.notification_gap
+            || self.title_change_grace != other.title_change_grace
+            || self.notifications_enabled != other.notifications_enabled
+            || self.low_priority_every != other.low_priority_every
+            || self.menu_accelerators != other.menu_accelerators
+            || self.menu_title_length != other.menu_title_length
+            || self.broadcaster_badges != other.broadcaster_badges
+            || self.unseen_badge != other.unseen_badge
+            || self.inactive_after_days != other.inactive_after_days
+            || self.viewer_format != other.viewer_format
+            || self.live_time_display != other.live_time_display
+            || self.menu_format != other.menu_format
+            || self.menu_format_offline != other.menu_format_offline
+            || self.browser_path != other.browser_path
+            || self.custom_player != other.custom_player
+            || self.browser_args != other.browser_args
+            || self.app_window != other.app_window
+            || self.app_window_size != other.app_window_size
+            || self.incognito != other.incognito
+            || self.mpv_geometry != other.mpv_geometry
+            || self.rewind_offsets != other.rewind_offsets
+            || self.chat_client != other.chat_client
+            || self.chat_alerts != other.chat_alerts
+            || self.tag_alerts != other.tag_alerts
+            || self.watch_groups != other.watch_groups
+            || self.youtube_api_key != other.youtube_api_key
+            || self.api_base_url != other.api_base_url
+            || self.auth_base_url != other.auth_base_url
+            || self.check_for_updates != other.check_for_updates
+            || self.self_update != other.self_update
+            || self.debug_menu != other.debug_menu
+        {
+            return false;
+        }
+
+        if self.channels.len() != other.channels.len()
+            || self.invalid_channels != other.invalid_channels
+            || self.channels_file != other.channels_file
+        {
+            return false;
+        }
+
+        self.channels
+            .iter()
+            .zip(other.channels.iter())
+            .filter(|(a, b)| {
+                a.name != b.name
+                    || a.platform != b.platform
+                    || a.note != b.note
+                    || a.also_open_chat != b.also_open_chat
+                    || a.priority != b.priority
+            })
+            .count()
+            == 0
+    }
+}
+
+pub fn migrate(config: &Arc<Mutex<State>>, new_config: State) {
+    let mut local_config = config.lock_or_recover();
+
+    local_config.client = new_config.client.clone();
+    local_config.secret = new_config.secret.clone();
+    local_config.player = new_config.player;
+    local_config.config_file = new_config.config_file.clone();
+    local_config.notify_title_changed = new_config.notify_title_changed.clone();
+    local_config.notify_game_changed = new_config.notify_game_changed.clone();
+    local_config.notify_live_except = new_config.notify_live_except.clone();
+    local_config.notify_offline = new_config.notify_offline.clone();
+    local_config.auto_open = new_config.auto_open.clone();
+    local_config.tray_icon = new_config.tray_icon.clone();
+    local_config.on_lock = new_config.on_lock;
+    local_config.metered_behavior = new_config.metered_behavior;
+    local_config.notification_style = new_config.notification_style;
+    local_config.notification_duration = new_config.notification_duration;
+    local_config.log_level = new_config.log_level;
+    local_config.notify_already_live = new_config.notify_already_live;
+    local_config.daily_summary = new_config.daily_summary;
+    local_config.viewer_trend = new_config.viewer_trend;
+    local_config.adaptive_polling = new_config.adaptive_polling;
+    local_config.update_interval_seconds = new_config.update_interval_seconds;
+    local_config.notification_gap = new_config.notification_gap;
+    local_config.title_change_grace = new_config.title_change_grace;
+    local_config.notifications_enabled = new_config.notifications_enabled;
+    local_config.low_priority_every = new_config.low_priority_every;
+    local_config.menu_accelerators = new_config.menu_accelerators;
+    local_config.menu_title_length = new_config.menu_title_length;
+    local_config.broadcaster_badges = new_config.broadcaster_badges;
+    local_config.unseen_badge = new_config.unseen_badge;
+    local_config.inactive_after_days = new_config.inactive_after_days;
+    local_config.viewer_format = new_config.viewer_format;
+    local_config.live_time_display = new_config.live_time_display;
+    local_config.menu_format = new_config.menu_format.clone();
+    local_config.menu_format_offline = new_config.menu_format_offline.clone();
+    local_config.browser_path = new_config.browser_path.clone();
+    local_config.custom_player = new_config.custom_player.clone();
+    local_config.browser_args = new_config.browser_args.clone();
+    local_config.app_window = new_config.app_window;
+    local_config.app_window_size = new_config.app_window_size;
+    local_config.incognito = new_config.incognito;
+    local_config.mpv_geometry = new_config.mpv_geometry.clone();
+    local_config.rewind_offsets = new_config.rewind_offsets.clone();
+    local_config.chat_client = new_config.chat_client;
+    local_config.chat_alerts = new_config.chat_alerts.clone();
+    local_config.tag_alerts = new_config.tag_alerts.clone();
+    local_config.watch_groups = new_config.watch_groups.clone();
+    local_config.youtube_api_key = new_config.youtube_api_key.clone();
+    local_config.api_base_url = new_config.api_base_url.clone();
+    local_config.auth_base_url = new_config.auth_base_url.clone();
+    local_config.check_for_updates = new_config.check_for_updates;
+    local_config.self_update = new_config.self_update;
+    local_config.debug_menu = new_config.debug_menu;
+    local_config.invalid_channels = new_config.invalid_channels.clone();
+    local_config.channels_file = new_config.channels_file.clone();
+
+    // We want to keep the same player that was selected by the user in the current session.
+    // local_config.session_player = new_config.session_player;
+    // local_config.session_app_window = new_config.session_app_window;
+    // local_config.session_incognito = new_config.session_incognito;
+
+    // Merge the existing channel information with the new one.
+    let old_channels = local_config.channels.clone();
+
+    local_config.channels = new_config.channels;
+
+    for channel in &mut local_config.channels {
+        for old_channel in &old_channels {
+            if channel.name == old_channel.name && channel.platform == old_channel.platform {
+                // Save the old data, except what comes from the config.
+                *channel = Channel {
+                    note: channel.note.take(),
+                    also_open_chat: channel.also_open_chat,
+                    priority: channel.priority,
+                    ..old_channel.clone()
+                };
+            }
+        }
+    }
+}
+
+/// Writes the notification preferences back into the config file, leaving every other field as it was.
+pub fn save_notifications(state: &State) -> std::io::Result<()> {
+    update_file(&state.config_file, |object| {
+        object.insert(
+            String::from("notify_title_changed"),
+            serde_json::json!(state.notify_title_changed),
+        );
+        object.insert(
+            String::from("notify_game_changed"),
+            serde_json::json!(state.notify_game_changed),
+        );
+        object.insert(
+            String::from("notify_live_except"),
+            serde_json::json!(state.notify_live_except),
+        );
+        object.insert(
+            String::from("notify_offline"),
+            serde_json::json!(state.notify_offline),
+        );
+    })
+}
+
+/// Writes "notifications_enabled" (from the tray menu) back into the config file.
+pub fn save_notifications_enabled(state: &State) -> std::io::Result<()> {
+    update_file(&state.config_file, |object| {
+        object.insert(
+            String::from("notifications_enabled"),
+            serde_json::json!(state.notifications_enabled),
+        );
+    })
+}
+
+/// What happens to an entry of the channel lists, see `edit_channel_entries`.
+enum EntryEdit {
+    Keep,
+    Rename(String),
+    Remove,
+}
+
+/// The channel of an entry of the config file, as it is in the state (e.g. a link becomes a name).
+fn entry_channel(entry: &serde_json::Value) -> Option<(String, Platform)> {
+    let (name, platform) = match entry {
+        serde_json::Value::String(name) => (name.as_str(), None),
+        serde_json::Value::Object(channel) => (
+            channel.get("name")?.as_str()?,
+            match channel.get("platform") {
+                Some(platform) => Some(Platform::deserialize(platform).ok()?),
+                None => None,
+            },
+        ),
+        _ => return None,
+    };
+
+    let platform = platform
+        .or_else(|| platform_of_link(name))
+        .unwrap_or(Platform::Twitch);
+
+    Some((validate_channel_name(name, platform).ok()?, platform))
+}
+
+/// Renames or removes some channels in a config file (and in its "channels_file"), the files are first
+///  copied to "<file>.bak". The notify lists follow the channels of Twitch.
+fn edit_channel_entries(
+    state: &State,
+    edit: impl Fn(&str, Platform) -> EntryEdit,
+) -> std::io::Result<()> {
+    let config_file = state.config_file.as_str();
+
+    std::fs::copy(config_file, format!("{}.bak", config_file))?;
+
+    if let Some(channels_file) = &state.channels_file {
+        let path = resolve_path(config_file, channels_file);
+        let text = std::fs::read_to_string(&path)?;
+
+        let mut changed = false;
+
+        // The comments of the renamed lines are kept.
+        let lines: Vec<String> = text
+            .lines()
+            .filter_map(|line| {
+                let (entry, comment) = match line.split_once('#') {
+                    Some((entry, comment)) => (entry, Some(comment)),
+                    None => (line, None),
+                };
+
+                let entry = entry.trim();
+                let platform = platform_of_link(entry).unwrap_or(Platform::Twitch);
+
+                let edit = match validate_channel_name(entry, platform) {
+                    Ok(name) => edit(&name, platform),
+                    Err(_) => EntryEdit::Keep,
+                };
+
+                match edit {
+                    EntryEdit::Keep => Some(String::from(line)),
+                    EntryEdit::Rename(new_name) => {
+                        changed = true;
+
+                        Some(match comment {
+                            Some(comment) => format!("{} #{}", new_name, comment),
+                            None => new_name,
+                        })
+                    }
+                    EntryEdit::Remove => {
+                        changed = true;
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        if changed {
+            let mut backup = path.clone().into_os_string();
+            backup.push(".bak");
+
+            std::fs::copy(&path, backup)?;
+
+            let mut temporary = path.clone().into_os_string();
+            temporary.push(".tmp");
+
+            std::fs::write(&temporary, lines.join("\n") + "\n")?;
+            std::fs::rename(&temporary, &path)?;
+        }
+    }
+
+    update_file(config_file, |object| {
+        if let Some(channels) = object
+            .get_mut("channels")
+            .and_then(serde_json::Value::as_array_mut)
+        {
+            channels.retain_mut(|entry| {
+                let edit = match entry_channel(entry) {
+                    Some((name, platform)) => edit(&name, platform),
+                    None => EntryEdit::Keep,
+                };
+
+                match (edit, entry) {
+                    (EntryEdit::Keep, _) => true,
+                    (EntryEdit::Remove, _) => false,
+                    (EntryEdit::Rename(new_name), serde_json::Value::Object(channel)) => {
+                        channel.insert(String::from("name"), serde_json::json!(new_name));
+                        true
+                    }
+                    (EntryEdit::Rename(new_name), entry) => {
+                        *entry = serde_json::json!(new_name);
+                        true
+                    }
+                }
+            });
+        }
+
+        for list in [
+            "notify_title_changed",
+            "notify_game_changed",
+            "notify_live_except",
+            "notify_offline",
+            "auto_open",
+        ] {
+            if let Some(names) = object
+                .get_mut(list)
+                .and_then(serde_json::Value::as_array_mut)
+            {
+                names.retain_mut(|name| {
+                    let edit = match name.as_str() {
+                        Some(entry) => edit(&normalize_channel_name(entry), Platform::Twitch),
+                        None => EntryEdit::Keep,
+                    };
+
+                    match edit {
+                        EntryEdit::Keep => true,
+                        EntryEdit::Rename(new_name) => {
+                            *name = serde_json::json!(new_name);
+                            true
+                        }
+                        EntryEdit::Remove => false,
+                    }
+                });
+            }
+        }
+    })
+}
+
+/// Renames a Twitch channel in a config file (in the channels and in the notify lists) and in its "channels_file",
+///  the files are first copied to "<file>.bak".
+pub fn rename_channel(state: &State, old_name: &str, new_name: &str) -> std::io::Result<()> {
+    edit_channel_entries(state, |name, platform| {
+        if platform == Platform::Twitch && name == old_name {
+            EntryEdit::Rename(String::from(new_name))
+        } else {
+            EntryEdit::Keep
+        }
+    })
+}
+
+/// Removes some channels from a config file and from its "channels_file", the same way as `rename_channel`.
+pub fn remove_channels(state: &State, channels: &[Channel]) -> std::io::Result<()> {
+    edit_channel_entries(state, |name, platform| {
+        let removed = channels
+            .iter()
+            .any(|channel| channel.name == name && channel.platform == platform);
+
+        if removed {
+            EntryEdit::Remove
+        } else {
+            EntryEdit::Keep
+        }
+    })
+}
+
+/// Changes some fields of a config file, leaving every other field as it was.
+fn update_file(
+    config_file: &str,
+    update: impl FnOnce(&mut serde_json::Map<String, serde_json::Value>),
+) -> std::io::Result<()> {
+    let file = std::fs::File::open(config_file)?;
+    let reader = std::io::BufReader::new(file);
+
+    let mut value: serde_json::Value = serde_json::from_reader(reader)?;
+
+    let object = value.as_object_mut().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "Expected an object.")
+    })?;
+
+    update(object);
+
+    write_file(config_file, &value)
+}
+
+/// Atomically replaces the contents of a config file: the new contents are written to a temporary file
+///  which is then renamed over the old one, so the hot-reload never sees a half-written file.
+pub fn write_file<P: AsRef<Path>>(filename: P, value: &serde_json::Value) -> std::io::Result<()> {
+    let filename = filename.as_ref();
+    let temporary_file = format!("{}.tmp", filename.display());
+
+    {
+        let file = std::fs::File::create(&temporary_file)?;
+        let mut writer = std::io::BufWriter::new(file);
+
+        // Keep the same indentation as the example config.
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut writer, formatter);
+        value.serialize(&mut serializer)?;
+
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+    }
+
+    std::fs::rename(&temporary_file, filename)
+}
+
+fn read_state(filename: &str) -> Result<State, String> {
+    let file = std::fs::File::open(filename).map_err(|e| {
+        format!(
+            "Please ensure that there's a valid secret file in the same directory ({}: {}).",
+            filename, e
+        )
+    })?;
+    let reader = std::io::BufReader::new(file);
+
+    let mut state: State = serde_json::from_reader(reader)
+        .map_err(|e| format!("Invalid config format ({}: {}).", filename, e))?;
+
+    // As the 'config_file' field is not serialized, we need to set it.
+    state.config_file = String::from(filename);
+
+    validate_base_url("api_base_url", &state.api_base_url)
+        .and_then(|_| validate_base_url("auth_base_url", &state.auth_base_url))
+        .map_err(|e| format!("{} ({})", e, filename))?;
+
+    Ok(state)
+}
+
+/// Logs the notify lists that have unknown channels, and shows them in a notification.
+pub fn warn_unmatched_notify_entries(state: &State) {
+    let unmatched = state.unmatched_notify_entries();
+
+    if unmatched.is_empty() {
+        return;
+    }
+
+    log::warn!(
+        "Unknown channels in the notify lists ({})",
+        unmatched.join("; ")
+    );
+
+    send_notification(
+        "Some of the notify lists have unknown channels.",
+        &unmatched.join("\n"),
+    );
+}
+
+/// Logs the members of the watch groups that aren't in the channels, and shows them in a notification.
+pub fn warn_unmatched_group_members(state: &State) {
+    let unmatched = state.unmatched_group_members();
+
+    if unmatched.is_empty() {
+        return;
+    }
+
+    log::warn!(
+        "Unknown channels in the watch groups ({})",
+        unmatched.join("; ")
+    );
+
+    send_notification(
+        "Some of the watch groups have unknown channels.",
+        &unmatched.join("\n"),
+    );
+}
+
+/// Logs the placeholders of the menu formats that we don't know about, and shows them in a notification.
+/// They are shown as they are in the menu.
+pub fn warn_unknown_placeholders(state: &State) {
+    let unknown = state.unknown_menu_placeholders();
+
+    if unknown.is_empty() {
+        return;
+    }
+
+    log::warn!(
+        "Unknown placeholders in the menu formats ({})",
+        unknown.join("; ")
+    );
+
+    send_notification(
+        "Some of the menu formats have unknown placeholders.",
+        &unknown.join("\n"),
+    );
+}
+
+/// Logs the browser of the config if it doesn't exist, and shows it in a notification.
+/// The default browser is used until it's fixed.
+pub fn warn_missing_browser(state: &State) {
+    let browser = match state.missing_browser() {
+        Some(browser) => browser,
+        None => return,
+    };
+
+    log::warn!(
+        "The browser of {} doesn't exist ({})",
+        state.config_file,
+        browser.display()
+    );
+
+    send_notification(
+        "The browser of the config doesn't exist, the default one will be used.",
+        &browser.display().to_string(),
+    );
+}
+
+/// Logs the channels that were dropped from the list, and shows them in a notification.
+pub fn warn_invalid_channels(state: &State) {
+    if state.invalid_channels.is_empty() {
+        return;
+    }
+
+    log::warn!(
+        "Ignored the invalid channels in {} ({})",
+        state.config_file,
+        state.invalid_channels.join("; ")
+    );
+
+    send_notification(
+        "Some of the channels are invalid and were ignored.",
+        &state.invalid_channels.join("\n"),
+    );
+}
+
+/// Whether we were started with `--check`, see the `headless` module.
+pub fn check_requested() -> bool {
+    Arguments::from_args().check
+}
+
+/// Reads the config file that should be used at startup: the one that was provided by the arguments,
+///  otherwise the last profile that was selected, otherwise the default one.
+pub fn read() -> State {
+    try_read().unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_read() -> Result<State, String> {
+    let args: Arguments = Arguments::from_args();
+
+    let config_file = args
+        .config_file
+        .or_else(|| {
+            app_state::read()
+                .profile
+                .filter(|profile| Path::new(profile).is_file())
+        })
+        .unwrap_or_else(|| String::from(DEFAULT_CONFIG_FILE));
+
+    try_read_file(&config_file)
+}
+
+/// Reads a specific config file, the command line arguments have priority over the file.
+pub fn read_file(config_file: &str) -> State {
+    try_read_file(config_file).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_read_file(config_file: &str) -> Result<State, String> {
+    let args: Arguments = Arguments::from_args();
+
+    let config = read_state(config_file)?;
+
+    let (mut channels, mut invalid_channels) =
+        validate_channels(args.channels.clone().unwrap_or(config.channels));
+
+    // The channels of the command line replace both lists.
+    if let (None, Some(channels_file)) = (&args.channels, &config.channels_file) {
+        let (file_channels, invalid) =
+            read_channels_file(&resolve_path(config_file, channels_file));
+
+        invalid_channels.extend(invalid);
+
+        for channel in file_channels {
+            let listed = channels
+                .iter()
+                .any(|other| other.name == channel.name && other.platform == channel.platform);
+
+            if !listed {
+                channels.push(channel);
+            }
+        }
+    }
+
+    Ok(State {
+        client: args.client.unwrap_or(config.client),
+        secret: args.secret.unwrap_or(config.secret),
+        player: args.player.unwrap_or(config.player),
+
+        // The session player will be migrated from the old config,
+        //  so we can safely ignore it here.
+        session_player: None,
+        session_app_window: None,
+        session_incognito: None,
+
+        config_file: config.config_file,
+
+        // Same as the session player, these are kept from the old config.
+        start_at_login: false,
+        token: None,
+        update_failure: None,
+        last_update: None,
+        available_update: None,
+
+        channels,
+        channels_file: config.channels_file,
+        invalid_channels,
+        notify_title_changed: args
+            .notify_title_changed
+            .unwrap_or(config.notify_title_changed),
+        notify_game_changed: args
+            .notify_game_changed
+            .unwrap_or(config.notify_game_changed),
+        notify_live_except: config.notify_live_except,
+        notify_offline: config.notify_offline,
+        auto_open: config.auto_open,
+        pending_auto_open: Vec::new(),
+        tray_icon: config.tray_icon,
+        on_lock: config.on_lock,
+        metered_behavior: config.metered_behavior,
+        notification_style: config.notification_style,
+        notification_duration: config.notification_duration,
+        log_level: if args.verbose {
+            LogLevel::Debug
+        } else {
+            config.log_level
+        },
+        notify_already_live: config.notify_already_live,
+        daily_summary: config.daily_summary,
+        viewer_trend: config.viewer_trend,
+        adaptive_polling: config.adaptive_polling,
+        update_interval_seconds: args
+            .update_interval_seconds
+            .unwrap_or(config.update_interval_seconds),
+        notification_gap: config.notification_gap,
+        title_change_grace: config.title_change_grace,
+        notifications_enabled: config.notifications_enabled,
+        low_priority_every: config.low_priority_every,
+        menu_accelerators: config.menu_accelerators,
+        menu_title_length: config.menu_title_length,
+        broadcaster_badges: config.broadcaster_badges,
+        unseen_badge: config.unseen_badge,
+        inactive_after_days: config.inactive_after_days,
+        viewer_format: config.viewer_format,
+        live_time_display: config.live_time_display,
+        menu_format: config.menu_format,
+        menu_format_offline: config.menu_format_offline,
+        browser_path: config.browser_path,
+        custom_player: config.custom_player,
+        browser_args: config.browser_args,
+        app_window: config.app_window,
+        app_window_size: config.app_window_size,
+        incognito: config.incognito,
+        mpv_geometry: config.mpv_geometry,
+        rewind_offsets: config.rewind_offsets,
+        chat_client: config.chat_client,
+        chat_alerts: config.chat_alerts,
+        tag_alerts: config.tag_alerts,
+        watch_groups: config.watch_groups,
+        youtube_api_key: config.youtube_api_key,
+        api_base_url: config.api_base_url,
+        auth_base_url: config.auth_base_url,
+        check_for_updates: config.check_for_updates,
+        self_update: config.self_update,
+        debug_menu: config.debug_menu,
+        demo: args.demo || config.demo,
+        demo_seed: args.demo_seed.or(config.demo_seed),
+        activity: Activity::default(),
+        snoozed: BTreeMap::new(),
+    })
+}
+
+/// Expands the environment variables (e.g. '%USERPROFILE%\icon.ico') in a path,
+///  relative paths are resolved from the directory of the config file.
+pub fn resolve_path(config_file: &str, path: &str) -> PathBuf {
+    let mut expanded = String::new();
+    let mut rest = path;
+
+    while let Some(start) = rest.find('%') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        match rest.find('%') {
+            Some(end) => {
+                let name = &rest[..end];
+
+                // Unknown variables are kept as they are.
+                match std::env::var(name) {
+                    Ok(value) => expanded.push_str(&value),
+                    Err(_) => expanded.push_str(format!("%{}%", name).as_str()),
+                }
+
+                rest = &rest[end + 1..];
+            }
+            None => {
+                expanded.push('%');
+            }
+        }
+    }
+    expanded.push_str(rest);
+
+    let path = PathBuf::from(expanded);
+
+    if path.is_relative() {
+        if let Some(directory) = Path::new(config_file).parent() {
+            return directory.join(path);
+        }
+    }
+
+    path
+}
+
+/// The available profiles as (name, config file): the default config file,
+///  followed by every config file in the profiles directory.
+pub fn profiles() -> Vec<(String, String)> {
+    let args: Arguments = Arguments::from_args();
+
+    let default_file = args
+        .config_file
+        .unwrap_or_else(|| String::from(DEFAULT_CONFIG_FILE));
+
+    let mut profiles = vec![(String::from("Default"), default_file)];
+
+    if let Ok(entries) = std::fs::read_dir(PROFILES_DIRECTORY) {
+        let mut files: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension() == Some("json".as_ref()))
+            .collect();
+
+        files.sort();
+
+        for file in files {
+            if let Some(name) = file.file_stem() {
+                profiles.push((
+                    name.to_string_lossy().to_string(),
+                    file.display().to_string(),
+                ));
+            }
+        }
+    }
+
+    profiles
+}
+
+/// Replaces the current config with the one of another profile.
+/// Unlike `migrate`, none of the channel information is kept, so that it's fetched again.
+pub fn switch(config: &Arc<Mutex<State>>, new_config: State) {
+    let mut local_config = config.lock_or_recover();
+
+    let session_player = local_config.session_player;
+    let session_app_window = local_config.session_app_window;
+    let session_incognito = local_config.session_incognito;
+    let start_at_login = local_config.start_at_login;
+    let token = local_config.token.take();
+    let available_update = local_config.available_update.take();
+    let (demo, demo_seed) = (local_config.demo, local_config.demo_seed);
+    let activity = std::mem::take(&mut local_config.activity);
+
+    *local_config = new_config;
+
+    local_config.session_player = session_player;
+    local_config.session_app_window = session_app_window;
+    local_config.session_incognito = session_incognito;
+    local_config.start_at_login = start_at_login;
+    local_config.token = token;
+    local_config.available_update = available_update;
+    // The simulation was started with the first profile.
+    local_config.demo = demo;
+    local_config.demo_seed = demo_seed;
+    local_config.activity = activity;
+}
+
+/// A state for the tests, `extra` is added to a minimal config (e.g. its "channels").
+#[cfg(test)]
+pub(crate) fn test_state(extra: serde_json::Value) -> State {
+    let mut config = serde_json::json!({ "player": "browser" });
+
+    if let (Some(config), serde_json::Value::Object(extra)) = (config.as_object_mut(), extra) {
+        config.extend(extra);
+    }
+
+    serde_json::from_value(config).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_names() {
+        // The name, its platform, and the normalized name (or the start of the error).
+        let cases = [
+            ("j_blow", Platform::Twitch, Ok("j_blow")),
+            ("  J_Blow ", Platform::Twitch, Ok("j_blow")),
+            (
+                "https://www.twitch.tv/j_blow/",
+                Platform::Twitch,
+                Ok("j_blow"),
+            ),
+            ("twitch.tv/Museun", Platform::Twitch, Ok("museun")),
+            ("abcd", Platform::Twitch, Ok("abcd")),
+            (
+                "a234567890123456789012345",
+                Platform::Twitch,
+                Ok("a234567890123456789012345"),
+            ),
+            ("abc", Platform::Twitch, Err("the name must have")),
+            (
+                "a2345678901234567890123456",
+                Platform::Twitch,
+                Err("the name must have"),
+            ),
+            ("abc", Platform::Kick, Ok("abc")),
+            ("ab", Platform::Kick, Err("the name must have")),
+            ("j-blow", Platform::Twitch, Err("'-' isn't allowed")),
+            ("j-blow", Platform::Kick, Ok("j-blow")),
+            ("j blow", Platform::Twitch, Err("' ' isn't allowed")),
+            ("jöblow", Platform::Twitch, Err("'ö' isn't allowed")),
+            ("j.blow!", Platform::Twitch, Err("'.' isn't allowed")),
+            ("", Platform::Twitch, Err("the name is empty")),
+            ("   ", Platform::Twitch, Err("the name is empty")),
+            ("", Platform::YouTube, Err("the name is empty")),
+        ];
+
+        for (name, platform, expected) in cases {
+            match (validate_channel_name(name, platform), expected) {
+                (Ok(result), Ok(expected)) => assert_eq!(result, expected, "{:?}", name),
+                (Err(e), Err(expected)) => assert!(e.starts_with(expected), "{:?}: {}", name, e),
+                (result, _) => panic!("{:?} ({}): {:?}", name, platform, result),
+            }
+        }
+    }
+
+    #[test]
+    fn secrets_are_masked() {
+        let secret = Secret::new(String::from("abcdefghijklmnop1234"));
+
+        assert_eq!(secret.to_string(), "••••1234");
+        assert_eq!(format!("{:?}", secret), "Secret(••••1234)");
+        assert_eq!(secret.expose(), "abcdefghijklmnop1234");
+
+        // Short values would be almost entirely visible.
+        assert_eq!(Secret::new(String::from("short")).to_string(), "••••");
+
+        let state = test_state(serde_json::json!({ "secret": "abcdefghijklmnop1234" }));
+
+        assert!(!format!("{:?}", state).contains("abcdefghijklmnop"));
+    }
+
+    #[test]
+    fn compact_viewers() {
+        let cases = [
+            (0, "0"),
+            (999, "999"),
+            (1000, "1k"),
+            (1049, "1k"),
+            (1050, "1.1k"),
+            (12_437, "12.4k"),
+            (9_950, "10k"),
+            (999_949, "999.9k"),
+            (999_950, "1M"),
+            (1_000_000, "1M"),
+            (1_250_000, "1.3M"),
+        ];
+
+        for (viewers, expected) in cases {
+            assert_eq!(
+                ViewerFormat::Compact.format(viewers),
+                expected,
+                "{}",
+                viewers
+            );
+        }
+
+        assert_eq!(ViewerFormat::Full.format(12_437), "12437");
+    }
+
+    #[test]
+    fn entry_channels() {
+        use serde_json::json;
+
+        let cases = [
+            (json!("j_blow"), Some(("j_blow", Platform::Twitch))),
+            (json!("https://kick.com/xqc"), Some(("xqc", Platform::Kick))),
+            (
+                json!({"name": "J_Blow"}),
+                Some(("j_blow", Platform::Twitch)),
+            ),
+            // Like the channels of the state, a link is on its platform even without a "platform".
+            (
+                json!({"name": "https://kick.com/xqc"}),
+                Some(("xqc", Platform::Kick)),
+            ),
+            (
+                json!({"name": "xqc", "platform": "kick"}),
+                Some(("xqc", Platform::Kick)),
+            ),
+            (json!({"name": "xqc", "platform": "unknown"}), None),
+            (json!({"platform": "kick"}), None),
+            (json!(5), None),
+        ];
+
+        for (entry, expected) in cases {
+            let channel = entry_channel(&entry);
+
+            assert_eq!(
+                channel
+                    .as_ref()
+                    .map(|(name, platform)| (name.as_str(), *platform)),
+                expected,
+                "{}",
+                entry
+            );
+
+            // The name is only normalized once the config is read.
+            if let Some((_, platform)) = channel {
+                assert_eq!(
+                    Channel::deserialize(&entry).unwrap().platform,
+                    platform,
+                    "{}",
+                    entry
+                );
+            }
+        }
     }
 }
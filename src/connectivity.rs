@@ -0,0 +1,138 @@
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::config::{MeteredBehavior, State};
+use crate::platform;
+use crate::twitch::Message;
+
+pub const CHECK_CONNECTIVITY_TIME: Duration = Duration::from_secs(2);
+
+// While the connection is metered (with "metered_behavior": "reduce"), the channels aren't updated more often than this.
+pub const METERED_UPDATE_TIME: Duration = Duration::from_secs(5 * 60);
+
+// Only checked when the connection changes, not before every update.
+static METERED: AtomicBool = AtomicBool::new(false);
+
+// VPNs (and some Wi-Fi networks) reconnect a few times in a row, we wait until the connection is stable.
+pub const CONNECTIVITY_DEBOUNCE_TIME: Duration = Duration::from_secs(5);
+
+/// What the updates should do right now, it's always `Normal` while the connection isn't metered (or in the demo).
+pub fn metered_behavior(state: &State) -> MeteredBehavior {
+    if METERED.load(Ordering::Relaxed) && !state.demo {
+        state.metered_behavior
+    } else {
+        MeteredBehavior::Normal
+    }
+}
+
+/// Returns whether the connection became metered (or stopped being metered).
+fn check_metered() -> bool {
+    let metered = platform::is_metered_connection();
+    let changed = METERED.swap(metered, Ordering::Relaxed) != metered;
+
+    if changed {
+        info!(
+            "The connection is {}",
+            if metered {
+                "metered"
+            } else {
+                "not metered anymore"
+            }
+        );
+    }
+
+    changed
+}
+
+/// Checks if there's a route to the internet, connecting an UDP socket doesn't send any packets.
+fn is_connected() -> bool {
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| socket.connect("1.1.1.1:80"))
+        .is_ok()
+}
+
+/// Notifies the network thread when we are connected again (e.g. after plugging in a cable),
+///  so that it doesn't have to wait for the next update.
+pub struct Watcher {
+    thread: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Watcher {
+    pub fn spawn(tx: mpsc::Sender<Message>) -> Watcher {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        check_metered();
+
+        let network_tx = tx.clone();
+        let result = platform::listen_for_network_changes(move || {
+            if check_metered() {
+                network_tx.send(Message::MeteredChanged).ok();
+            }
+        });
+
+        if let Err(e) = result {
+            warn!("Couldn't listen for the network changes: {}", e);
+        }
+
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || watch(tx, thread_stop));
+
+        Watcher {
+            thread: Some(thread),
+            stop,
+        }
+    }
+
+    /// Waits for the thread to finish, it's woken up so that it doesn't wait for the next check.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            thread.join().ok();
+        }
+    }
+}
+
+/// Runs until `stop` is set.
+fn watch(tx: mpsc::Sender<Message>, stop: Arc<AtomicBool>) {
+    let mut was_connected = is_connected();
+
+    // When the connection comes back, it has to stay up for a while before we notify.
+    let mut connected_since: Option<Instant> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::park_timeout(CHECK_CONNECTIVITY_TIME);
+
+        if !is_connected() {
+            was_connected = false;
+            connected_since = None;
+            continue;
+        }
+
+        if was_connected {
+            continue;
+        }
+
+        let since = *connected_since.get_or_insert_with(Instant::now);
+
+        if since.elapsed() >= CONNECTIVITY_DEBOUNCE_TIME {
+            was_connected = true;
+            connected_since = None;
+
+            // The connection might not be the same one (e.g. the phone instead of the Wi-Fi).
+            check_metered();
+
+            if tx.send(Message::Reconnected).is_err() {
+                break;
+            }
+        }
+    }
+}
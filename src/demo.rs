@@ -0,0 +1,196 @@
+//! The demo mode ("--demo", or "demo": true), the channels are simulated instead of requested from the platforms.
+//! Every update is a step of the simulation: the channels go live, change their titles and go offline
+//!  a lot more often than they would, so that the notifications and the menu can be tried out.
+//! The simulation only depends on its seed (and on the channels), the seed is logged at startup to reproduce it.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::provider::{Stream, StreamProvider};
+
+// How often the simulation moves forward.
+pub const UPDATE_CHANNELS_TIME: Duration = Duration::from_secs(10);
+
+// The odds of each change at every step, in percent.
+const GO_LIVE_CHANCE: u64 = 20;
+const GO_OFFLINE_CHANCE: u64 = 10;
+const CHANGE_TITLE_CHANCE: u64 = 15;
+const CHANGE_TAGS_CHANCE: u64 = 10;
+const CHANGE_GAME_CHANCE: u64 = 5;
+
+const MAX_VIEWERS: u64 = 50_000;
+
+const TITLES: [&str; 10] = [
+    "Speedrunning until I get the world record",
+    "Chill stream, come hang out",
+    "Ranked grind !discord",
+    "First playthrough (no spoilers please)",
+    "24 hour charity stream",
+    "Building a compiler from scratch",
+    "Tournament qualifiers",
+    "Just chatting",
+    "Retro games marathon",
+    "New update day!",
+];
+
+const GAMES: [&str; 5] = [
+    "Just Chatting",
+    "Software and Game Development",
+    "Celeste",
+    "Chess",
+    "Retro",
+];
+
+// Some of the streams have them, to try the "tag_alerts".
+const TAGS: [&str; 4] = ["DropsEnabled", "Charity", "English", "Speedrun"];
+
+/// A seed for when none was given, different for each session.
+pub fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Xorshift, enough for a simulation and always the same for a seed.
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        // Zero would stay zero forever.
+        Random(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Between 0 and `max` (excluded).
+    fn below(&mut self, max: u64) -> u64 {
+        self.next() % max
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// The simulated streams of a platform, one step per update.
+pub struct DemoProvider {
+    random: Random,
+    // The channels that are live, by name.
+    live: HashMap<String, Stream>,
+}
+
+impl DemoProvider {
+    pub fn new(seed: u64) -> Self {
+        DemoProvider {
+            random: Random::new(seed),
+            live: HashMap::new(),
+        }
+    }
+
+    fn title(&mut self) -> String {
+        String::from(TITLES[self.random.below(TITLES.len() as u64) as usize])
+    }
+
+    fn game(&mut self) -> Option<String> {
+        Some(String::from(
+            GAMES[self.random.below(GAMES.len() as u64) as usize],
+        ))
+    }
+
+    /// Each tag is there half of the time.
+    fn tags(&mut self) -> Vec<String> {
+        TAGS.iter()
+            .filter(|_| self.random.chance(50))
+            .map(|tag| String::from(*tag))
+            .collect()
+    }
+
+    /// Moves a channel forward, the channels are stepped in the order they are in the config.
+    fn step(&mut self, channel: &str) {
+        match self.live.get(channel).cloned() {
+            None => {
+                if self.random.chance(GO_LIVE_CHANCE) {
+                    // Some of them have been live for a while (e.g. to see the "since" of the notifications).
+                    let uptime = Duration::from_secs(self.random.below(3 * 60 * 60));
+
+                    let stream = Stream {
+                        name: String::from(channel),
+                        title: self.title(),
+                        viewers: 1 + self.random.below(MAX_VIEWERS),
+                        started_at: SystemTime::now().checked_sub(uptime),
+                        tags: self.tags(),
+                        game: self.game(),
+                    };
+
+                    self.live.insert(String::from(channel), stream);
+                }
+            }
+            Some(mut stream) => {
+                if self.random.chance(GO_OFFLINE_CHANCE) {
+                    self.live.remove(channel);
+                    return;
+                }
+
+                if self.random.chance(CHANGE_TITLE_CHANCE) {
+                    stream.title = self.title();
+                }
+
+                if self.random.chance(CHANGE_TAGS_CHANCE) {
+                    stream.tags = self.tags();
+                }
+
+                if self.random.chance(CHANGE_GAME_CHANCE) {
+                    stream.game = self.game();
+                }
+
+                // Up to 10% more or less.
+                let change = stream.viewers / 10 + 1;
+                stream.viewers = (stream.viewers + self.random.below(2 * change))
+                    .saturating_sub(change)
+                    .max(1);
+
+                self.live.insert(String::from(channel), stream);
+            }
+        }
+    }
+}
+
+impl StreamProvider for DemoProvider {
+    type Error = Infallible;
+
+    async fn authenticate(&mut self, _client: &reqwest::Client) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    async fn fetch_streams(
+        &mut self,
+        _client: &reqwest::Client,
+        channels: &[String],
+    ) -> Result<Vec<Stream>, Infallible> {
+        // The channels that were removed from the config are forgotten.
+        self.live.retain(|name, _| channels.contains(name));
+
+        for channel in channels {
+            self.step(channel);
+        }
+
+        Ok(channels
+            .iter()
+            .filter_map(|channel| self.live.get(channel).cloned())
+            .collect())
+    }
+
+    fn watch_url(channel: &str) -> String {
+        let mut result = String::from("https://twitch.tv/");
+        result.push_str(channel);
+
+        result
+    }
+}
@@ -0,0 +1,457 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use std::path::Path;
+use std::time::Instant;
+
+use crate::config::State;
+use crate::lock::RecoverLock;
+use crate::metrics::METRICS;
+
+// How many error lines we keep around for the diagnostics.
+pub const MAX_ERRORS: usize = 20;
+
+// Longer error lines are cut in the reports.
+pub const MAX_ERROR_LENGTH: usize = 300;
+
+// Browsers (and GitHub) start rejecting URLs that are much longer than this.
+pub const MAX_REPORT_URL_LENGTH: usize = 2000;
+
+pub const NEW_ISSUE_URL: &str = "https://github.com/HazyAlex/taskbar-twitch/issues/new";
+
+static ERRORS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// When the previous session crashed, if it did.
+static LAST_CRASH: Mutex<Option<String>> = Mutex::new(None);
+
+// The full version of the last daily summary, the notification only has a few lines.
+static LAST_SUMMARY: Mutex<Option<String>> = Mutex::new(None);
+
+// The notifications that weren't shown, with "notifications_enabled": false.
+static DISABLED_NOTIFICATIONS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// The background tasks that keep panicking (e.g. "network"), they are still restarted.
+static FAILING_TASKS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// Keeps track of an error, so that it can be included in the diagnostics later (it's also written to the log).
+pub fn record_error(message: &str) {
+    log::error!("{}", message);
+
+    let mut errors = ERRORS.lock_or_recover();
+
+    if errors.len() == MAX_ERRORS {
+        errors.pop_front();
+    }
+
+    errors.push_back(String::from(message));
+}
+
+/// They are kept like the errors, so that the debug info still tells what would have been shown.
+pub fn record_disabled_notification(title: &str, text: &str) {
+    let line = format!("{}: {}", title, text.replace('\n', " "));
+
+    log::info!("Notification not shown (they are disabled): {}", line);
+
+    let mut notifications = DISABLED_NOTIFICATIONS.lock_or_recover();
+
+    if notifications.len() == MAX_ERRORS {
+        notifications.pop_front();
+    }
+
+    notifications.push_back(line);
+}
+
+pub fn record_crash(crashed_at: &str) {
+    *LAST_CRASH.lock_or_recover() = Some(String::from(crashed_at));
+}
+
+pub fn record_failing_task(name: &'static str) {
+    let mut tasks = FAILING_TASKS.lock_or_recover();
+
+    if !tasks.contains(&name) {
+        tasks.push(name);
+    }
+}
+
+/// A task that ran for a while after being restarted isn't failing anymore.
+pub fn clear_failing_task(name: &'static str) {
+    FAILING_TASKS.lock_or_recover().retain(|task| *task != name);
+}
+
+pub fn failing_tasks() -> Vec<&'static str> {
+    FAILING_TASKS.lock_or_recover().clone()
+}
+
+pub fn record_summary(report: &str) {
+    *LAST_SUMMARY.lock_or_recover() = Some(String::from(report));
+}
+
+/// The last `count` errors, from oldest to newest.
+pub fn recent_errors(count: usize) -> Vec<String> {
+    let errors = ERRORS.lock_or_recover();
+
+    errors
+        .iter()
+        .skip(errors.len().saturating_sub(count))
+        .cloned()
+        .collect()
+}
+
+/// Removes the credentials (and the channel names, unless requested) from a line.
+pub fn sanitize(line: &str, state: &State, include_channels: bool) -> String {
+    let mut result = String::from(line);
+
+    for sensitive in [state.client.as_str(), state.secret.expose()] {
+        if !sensitive.is_empty() {
+            result = result.replace(sensitive, "[redacted]");
+        }
+    }
+
+    // We never store the token itself, but it could still show up in an error message.
+    while let Some(start) = result.find("Bearer ") {
+        let token_start = start + "Bearer ".len();
+        let token_end = result[token_start..]
+            .find(|c: char| c.is_whitespace() || c == '"' || c == '\'')
+            .map(|end| token_start + end)
+            .unwrap_or(result.len());
+
+        result.replace_range(start..token_end, "[redacted token]");
+    }
+
+    if !include_channels {
+        for name in channel_names(state) {
+            result = replace_ignore_ascii_case(&result, name, "[channel]");
+        }
+    }
+
+    result
+}
+
+/// Every name of a channel in the config, including the ones that are only in a notify list (e.g. a typo).
+/// The longest first, so that "j_blow" doesn't only hide the start of "j_blow_2".
+fn channel_names(state: &State) -> Vec<&str> {
+    let lists = [
+        &state.notify_title_changed,
+        &state.notify_game_changed,
+        &state.notify_live_except,
+        &state.notify_offline,
+        &state.auto_open,
+    ];
+
+    let mut names: Vec<&str> = state
+        .channels
+        .iter()
+        .map(|channel| channel.name.as_str())
+        .chain(lists.iter().copied().flatten().map(String::as_str))
+        .chain(state.watch_groups.values().flatten().map(String::as_str))
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+    names.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+
+    names
+}
+
+/// The errors have the names as the platform writes them (e.g. "J_Blow"), not as they are in the config.
+fn replace_ignore_ascii_case(text: &str, from: &str, to: &str) -> String {
+    // The lowercase has the same length, so its positions are also the ones of the text.
+    let lowercase = text.to_ascii_lowercase();
+    let from = from.to_ascii_lowercase();
+
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (start, _) in lowercase.match_indices(&from) {
+        result.push_str(&text[last..start]);
+        result.push_str(to);
+        last = start + from.len();
+    }
+
+    result.push_str(&text[last..]);
+
+    result
+}
+
+/// A list (in markdown) of everything that helps with a bug report, nothing in it is sensitive.
+/// Shared by the bug reports and "Copy debug info".
+pub fn diagnostics(
+    state: &State,
+    app_version: &str,
+    os_version: &str,
+    include_channels: bool,
+) -> String {
+    let player = match state.session_player {
+        Some(session_player) => format!("{} (config: {})", session_player, state.player),
+        None => state.player.to_string(),
+    };
+
+    // The rest of the path could have the name of the user.
+    let config_file = Path::new(&state.config_file)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let ago = |instant: Instant| format!("{} seconds ago", instant.elapsed().as_secs());
+
+    let mut result = String::new();
+
+    result.push_str(format!("- {}\n", app_version).as_str());
+    result.push_str(format!("- OS: {}\n", os_version).as_str());
+    result.push_str(format!("- Config file: {}\n", config_file).as_str());
+    result.push_str(format!("- Player: {}\n", player).as_str());
+    result.push_str(format!("- Channels: {}\n", state.channels.len()).as_str());
+    result.push_str(format!("- Update interval: {} seconds\n", state.update_interval()).as_str());
+
+    let last_update = state.last_update.map(ago);
+    result.push_str(
+        format!(
+            "- Last update: {}\n",
+            last_update.as_deref().unwrap_or("never")
+        )
+        .as_str(),
+    );
+
+    if let Some(failure) = &state.update_failure {
+        result.push_str(
+            format!(
+                "- Updates failing since: {} ({})\n",
+                ago(failure.since),
+                failure.reason
+            )
+            .as_str(),
+        );
+    }
+
+    let token = match &state.token {
+        Some(token) => format!(
+            "obtained {} seconds ago",
+            token.obtained_at.elapsed().as_secs()
+        ),
+        None => String::from("not obtained"),
+    };
+    result.push_str(format!("- Token: {}\n", token).as_str());
+
+    result.push_str(format!("- Notification style: {:?}\n", state.notification_style).as_str());
+
+    if !state.notifications_enabled {
+        result.push_str("- Notifications: disabled, the last ones that weren't shown:\n");
+
+        for notification in DISABLED_NOTIFICATIONS.lock_or_recover().iter() {
+            result.push_str(
+                format!(
+                    "  - {}\n",
+                    sanitize_error(notification, state, include_channels)
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    for line in METRICS.lines() {
+        result.push_str(format!("- {}\n", line).as_str());
+    }
+
+    let failing_tasks = failing_tasks();
+
+    if !failing_tasks.is_empty() {
+        result.push_str(format!("- Failing tasks: {}\n", failing_tasks.join(", ")).as_str());
+    }
+
+    if let Some(crashed_at) = LAST_CRASH.lock_or_recover().as_ref() {
+        result
+            .push_str(format!("- Last crash: {} (please attach crash.log)\n", crashed_at).as_str());
+    }
+
+    if include_channels {
+        let names: Vec<&str> = state.channels.iter().map(|c| c.name.as_str()).collect();
+
+        result.push_str(format!("- Channel names: {}\n", names.join(", ")).as_str());
+    }
+
+    result
+}
+
+/// Sanitizes an error line, very long ones are cut so that the report stays readable.
+fn sanitize_error(error: &str, state: &State, include_channels: bool) -> String {
+    let error = sanitize(error, state, include_channels);
+
+    match error.char_indices().nth(MAX_ERROR_LENGTH) {
+        Some((end, _)) => format!("{}...", &error[..end]),
+        None => error,
+    }
+}
+
+/// Builds the body of a bug report, every error line is sanitized.
+pub fn report_body(
+    state: &State,
+    app_version: &str,
+    os_version: &str,
+    errors: &[String],
+    include_channels: bool,
+) -> String {
+    let mut body = String::new();
+
+    body.push_str("### Description\n\n");
+    body.push_str("<!-- Please describe the problem here. -->\n\n");
+
+    body.push_str("### Diagnostics\n\n");
+    body.push_str(diagnostics(state, app_version, os_version, include_channels).as_str());
+
+    if !errors.is_empty() {
+        body.push_str("\n### Recent errors\n\n```\n");
+
+        for error in errors {
+            body.push_str(sanitize_error(error, state, include_channels).as_str());
+            body.push('\n');
+        }
+
+        body.push_str("```\n");
+    }
+
+    body
+}
+
+/// The text that "Copy debug info" puts in the clipboard, e.g. to paste in a message when asking for help.
+pub fn debug_info(
+    state: &State,
+    app_version: &str,
+    os_version: &str,
+    errors: &[String],
+    include_channels: bool,
+) -> String {
+    let mut result = diagnostics(state, app_version, os_version, include_channels);
+
+    if !errors.is_empty() {
+        result.push_str("- Recent errors:\n");
+
+        for error in errors {
+            result.push_str(
+                format!("  - {}\n", sanitize_error(error, state, include_channels)).as_str(),
+            );
+        }
+    }
+
+    if let Some(summary) = LAST_SUMMARY.lock_or_recover().as_ref() {
+        result.push_str("- Last daily summary:\n");
+
+        for line in summary.lines() {
+            result.push_str(
+                format!("  - {}\n", sanitize(line.trim(), state, include_channels)).as_str(),
+            );
+        }
+    }
+
+    result
+}
+
+/// The URL to open a new issue with the report already filled in.
+/// The oldest errors are dropped until the URL is short enough.
+pub fn report_url(
+    state: &State,
+    app_version: &str,
+    os_version: &str,
+    errors: &[String],
+    include_channels: bool,
+) -> String {
+    let mut errors = errors;
+
+    loop {
+        let body = report_body(state, app_version, os_version, errors, include_channels);
+
+        let url = reqwest::Url::parse_with_params(NEW_ISSUE_URL, &[("body", body)])
+            .expect("Valid issue URL.");
+
+        if url.as_str().len() <= MAX_REPORT_URL_LENGTH || errors.is_empty() {
+            return url.to_string();
+        }
+
+        errors = &errors[1..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::test_state;
+    use crate::twitch::TokenInfo;
+
+    const CLIENT: &str = "clientidclientid1234";
+    const SECRET: &str = "secretsecretsecret5678";
+    const TOKEN: &str = "tokentokentoken9012";
+
+    fn state() -> State {
+        let mut state = test_state(serde_json::json!({
+            "client": CLIENT,
+            "secret": SECRET,
+            "channels": ["j_blow", "j_blow_2"],
+            "notify_offline": ["museun"],
+        }));
+
+        state.token = Some(TokenInfo {
+            obtained_at: Instant::now(),
+            expires_in: None,
+        });
+
+        state
+    }
+
+    #[test]
+    fn the_credentials_are_never_in_the_reports() {
+        let state = state();
+
+        let errors = [
+            format!("Invalid client secret: {} ({})", SECRET, CLIENT),
+            format!(
+                "Request failed: {{\"Authorization\": \"Bearer {}\"}}",
+                TOKEN
+            ),
+            format!("Unauthorized (Bearer {})", TOKEN),
+        ];
+
+        for include_channels in [false, true] {
+            let reports = [
+                report_body(&state, "Version 1.0", "Linux", &errors, include_channels),
+                debug_info(&state, "Version 1.0", "Linux", &errors, include_channels),
+                report_url(&state, "Version 1.0", "Linux", &errors, include_channels),
+            ];
+
+            for report in reports {
+                for sensitive in [CLIENT, SECRET, TOKEN] {
+                    assert!(!report.contains(sensitive), "{} in {}", sensitive, report);
+                }
+
+                assert_eq!(report.contains("j_blow"), include_channels, "{}", report);
+            }
+        }
+    }
+
+    #[test]
+    fn sanitize_keeps_the_rest_of_the_line() {
+        let state = state();
+
+        assert_eq!(
+            sanitize(
+                &format!("j_blow: Bearer {} was rejected", TOKEN),
+                &state,
+                false
+            ),
+            "[channel]: [redacted token] was rejected"
+        );
+    }
+
+    #[test]
+    fn channels_are_hidden_whatever_their_case() {
+        let state = state();
+
+        assert_eq!(
+            sanitize("J_Blow, j_blow_2 and Museun are live", &state, false),
+            "[channel], [channel] and [channel] are live"
+        );
+        assert_eq!(
+            sanitize("J_Blow, j_blow_2 and Museun are live", &state, true),
+            "J_Blow, j_blow_2 and Museun are live"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! The modes that run without a tray icon (e.g. `--check`), they print to stdout/stderr and exit.
+//! Scripts can tell what happened from the exit code.
+
+use std::sync::{Arc, Mutex};
+
+use crate::config;
+use crate::config::Platform;
+use crate::kick::{KickError, KickProvider};
+use crate::lock::RecoverLock;
+use crate::provider;
+use crate::provider::StreamProvider;
+use crate::twitch;
+use crate::twitch::{TwitchError, TwitchProvider};
+use crate::youtube::{YouTubeError, YouTubeProvider};
+
+// At least one channel is live.
+pub const EXIT_LIVE: i32 = 0;
+pub const EXIT_NOTHING_LIVE: i32 = 1;
+// The config file is missing or invalid (or a platform rejected one of the channels).
+pub const EXIT_INVALID_CONFIG: i32 = 2;
+pub const EXIT_AUTH_FAILED: i32 = 3;
+pub const EXIT_NETWORK_ERROR: i32 = 4;
+
+fn exit_code(e: &TwitchError) -> i32 {
+    match e {
+        TwitchError::Unauthorized(_) => EXIT_AUTH_FAILED,
+        TwitchError::ApiError { .. } => EXIT_INVALID_CONFIG,
+        TwitchError::Network(_)
+        | TwitchError::Timeout
+        | TwitchError::RateLimited { .. }
+        | TwitchError::Decode(_)
+        | TwitchError::ServiceUnavailable { .. } => EXIT_NETWORK_ERROR,
+    }
+}
+
+fn kick_exit_code(e: &KickError) -> i32 {
+    match e {
+        KickError::NotFound(_) => EXIT_INVALID_CONFIG,
+        _ => EXIT_NETWORK_ERROR,
+    }
+}
+
+// An invalid API key is rejected with a 400.
+fn youtube_exit_code(e: &YouTubeError) -> i32 {
+    match e {
+        YouTubeError::NotFound(_) => EXIT_INVALID_CONFIG,
+        YouTubeError::Rejected(status) if status.is_client_error() => EXIT_INVALID_CONFIG,
+        _ => EXIT_NETWORK_ERROR,
+    }
+}
+
+/// Prints the channels that are live (one per line), returns the exit code.
+pub async fn check() -> i32 {
+    let state = match config::try_read() {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return EXIT_INVALID_CONFIG;
+        }
+    };
+
+    let has_twitch_channels = state.has_twitch_channels();
+
+    if let Some(missing) = state.missing_credentials().filter(|_| has_twitch_channels) {
+        eprintln!(
+            "error: the Twitch {} isn't set in {}",
+            missing, state.config_file
+        );
+        return EXIT_INVALID_CONFIG;
+    }
+
+    for invalid in &state.invalid_channels {
+        eprintln!("warning: ignored the invalid channel {}", invalid);
+    }
+
+    for unmatched in state.unmatched_notify_entries() {
+        eprintln!("warning: unknown channels in {}", unmatched);
+    }
+
+    for unknown in state.unknown_menu_placeholders() {
+        eprintln!("warning: unknown placeholders in {}", unknown);
+    }
+
+    let state = Arc::new(Mutex::new(state));
+    let client = reqwest::Client::new();
+
+    let mut twitch = TwitchProvider::new(state.clone());
+
+    if has_twitch_channels {
+        if let Err(e) = twitch.authenticate(&client).await {
+            eprintln!("error: {}", e);
+            return exit_code(&e);
+        }
+    }
+
+    // We don't know which channels were live before, so there aren't any notifications (and if the config
+    //  asks for them, summarizing only returns them).
+    if let Err(e) = twitch::update_channels(&client, &mut twitch, &state, 0, true).await {
+        eprintln!("error: {}", e);
+        return exit_code(&e);
+    }
+
+    if let Err(e) = update_platform(KickProvider, &client, &state, Platform::Kick).await {
+        eprintln!("error: {}", e);
+        return kick_exit_code(&e);
+    }
+
+    let youtube = YouTubeProvider::new(state.clone());
+
+    if let Err(e) = update_platform(youtube, &client, &state, Platform::YouTube).await {
+        eprintln!("error: {}", e);
+        return youtube_exit_code(&e);
+    }
+
+    let state = state.lock_or_recover();
+
+    let mut live = 0;
+
+    for channel in state.channels.iter().filter(|channel| channel.is_online()) {
+        live += 1;
+
+        println!(
+            "{} - {} ({} viewers)",
+            channel.name,
+            channel.title.as_deref().unwrap_or_default(),
+            channel.viewers.unwrap_or_default()
+        );
+    }
+
+    if live == 0 {
+        EXIT_NOTHING_LIVE
+    } else {
+        EXIT_LIVE
+    }
+}
+
+/// Updates the channels of another platform than Twitch, if there are any.
+async fn update_platform<P: StreamProvider>(
+    mut provider: P,
+    client: &reqwest::Client,
+    state: &Arc<Mutex<config::State>>,
+    platform: Platform,
+) -> Result<(), P::Error> {
+    let channels = provider::channels_of(state, platform);
+
+    if channels.is_empty() {
+        return Ok(());
+    }
+
+    let streams = provider.fetch_streams(client, &channels).await?;
+    provider::apply_streams(state, platform, &channels, &streams, true);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use reqwest::StatusCode;
+
+    #[test]
+    fn twitch_exit_codes() {
+        let cases = [
+            (
+                TwitchError::Unauthorized(String::from("invalid client")),
+                EXIT_AUTH_FAILED,
+            ),
+            (
+                TwitchError::ApiError {
+                    status: StatusCode::BAD_REQUEST,
+                    message: String::from("Malformed query params."),
+                },
+                EXIT_INVALID_CONFIG,
+            ),
+            (TwitchError::Timeout, EXIT_NETWORK_ERROR),
+            (TwitchError::RateLimited { reset: None }, EXIT_NETWORK_ERROR),
+            (
+                TwitchError::Decode(String::from("no 'data'")),
+                EXIT_NETWORK_ERROR,
+            ),
+            (
+                TwitchError::ServiceUnavailable {
+                    status: StatusCode::SERVICE_UNAVAILABLE,
+                },
+                EXIT_NETWORK_ERROR,
+            ),
+        ];
+
+        for (e, expected) in cases {
+            assert_eq!(exit_code(&e), expected, "{}", e);
+        }
+    }
+
+    #[test]
+    fn other_platforms_exit_codes() {
+        let not_found = String::from("j_blow");
+
+        assert_eq!(
+            kick_exit_code(&KickError::NotFound(not_found.clone())),
+            EXIT_INVALID_CONFIG
+        );
+        assert_eq!(
+            kick_exit_code(&KickError::Rejected(StatusCode::FORBIDDEN)),
+            EXIT_NETWORK_ERROR
+        );
+        assert_eq!(
+            kick_exit_code(&KickError::Decode(String::new())),
+            EXIT_NETWORK_ERROR
+        );
+
+        assert_eq!(
+            youtube_exit_code(&YouTubeError::NotFound(not_found)),
+            EXIT_INVALID_CONFIG
+        );
+        assert_eq!(
+            youtube_exit_code(&YouTubeError::Rejected(StatusCode::BAD_REQUEST)),
+            EXIT_INVALID_CONFIG
+        );
+        assert_eq!(
+            youtube_exit_code(&YouTubeError::Rejected(StatusCode::SERVICE_UNAVAILABLE)),
+            EXIT_NETWORK_ERROR
+        );
+        assert_eq!(
+            youtube_exit_code(&YouTubeError::Decode(String::new())),
+            EXIT_NETWORK_ERROR
+        );
+    }
+
+    // They are documented in the README, scripts depend on them.
+    #[test]
+    fn documented_exit_codes() {
+        assert_eq!(
+            [
+                EXIT_LIVE,
+                EXIT_NOTHING_LIVE,
+                EXIT_INVALID_CONFIG,
+                EXIT_AUTH_FAILED,
+                EXIT_NETWORK_ERROR,
+            ],
+            [0, 1, 2, 3, 4]
+        );
+    }
+}
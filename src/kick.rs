@@ -0,0 +1,166 @@
+//! The channels on Kick, their status is public so there aren't any credentials.
+//! Kick doesn't have a request for several channels, each one is requested on its own.
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use log::debug;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::logging;
+use crate::provider::{Stream, StreamProvider};
+
+pub const UPDATE_CHANNELS_TIME: Duration = Duration::from_secs(60);
+
+/// Everything that can go wrong when talking to Kick.
+#[derive(Debug)]
+pub enum KickError {
+    // Couldn't reach Kick (e.g. we are offline).
+    Network(reqwest::Error),
+    // The channel doesn't exist.
+    NotFound(String),
+    // Kick (or the protection in front of it) rejected the request, e.g. too many requests.
+    Rejected(StatusCode),
+    // The response isn't what we expected.
+    Decode(String),
+}
+
+impl Display for KickError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KickError::Network(e) => write!(f, "Couldn't reach Kick: {}", e),
+            KickError::NotFound(channel) => {
+                write!(f, "The Kick channel {} doesn't exist.", channel)
+            }
+            KickError::Rejected(status) => write!(f, "Kick rejected the request ({}).", status),
+            KickError::Decode(message) => write!(f, "Invalid response from Kick: {}", message),
+        }
+    }
+}
+
+impl From<reqwest::Error> for KickError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            KickError::Decode(e.to_string())
+        } else {
+            KickError::Network(e)
+        }
+    }
+}
+
+/// The fields that we use of "api/v2/channels/<name>".
+#[derive(Debug, Deserialize)]
+pub struct ChannelResponse {
+    pub slug: String,
+    // Only while the channel is live.
+    pub livestream: Option<LivestreamResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LivestreamResponse {
+    #[serde(default)]
+    pub session_title: Option<String>,
+    #[serde(default)]
+    pub viewer_count: u64,
+    // In UTC, e.g. "2023-05-14 18:03:27", the older streams only have the second one.
+    #[serde(default)]
+    pub start_time: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub categories: Vec<CategoryResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CategoryResponse {
+    pub name: String,
+}
+
+impl ChannelResponse {
+    pub fn stream(&self) -> Option<Stream> {
+        let livestream = self.livestream.as_ref()?;
+
+        Some(Stream {
+            name: self.slug.clone(),
+            title: livestream
+                .session_title
+                .as_deref()
+                .map(str::trim)
+                .filter(|title| !title.is_empty())
+                .unwrap_or("Unknown title")
+                .to_string(),
+            viewers: livestream.viewer_count,
+            started_at: livestream
+                .start_time
+                .as_deref()
+                .or(livestream.created_at.as_deref())
+                .and_then(parse_time),
+            tags: Vec::new(),
+            game: livestream
+                .categories
+                .first()
+                .map(|category| category.name.clone()),
+        })
+    }
+}
+
+/// Kick doesn't write its times like Twitch, e.g. "2023-05-14 18:03:27" instead of "2023-05-14T18:03:27Z".
+fn parse_time(value: &str) -> Option<std::time::SystemTime> {
+    logging::parse_time(&format!("{}Z", value.trim().replacen(' ', "T", 1)))
+}
+
+/// The public API of Kick, the one its website uses.
+pub struct KickProvider;
+
+impl StreamProvider for KickProvider {
+    type Error = KickError;
+
+    async fn authenticate(&mut self, _client: &reqwest::Client) -> Result<(), KickError> {
+        Ok(())
+    }
+
+    async fn fetch_streams(
+        &mut self,
+        client: &reqwest::Client,
+        channels: &[String],
+    ) -> Result<Vec<Stream>, KickError> {
+        let mut streams = Vec::new();
+
+        for channel in channels {
+            let url = format!("https://kick.com/api/v2/channels/{}", channel);
+
+            debug!("Requesting the Kick channel: {}", url);
+
+            let response = client
+                .get(url)
+                .header("Accept", "application/json")
+                .send()
+                .await?;
+
+            let status = response.status();
+
+            if status == StatusCode::NOT_FOUND {
+                return Err(KickError::NotFound(channel.clone()));
+            }
+
+            if !status.is_success() {
+                return Err(KickError::Rejected(status));
+            }
+
+            let response: ChannelResponse = serde_json::from_str(&response.text().await?)
+                .map_err(|e| KickError::Decode(e.to_string()))?;
+
+            streams.extend(response.stream());
+        }
+
+        Ok(streams)
+    }
+
+    fn watch_url(channel: &str) -> String {
+        let mut result = String::from("https://kick.com/");
+        result.push_str(channel);
+
+        result
+    }
+}
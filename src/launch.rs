@@ -0,0 +1,582 @@
+//! Opens the streams with the player that was selected, the browser of the config is used
+//!  instead of the default one when there's one.
+//! The menu starts them with `in_background`, so that a slow player (or shell) doesn't block the tray.
+
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, OnceLock};
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::config::{Channel, ChatClient, OpenStreamUsing, Platform, State};
+use crate::platform;
+use crate::platform::send_notification;
+use crate::template;
+
+// Between the streams of a watch group, so that the players don't all start at once.
+pub const GROUP_LAUNCH_GAP: Duration = Duration::from_secs(1);
+
+type Launch = Box<dyn FnOnce() + Send>;
+
+// The thread that starts what the menu opens, one after the other.
+static LAUNCHER: OnceLock<mpsc::Sender<Launch>> = OnceLock::new();
+
+// The notifications about the browsers without an app mode (or a private mode) are only shown once.
+static APP_WINDOW_UNSUPPORTED_SHOWN: AtomicBool = AtomicBool::new(false);
+static INCOGNITO_UNSUPPORTED_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Starts something (e.g. a stream) on the launcher thread, after what was asked before it,
+///  so that two clicks in a row still open in order. Its failures are shown as notifications, as usual.
+pub fn in_background(launch: impl FnOnce() + Send + 'static) {
+    let launcher = LAUNCHER.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Launch>();
+
+        std::thread::spawn(move || {
+            for launch in receiver {
+                // The panic is already reported by the hook, the next launches still have to start.
+                std::panic::catch_unwind(AssertUnwindSafe(launch)).ok();
+            }
+        });
+
+        sender
+    });
+
+    launcher.send(Box::new(launch)).ok();
+}
+
+/// The browsers that we know the arguments of, from the name of their executable.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BrowserFamily {
+    // Chrome, Chromium, Brave, Vivaldi, Opera, ...
+    Chromium,
+    Edge,
+    Firefox,
+    Other,
+}
+
+impl BrowserFamily {
+    pub fn of(browser: &Path) -> BrowserFamily {
+        let name = browser
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match name.as_str() {
+            "msedge" | "microsoft-edge" | "microsoft-edge-stable" => BrowserFamily::Edge,
+            "firefox" | "firefox-esr" | "librewolf" | "waterfox" => BrowserFamily::Firefox,
+            "chrome"
+            | "google-chrome"
+            | "google-chrome-stable"
+            | "chromium"
+            | "chromium-browser"
+            | "brave"
+            | "brave-browser"
+            | "vivaldi"
+            | "opera" => BrowserFamily::Chromium,
+            _ => BrowserFamily::Other,
+        }
+    }
+
+    /// Whether it can open a window without tabs and toolbars ("--app").
+    pub fn has_app_mode(self) -> bool {
+        matches!(self, BrowserFamily::Chromium | BrowserFamily::Edge)
+    }
+
+    /// The argument that opens a private window, it goes before the URL.
+    pub fn private_window_argument(self) -> Option<&'static str> {
+        match self {
+            BrowserFamily::Chromium => Some("--incognito"),
+            BrowserFamily::Edge => Some("--inprivate"),
+            BrowserFamily::Firefox => Some("-private-window"),
+            BrowserFamily::Other => None,
+        }
+    }
+}
+
+/// The arguments that come before the ones of the config, for the options of the current session.
+fn session_arguments(config: &State, browser: &Path) -> Vec<String> {
+    if !config.incognito() {
+        return Vec::new();
+    }
+
+    match BrowserFamily::of(browser).private_window_argument() {
+        Some(argument) => vec![String::from(argument)],
+        None => {
+            warn_incognito_unsupported(&format!(
+                "We don't know how to open {} in a private window.",
+                browser.display()
+            ));
+
+            Vec::new()
+        }
+    }
+}
+
+fn warn_incognito_unsupported(reason: &str) {
+    warn!("Ignored the private window option: {}", reason);
+
+    if !INCOGNITO_UNSUPPORTED_SHOWN.swap(true, Ordering::Relaxed) {
+        send_notification("The streams can't be opened in a private window.", reason);
+    }
+}
+
+/// Opens the live channels of a watch group one after the other, then tells which ones weren't opened
+///  (e.g. "2 of 3 opened, c is offline").
+/// It's called from another thread, as it waits between them.
+pub fn open_watch_group(config: &State, group: &str, live: &[Channel], offline: &[String]) {
+    info!("Opening the watch group {}", group);
+
+    let mut failed = Vec::new();
+
+    for (index, channel) in live.iter().enumerate() {
+        if index > 0 {
+            std::thread::sleep(GROUP_LAUNCH_GAP);
+        }
+
+        if let Err(e) = start_stream(config, channel) {
+            warn!("Couldn't open the stream of {}: {}", channel.name, e);
+
+            failed.push(format!("{}: {}", channel.name, e));
+        }
+    }
+
+    if offline.is_empty() && failed.is_empty() {
+        return;
+    }
+
+    let mut text = format!(
+        "{} of {} opened",
+        live.len() - failed.len(),
+        live.len() + offline.len()
+    );
+
+    match offline {
+        [] => {}
+        [name] => text.push_str(&format!(", {} is offline", name)),
+        names => text.push_str(&format!(", {} are offline", names.join(", "))),
+    }
+
+    for failure in failed {
+        text.push('\n');
+        text.push_str(&failure);
+    }
+
+    send_notification(&format!("Watch group: {}", group), &text);
+}
+
+/// Where a rewound stream starts, streamlink reads it from the start of the stream (as far as Twitch keeps it).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rewind {
+    Start,
+    Minutes(u64),
+}
+
+impl Rewind {
+    fn arguments(self) -> Vec<String> {
+        match self {
+            Rewind::Start => vec![String::from("--hls-live-restart")],
+            Rewind::Minutes(minutes) => vec![
+                String::from("--hls-start-offset"),
+                format!("{:02}:{:02}:00", minutes / 60, minutes % 60),
+            ],
+        }
+    }
+}
+
+/// Whether the player of the current session can rewind the streams, VLC can when it goes through streamlink.
+pub fn can_rewind(config: &State) -> bool {
+    match config.session_player.unwrap_or(config.player) {
+        OpenStreamUsing::Streamlink => true,
+        OpenStreamUsing::Vlc => find_in_path("streamlink").is_some(),
+        OpenStreamUsing::Browser
+        | OpenStreamUsing::Mpv
+        | OpenStreamUsing::Popout
+        | OpenStreamUsing::Custom => false,
+    }
+}
+
+/// Opens a channel from before the live edge, the other players open it live (and tell why).
+pub fn open_rewound_stream(config: &State, channel: &Channel, rewind: Rewind) {
+    if !can_rewind(config) {
+        send_notification(
+            "The stream can't be rewound.",
+            "Only streamlink (or VLC through streamlink) can watch a stream from the start, it's opened live instead.",
+        );
+
+        open_stream(config, channel);
+        return;
+    }
+
+    let url = channel.platform.watch_url(&channel.name);
+
+    let result = match config.session_player.unwrap_or(config.player) {
+        OpenStreamUsing::Vlc => open_in_vlc(&url, &rewind.arguments()),
+        _ => {
+            let mut args = url;
+            args.push_str(" best ");
+            args.push_str(&rewind.arguments().join(" "));
+
+            open::with(args, "streamlink")
+        }
+    };
+
+    if let Err(e) = result {
+        warn!("Couldn't open the stream of {}: {}", channel.name, e);
+
+        send_notification("Couldn't open the stream.", &e.to_string());
+    }
+}
+
+/// Opens a channel with the player of the current session (or the one of the config).
+pub fn open_stream(config: &State, channel: &Channel) {
+    if let Err(e) = start_stream(config, channel) {
+        warn!("Couldn't open the stream of {}: {}", channel.name, e);
+
+        send_notification("Couldn't open the stream.", &e.to_string());
+    }
+}
+
+/// Opens the stream and the chat of a channel, the failures are shown in a single notification.
+/// It's called from another thread, as it waits for both of them to start.
+pub fn open_stream_with_chat(config: &State, channel: &Channel) {
+    let errors: Vec<String> = [
+        ("stream", start_stream(config, channel)),
+        ("chat", open_chat(config, channel)),
+    ]
+    .iter()
+    .filter_map(|(what, result)| {
+        result
+            .as_ref()
+            .err()
+            .map(|e| format!("The {}: {}", what, e))
+    })
+    .collect();
+
+    if errors.is_empty() {
+        return;
+    }
+
+    warn!("Couldn't open {} ({})", channel.name, errors.join("; "));
+
+    send_notification(
+        &format!("Couldn't open everything for {}.", channel.name),
+        &errors.join("\n"),
+    );
+}
+
+fn start_stream(config: &State, channel: &Channel) -> std::io::Result<()> {
+    let player = config.session_player.unwrap_or(config.player);
+
+    let url = channel.platform.watch_url(&channel.name);
+    let name = channel.name.as_str();
+
+    // Only Twitch has a popout player, the other platforms are opened in the browser as usual.
+    let player = match player {
+        OpenStreamUsing::Popout if channel.platform != Platform::Twitch => OpenStreamUsing::Browser,
+        player => player,
+    };
+
+    match player {
+        OpenStreamUsing::Browser if config.app_window() => {
+            open_app_window(config, &player_url(channel))
+        }
+        OpenStreamUsing::Browser => open_in_browser(config, &url),
+        OpenStreamUsing::Mpv => {
+            let mut args = url;
+            args.push_str(" --ytdl-format=best");
+
+            // Where the window goes, e.g. "1280x720+0+0" to leave room for the chat.
+            if let Some(geometry) = &config.mpv_geometry {
+                args.push_str(" --geometry=");
+                args.push_str(geometry);
+            }
+
+            open::with(args, "mpv")
+        }
+        OpenStreamUsing::Streamlink => {
+            let mut args = url;
+            args.push_str(" best");
+
+            open::with(args, "streamlink")
+        }
+        OpenStreamUsing::Vlc => open_in_vlc(&url, &[]),
+        OpenStreamUsing::Custom => open_in_custom_player(config, &url),
+        OpenStreamUsing::Popout if config.app_window() => {
+            open_app_window(config, &player_url(channel))
+        }
+        OpenStreamUsing::Popout => open_in_browser(config, &popout_url(name)),
+    }
+}
+
+/// Looks for an executable in the PATH (e.g. "streamlink"), with its extension on Windows.
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    let file_name = if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        String::from(name)
+    };
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|directory| directory.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// VLC can't always play the streams by itself, so streamlink gives them to it when it's installed.
+/// The streamlink arguments (e.g. to rewind the stream) are ignored without it.
+fn open_in_vlc(url: &str, streamlink_args: &[String]) -> std::io::Result<()> {
+    let vlc = find_in_path("vlc").or_else(platform::vlc_location);
+
+    if let Some(streamlink) = find_in_path("streamlink") {
+        let player = vlc
+            .map(|vlc| vlc.display().to_string())
+            .unwrap_or_else(|| String::from("vlc"));
+
+        let mut args = vec![
+            String::from("--player"),
+            player,
+            String::from(url),
+            String::from("best"),
+        ];
+        args.extend_from_slice(streamlink_args);
+
+        return spawn(&streamlink, &args);
+    }
+
+    match vlc {
+        Some(vlc) => spawn(&vlc, &[String::from(url)]),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "VLC isn't installed (or it isn't in the PATH).",
+        )),
+    }
+}
+
+/// Splits a command into its program and its arguments, at the spaces that aren't between double quotes
+///  (so that e.g. `"C:\Program Files\mpv\mpv.exe" {url}` has a single program). The quotes are removed.
+pub fn split_command(command: &str) -> Vec<String> {
+    let mut result = Vec::new();
+
+    let mut current = String::new();
+    let mut quoted = false;
+    // So that "" is still an (empty) argument.
+    let mut in_argument = false;
+
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_argument = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_argument {
+                    result.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_argument = true;
+            }
+        }
+    }
+
+    if in_argument {
+        result.push(current);
+    }
+
+    result
+}
+
+/// Runs the command of "custom_player" with the link to the stream, see `split_command`.
+/// The "{url}" placeholders are replaced after the split, so the link is always a single argument.
+fn open_in_custom_player(config: &State, url: &str) -> std::io::Result<()> {
+    let command = config
+        .custom_player()
+        .map(split_command)
+        .unwrap_or_default();
+
+    let (program, args) = match command.split_first() {
+        Some((program, args)) => (program, args),
+        None => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "There's no \"custom_player\" in the config.",
+            ))
+        }
+    };
+
+    let values = [("url", Some(String::from(url)))];
+
+    let mut args: Vec<String> = args
+        .iter()
+        .map(|arg| template::render(arg, &values))
+        .collect();
+
+    if !command.iter().any(|arg| arg.contains("{url}")) {
+        args.push(String::from(url));
+    }
+
+    spawn(Path::new(program), &args)
+}
+
+/// Opens the chat of a channel with the chat client of the config.
+/// If Chatterino isn't installed, the chat is opened in the browser instead.
+/// Chatterino only has the chats of Twitch, the chats of the other platforms are on their page.
+pub fn open_chat(config: &State, channel: &Channel) -> std::io::Result<()> {
+    if channel.platform != Platform::Twitch {
+        return open_in_browser(config, &channel.platform.watch_url(&channel.name));
+    }
+
+    let channel = channel.name.as_str();
+
+    if config.chat_client == ChatClient::Chatterino {
+        match find_in_path("chatterino").or_else(platform::chatterino_location) {
+            Some(chatterino) => return spawn(&chatterino, &chatterino_arguments(channel)),
+            None => {
+                warn!(
+                    "Couldn't find Chatterino, opening the chat of {} in the browser",
+                    channel
+                );
+
+                send_notification(
+                    "Couldn't find Chatterino, the chat was opened in the browser instead.",
+                    "It should be in the PATH, or in its default location.",
+                );
+            }
+        }
+    }
+
+    open_in_browser(config, &chat_url(channel))
+}
+
+/// e.g. "--channels t:j_blow", the "t:" is for Twitch (it also supports other sites).
+pub fn chatterino_arguments(channel: &str) -> Vec<String> {
+    vec![String::from("--channels"), format!("t:{}", channel)]
+}
+
+fn chat_url(channel: &str) -> String {
+    let mut result = String::from("https://www.twitch.tv/popout/");
+    result.push_str(channel);
+    result.push_str("/chat?popout=");
+
+    result
+}
+
+fn popout_url(channel: &str) -> String {
+    // The player starts muted otherwise.
+    let mut result = String::from("https://player.twitch.tv/?channel=");
+    result.push_str(channel);
+    result.push_str("&parent=twitch.tv&muted=false");
+
+    result
+}
+
+/// What goes in an app window, the other platforms don't have a popout player so it's their page.
+fn player_url(channel: &Channel) -> String {
+    match channel.platform {
+        Platform::Twitch => popout_url(&channel.name),
+        platform => platform.watch_url(&channel.name),
+    }
+}
+
+/// Opens a player in a window of its own, if the browser of the config has an app mode.
+/// Otherwise, it's opened as usual.
+fn open_app_window(config: &State, url: &str) -> std::io::Result<()> {
+    let browser = match config.browser() {
+        Some(browser) if BrowserFamily::of(&browser).has_app_mode() => browser,
+        _ => {
+            if !APP_WINDOW_UNSUPPORTED_SHOWN.swap(true, Ordering::Relaxed) {
+                send_notification(
+                    "The streams can't be opened as an app window.",
+                    "This needs a \"browser_path\" to a Chromium based browser (e.g. Chrome or Edge), the streams will be opened as usual.",
+                );
+            }
+
+            return open_in_browser(config, url);
+        }
+    };
+
+    // The other arguments (e.g. the profile) are kept, the URL goes in "--app".
+    let mut args = session_arguments(config, &browser);
+
+    args.extend(
+        config
+            .browser_args
+            .iter()
+            .filter(|arg| !arg.contains("{url}"))
+            .cloned(),
+    );
+
+    args.push(format!("--app={}", url));
+
+    if let Some((width, height)) = config.app_window_size {
+        args.push(format!("--window-size={},{}", width, height));
+    }
+
+    match spawn(&browser, &args) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            warn!("Couldn't start the browser {}: {}", browser.display(), e);
+
+            send_notification(
+                "Couldn't start the browser, the default one was used instead.",
+                &format!("{} ({})", browser.display(), e),
+            );
+
+            open::that(url)
+        }
+    }
+}
+
+/// The "browser_args" of the config for a URL: the "{url}" placeholders are replaced,
+///  the URL is added as the last argument if there isn't one.
+/// Each argument is passed as it is, so they can have spaces (e.g. "--profile-directory=Profile 2").
+pub fn browser_arguments(args: &[String], url: &str) -> Vec<String> {
+    let values = [("url", Some(String::from(url)))];
+
+    let mut result: Vec<String> = args
+        .iter()
+        .map(|arg| template::render(arg, &values))
+        .collect();
+
+    if !args.iter().any(|arg| arg.contains("{url}")) {
+        result.push(String::from(url));
+    }
+
+    result
+}
+
+/// Opens a URL with the browser of the config, or with the default one.
+/// If the browser of the config can't be started, the default one is used instead.
+pub fn open_in_browser(config: &State, url: &str) -> std::io::Result<()> {
+    if let Some(browser) = config.browser() {
+        let mut args = session_arguments(config, &browser);
+        args.extend(browser_arguments(&config.browser_args, url));
+
+        match spawn(&browser, &args) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                warn!("Couldn't start the browser {}: {}", browser.display(), e);
+
+                send_notification(
+                    "Couldn't start the browser, the default one was used instead.",
+                    &format!("{} ({})", browser.display(), e),
+                );
+            }
+        }
+    } else if config.incognito() {
+        warn_incognito_unsupported(
+            "The default browser can't be opened in a private window, this needs a \"browser_path\".",
+        );
+    }
+
+    open::that(url)
+}
+
+fn spawn(program: &Path, args: &[String]) -> std::io::Result<()> {
+    // We don't wait for it, it keeps running on its own.
+    Command::new(program).args(args).spawn().map(|_| ())
+}
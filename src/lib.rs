@@ -0,0 +1,83 @@
+//! The core of taskbar-twitch: the config, the platforms that the channels stream on (see `provider`),
+//!  what happens to the channels at every update, and the notifications.
+//! The binary adds the tray icon and its event loop on top of it, the events of its menu are `Events`.
+
+pub mod app_state;
+pub mod avatars;
+pub mod chat_alerts;
+pub mod config;
+pub mod connectivity;
+pub mod demo;
+pub mod diagnostics;
+pub mod kick;
+pub mod launch;
+pub mod lock;
+pub mod logging;
+pub mod metrics;
+pub mod notification_queue;
+pub mod platform;
+pub mod provider;
+pub mod summary;
+pub mod template;
+pub mod text;
+pub mod twitch;
+pub mod updates;
+pub mod youtube;
+
+use config::{NotificationKind, OpenStreamUsing};
+use launch::Rewind;
+
+/// What the tray icon, its menu and the background tasks tell the event loop.
+///
+/// ```
+/// use taskbar_twitch::Events;
+///
+/// // e.g. "Snooze for 1 hour" in the menu of a channel.
+/// let event = Events::Snooze(String::from("j_blow"), 60 * 60);
+///
+/// match event {
+///     Events::Snooze(channel, seconds) => println!("Snoozing {} for {}s", channel, seconds),
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Events {
+    // Tray Icon events
+    ClickTrayIcon,
+    DoubleClickTrayIcon,
+    Exit,
+    // User events
+    OpenChannelsFile,
+    ToggleStartAtLogin,
+    ToggleNotifications,
+    Reauthenticate,
+    SystemResumed,
+    SessionLocked,
+    SessionUnlocked,
+    SessionEnding,
+    ReportProblem { include_channels: bool },
+    CopyDebugInfo,
+    SwitchProfile(String), // config file of the profile
+    UpdatedChannels,
+    RefreshChannels,
+    UpdateFailed,
+    UpdateAvailable,
+    OpenReleasePage,
+    ResetCounters,
+    ChangeCurrentPlayer(OpenStreamUsing),
+    ToggleAppWindow,
+    ToggleIncognito,
+    OpenChannel(String), // name of the channel
+    AutoOpenChannel(String),
+    OpenStreamAndChat(String),
+    WatchFrom(String, Rewind),
+    OpenWatchGroup(String), // name of the group
+    CleanUpInactive,
+    ExportAppData,
+    ImportAppData,
+    OpenChat(String),
+    ToggleNotification(String, NotificationKind),
+    Snooze(String, u64), // name of the channel, for how many seconds
+    Unsnooze(String),
+    MarkAllSeen,
+}
@@ -0,0 +1,20 @@
+use std::sync::{Mutex, MutexGuard};
+
+/// A thread that panics while holding a lock poisons it, which would make every other thread panic as well.
+/// As the background tasks are restarted after a panic, we keep using the data instead.
+pub trait RecoverLock<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> RecoverLock<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|e| {
+            log::warn!("A thread panicked while holding a lock, recovering its data.");
+
+            // Only warn once, the next locks will succeed.
+            self.clear_poison();
+
+            e.into_inner()
+        })
+    }
+}
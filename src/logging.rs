@@ -0,0 +1,304 @@
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::app_state;
+use crate::config::LogLevel;
+use crate::platform;
+
+// The offsets from UTC are always a multiple of 15 minutes.
+const UTC_OFFSET_PRECISION: i64 = 15 * 60;
+
+pub const LOGS_DIRECTORY: &str = "logs";
+pub const LOG_FILE: &str = "taskbar-twitch";
+pub const CRASH_LOG_FILE: &str = "crash.log";
+
+// Used when our own directory isn't available.
+pub const FALLBACK_CRASH_LOG_FILE: &str = "taskbar-twitch-crash.log";
+
+// Once the log file is bigger than this, it's rotated.
+pub const MAX_LOG_FILE_SIZE: u64 = 1024 * 1024;
+
+// The current log file and the older ones (taskbar-twitch.1.log, taskbar-twitch.2.log, ...).
+pub const MAX_LOG_FILES: usize = 3;
+
+enum Message {
+    Line(String),
+    // Sends back a message once every line before it was written.
+    Flush(mpsc::Sender<()>),
+}
+
+/// Sends every line to a thread that writes them, so that the event loop never waits for the disk.
+struct Logger {
+    tx: Mutex<mpsc::Sender<Message>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // The libraries we use have their own logs, which are too noisy.
+        metadata.level() <= log::max_level() && metadata.target().starts_with("taskbar_twitch")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!("{} {:<5} {}\n", timestamp(), record.level(), record.args());
+
+        if let Ok(tx) = self.tx.lock() {
+            tx.send(Message::Line(line)).ok();
+        }
+    }
+
+    fn flush(&self) {
+        let (done_tx, done_rx) = mpsc::channel();
+
+        let sent = match self.tx.lock() {
+            Ok(tx) => tx.send(Message::Flush(done_tx)).is_ok(),
+            Err(_) => false,
+        };
+
+        if sent {
+            done_rx.recv_timeout(Duration::from_secs(1)).ok();
+        }
+    }
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+        }
+    }
+}
+
+/// Starts writing the logs to our directory, until the config is read only the important events are logged.
+pub fn init() {
+    let directory = app_state::directory().join(LOGS_DIRECTORY);
+
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || write_lines(directory, rx));
+
+    let logger = Logger { tx: Mutex::new(tx) };
+
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(LogLevel::default().into());
+    }
+}
+
+pub fn set_level(level: LogLevel) {
+    log::set_max_level(level.into());
+}
+
+/// Where the crash log is kept: next to the other logs, or in the temporary directory if they aren't available.
+fn crash_log_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    if let Some(directory) = platform::data_directory() {
+        files.push(directory.join(LOGS_DIRECTORY).join(CRASH_LOG_FILE));
+    }
+
+    files.push(std::env::temp_dir().join(FALLBACK_CRASH_LOG_FILE));
+
+    files
+}
+
+/// Appends a crash report to the crash log, this is called from the panic hook so it must never panic.
+/// The regular log is written by another thread, which might not get the chance to write it before we exit.
+pub fn write_crash(report: &str) {
+    for path in crash_log_files() {
+        if let Some(directory) = path.parent() {
+            if std::fs::create_dir_all(directory).is_err() {
+                continue;
+            }
+        }
+
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(report.as_bytes()));
+
+        if written.is_ok() {
+            return;
+        }
+    }
+}
+
+/// When the application last crashed, if it ever did.
+pub fn last_crash() -> Option<SystemTime> {
+    crash_log_files()
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+fn log_file(directory: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        directory.join(format!("{}.log", LOG_FILE))
+    } else {
+        directory.join(format!("{}.{}.log", LOG_FILE, index))
+    }
+}
+
+/// The oldest file is replaced, the others are renamed (taskbar-twitch.log -> taskbar-twitch.1.log, ...).
+fn rotate(directory: &Path) {
+    for index in (1..MAX_LOG_FILES).rev() {
+        std::fs::rename(log_file(directory, index - 1), log_file(directory, index)).ok();
+    }
+}
+
+fn write_lines(directory: PathBuf, rx: mpsc::Receiver<Message>) {
+    // The file is only opened when there's something to write.
+    let mut file: Option<File> = None;
+
+    for message in rx {
+        match message {
+            Message::Line(line) => {
+                if file.is_none() {
+                    std::fs::create_dir_all(&directory).ok();
+
+                    file = OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(log_file(&directory, 0))
+                        .ok();
+                }
+
+                let size = match &mut file {
+                    Some(f) => {
+                        f.write_all(line.as_bytes()).ok();
+                        f.metadata().map(|metadata| metadata.len()).unwrap_or(0)
+                    }
+                    None => 0,
+                };
+
+                if size >= MAX_LOG_FILE_SIZE {
+                    file = None;
+                    rotate(&directory);
+                }
+            }
+            Message::Flush(done) => {
+                if let Some(f) = &mut file {
+                    f.flush().ok();
+                }
+
+                done.send(()).ok();
+            }
+        }
+    }
+}
+
+/// The current time in UTC (e.g. "2021-08-14 18:03:27").
+pub fn timestamp() -> String {
+    format_time(SystemTime::now())
+}
+
+/// The days since the epoch of a date, see http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn seconds_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Parses a time in UTC, as Twitch sends them (e.g. "2021-08-14T18:03:27Z").
+pub fn parse_time(value: &str) -> Option<SystemTime> {
+    let (date, time) = value.strip_suffix('Z')?.split_once('T')?;
+
+    let mut date = date.splitn(3, '-').map(|field| field.parse::<i64>().ok());
+    let mut time = time.splitn(3, ':');
+
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    let hour: i64 = time.next()?.parse().ok()?;
+    let minute: i64 = time.next()?.parse().ok()?;
+    // The seconds could have a fraction.
+    let second: i64 = time.next()?.split('.').next()?.parse().ok()?;
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+
+    if seconds < 0 {
+        return None;
+    }
+
+    Some(UNIX_EPOCH + Duration::from_secs(seconds as u64))
+}
+
+/// The difference between the local time and UTC, in seconds (e.g. 3600 in Central Europe, during the winter).
+pub fn utc_offset() -> Option<i64> {
+    let local = platform::local_time()?;
+
+    let local_seconds = days_from_civil(local.year as i64, local.month as i64, local.day as i64)
+        * 86400
+        + local.hour as i64 * 3600
+        + local.minute as i64 * 60;
+
+    // The local time doesn't have the seconds, so it's rounded.
+    let offset = local_seconds - seconds_since_epoch(SystemTime::now());
+
+    Some((offset as f64 / UTC_OFFSET_PRECISION as f64).round() as i64 * UTC_OFFSET_PRECISION)
+}
+
+/// The local time of the day (e.g. "19:05"), with the offset from `utc_offset`.
+pub fn format_clock_time(time: SystemTime, utc_offset: i64) -> String {
+    let seconds = (seconds_since_epoch(time) + utc_offset).rem_euclid(86400);
+
+    format!("{:02}:{:02}", seconds / 3600, seconds % 3600 / 60)
+}
+
+/// Formats a time in UTC, so that we don't need a date library.
+pub fn format_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // Converts the days since the epoch to a date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
@@ -1,49 +1,158 @@
-#![windows_subsystem = "windows"]
+#![cfg_attr(windows, windows_subsystem = "windows")]
 
-mod config;
-use config::OpenStreamUsing;
-use config::State;
+//! The tray icon and its menu, around the library: the event loop, and the background tasks that it starts.
 
-mod twitch;
+use taskbar_twitch::config;
+use taskbar_twitch::config::LogLevel;
+use taskbar_twitch::config::NotificationKind;
+use taskbar_twitch::config::OpenStreamUsing;
+use taskbar_twitch::config::State;
 
-use std::path::Path;
-use std::path::PathBuf;
+use taskbar_twitch::app_state;
+use taskbar_twitch::chat_alerts;
+use taskbar_twitch::connectivity;
+use taskbar_twitch::demo;
+use taskbar_twitch::diagnostics;
+use taskbar_twitch::kick;
+use taskbar_twitch::launch;
+use taskbar_twitch::launch::Rewind;
+use taskbar_twitch::lock;
+use taskbar_twitch::lock::RecoverLock;
+use taskbar_twitch::logging;
+use taskbar_twitch::metrics::METRICS;
+use taskbar_twitch::notification_queue;
+use taskbar_twitch::platform;
+use taskbar_twitch::platform::send_notification;
+use taskbar_twitch::platform::startup;
+use taskbar_twitch::platform::{MenuBuilder, MenuItem, TrayIconBase};
+use taskbar_twitch::provider;
+use taskbar_twitch::summary;
+use taskbar_twitch::template;
+use taskbar_twitch::text;
+use taskbar_twitch::twitch;
+use taskbar_twitch::updates;
+use taskbar_twitch::youtube;
+use taskbar_twitch::Events;
+
+mod badge;
+mod headless;
+mod self_update;
+mod sha256;
+
+use std::any::Any;
+use std::cell::Cell;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use enum_iterator::IntoEnumIterator;
 
 use winit::{
     event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     window::WindowBuilder,
 };
-use winrt_notification::Toast;
-
-use trayicon::{MenuBuilder, MenuItem, TrayIconBuilder};
 
 // Used to track releases - it's available in the traybar so that the user knows what version they currently have.
-const APP_VERSION: &'static str = "Version 1.0.3";
+const APP_VERSION: &str = concat!("Version ", env!("CARGO_PKG_VERSION"));
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum Events {
-    // Tray Icon events
-    ClickTrayIcon,
-    DoubleClickTrayIcon,
-    Exit,
-    // User events
-    OpenChannelsFile,
-    UpdatedChannels,
-    ChangeCurrentPlayer(OpenStreamUsing),
-    OpenChannel(usize), // index of the channel in the config
-}
+// A background task that panics is restarted after a while, unless it keeps panicking.
+// After this many restarts in a row, the task is shown as failing (it's still restarted).
+const MAX_TASK_RESTARTS: u32 = 5;
+// Doubles after every restart in a row.
+const TASK_RESTART_TIME: Duration = Duration::from_secs(10);
+const MAX_TASK_RESTART_TIME: Duration = Duration::from_secs(5 * 60);
+// A task that ran for this long before panicking again starts over.
+const TASK_STABLE_TIME: Duration = Duration::from_secs(30 * 60);
+
+const TOOLTIP: &str = "Taskbar Twitch";
+
+const DARK_THEME_ICON: &[u8] = include_bytes!("../resources/twitch.ico");
+const LIGHT_THEME_ICON: &[u8] = include_bytes!("../resources/twitch_light.ico");
+
+// Greyed out, used while we can't reach Twitch.
+const DARK_THEME_DEGRADED_ICON: &[u8] = include_bytes!("../resources/twitch_degraded.ico");
+const LIGHT_THEME_DEGRADED_ICON: &[u8] = include_bytes!("../resources/twitch_light_degraded.ico");
+
+// The icon is only greyed out once the updates have been failing for a while, so that it doesn't flicker.
+const DEGRADED_ICON_TIME: Duration = Duration::from_secs(60);
+
+// The custom tray icon that was last loaded, with its path, so that a badge or theme change doesn't read it again.
+static CUSTOM_ICON: Mutex<Option<CustomIcon>> = Mutex::new(None);
+
+// `None` if it couldn't be loaded.
+type CustomIcon = (PathBuf, Option<&'static [u8]>);
 
 #[tokio::main]
 async fn main() {
+    // Without a tray icon, the errors are printed instead of being shown as notifications.
+    if config::check_requested() {
+        platform::attach_console();
+
+        std::process::exit(headless::check().await);
+    }
+
     set_panic_hook();
 
-    let state = Arc::new(Mutex::new(config::read()));
+    logging::init();
+
+    let mut config = config::read();
+
+    app_state::restore_title_history(&mut config.channels);
+    app_state::restore_broadcasters(&mut config.channels);
+    app_state::restore_live_history(&mut config.channels);
+    config.snoozed = app_state::restore_snoozed(&config.channels);
+
+    logging::set_level(config.log_level);
+    platform::set_notifications_enabled(config.notifications_enabled);
+
+    log::info!("Starting ({}, {})", APP_VERSION, config.config_file);
+
+    config::warn_invalid_channels(&config);
+    config::warn_unmatched_notify_entries(&config);
+    config::warn_unmatched_group_members(&config);
+    config::warn_unknown_placeholders(&config);
+    config::warn_missing_browser(&config);
+
+    if let Some(missing) = config
+        .missing_credentials()
+        .filter(|_| config.has_twitch_channels())
+    {
+        log::warn!("The Twitch {} isn't set in {}", missing, config.config_file);
+    }
+
+    check_last_crash();
+
+    // After an update, the previous version might still be exiting.
+    std::thread::spawn(self_update::remove_previous_version);
+
+    match startup::refresh(&config.config_file) {
+        Ok(enabled) => config.start_at_login = enabled,
+        Err(e) => {
+            let message = format!("Couldn't read the startup settings: {}", e);
+
+            diagnostics::record_error(&message);
+            send_notification("Couldn't read the startup settings.", &e.to_string());
+        }
+    }
+
+    // The seed is logged, so that the same simulation can be run again with "--demo-seed".
+    let demo_seed = if config.demo {
+        let seed = config.demo_seed.unwrap_or_else(demo::random_seed);
+
+        log::info!("Demo mode, the channels are simulated (seed {})", seed);
+
+        Some(seed)
+    } else {
+        None
+    };
+
+    let state = Arc::new(Mutex::new(config));
 
     let event_loop = EventLoop::<Events>::with_user_event();
 
@@ -51,41 +160,161 @@ async fn main() {
     //  using this channel so it can fetch the updates for the newly added channels.
     let (tx, rx) = mpsc::channel();
 
+    match demo_seed {
+        Some(seed) => spawn_demo(&state, &event_loop, seed),
+        None => spawn_updates(&state, &event_loop, rx),
+    }
+
+    // The event loop also needs to notify the network thread (e.g. when re-authenticating).
+    let event_loop_tx = tx.clone();
+
+    let file_thread_state = state.clone();
+    let file_proxy = event_loop.create_proxy();
+    tokio::task::spawn_blocking(move || {
+        supervise("config", || {
+            futures::executor::block_on(async {
+                twitch::refresh_config(file_thread_state.clone(), &file_proxy, tx.clone()).await;
+            });
+        });
+    });
+
+    let updates_thread_state = state.clone();
+    let updates_proxy = event_loop.create_proxy();
+    tokio::task::spawn_blocking(move || {
+        supervise("updates", || {
+            updates::watch(updates_thread_state.clone(), &updates_proxy)
+        });
+    });
+
+    let chat_thread_state = state.clone();
+    tokio::task::spawn_blocking(move || {
+        supervise("chat alerts", || {
+            chat_alerts::watch(chat_thread_state.clone())
+        });
+    });
+
+    let connectivity_watcher = connectivity::Watcher::spawn(event_loop_tx.clone());
+    let notification_sender = notification_queue::Sender::spawn(state.clone());
+
+    let event_loop_state = state.clone();
+    run_event_loop(
+        event_loop,
+        event_loop_state,
+        event_loop_tx,
+        connectivity_watcher,
+        notification_sender,
+    );
+}
+
+/// The updates of each platform, they run on their own threads.
+fn spawn_updates(
+    state: &Arc<Mutex<State>>,
+    event_loop: &EventLoop<Events>,
+    rx: mpsc::Receiver<twitch::Message>,
+) {
     let network_thread_state = state.clone();
     let network_proxy = event_loop.create_proxy();
     tokio::task::spawn_blocking(move || {
-        futures::executor::block_on(async {
-            twitch::listen_for_events(network_thread_state, &network_proxy, rx).await;
+        supervise("network", || {
+            futures::executor::block_on(async {
+                twitch::listen_for_events(network_thread_state.clone(), &network_proxy, &rx).await;
+            });
         });
     });
 
-    let file_thread_state = state.clone();
-    let file_proxy = event_loop.create_proxy();
+    // The other platforms have their own updates, so that an outage of one doesn't delay the others.
+    let kick_thread_state = state.clone();
+    let kick_proxy = event_loop.create_proxy();
     tokio::task::spawn_blocking(move || {
-        futures::executor::block_on(async {
-            twitch::refresh_config(file_thread_state, &file_proxy, tx).await;
+        supervise("kick", || {
+            futures::executor::block_on(provider::poll(
+                kick::KickProvider,
+                config::Platform::Kick,
+                kick::UPDATE_CHANNELS_TIME,
+                kick_thread_state.clone(),
+                &kick_proxy,
+            ));
         });
     });
 
-    let event_loop_state = state.clone();
-    run_event_loop(event_loop, event_loop_state);
+    let youtube_thread_state = state.clone();
+    let youtube_proxy = event_loop.create_proxy();
+    tokio::task::spawn_blocking(move || {
+        supervise("youtube", || {
+            futures::executor::block_on(provider::poll(
+                youtube::YouTubeProvider::new(youtube_thread_state.clone()),
+                config::Platform::YouTube,
+                youtube::UPDATE_CHANNELS_TIME,
+                youtube_thread_state.clone(),
+                &youtube_proxy,
+            ));
+        });
+    });
+}
+
+/// Simulates every platform instead (see the `demo` module), each one with its own seed.
+fn spawn_demo(state: &Arc<Mutex<State>>, event_loop: &EventLoop<Events>, seed: u64) {
+    for (index, platform) in [
+        config::Platform::Twitch,
+        config::Platform::Kick,
+        config::Platform::YouTube,
+    ]
+    .iter()
+    .copied()
+    .enumerate()
+    {
+        let demo_thread_state = state.clone();
+        let demo_proxy = event_loop.create_proxy();
+        tokio::task::spawn_blocking(move || {
+            supervise("demo", || {
+                futures::executor::block_on(provider::poll(
+                    demo::DemoProvider::new(seed.wrapping_add(index as u64)),
+                    platform,
+                    demo::UPDATE_CHANNELS_TIME,
+                    demo_thread_state.clone(),
+                    &demo_proxy,
+                ));
+            });
+        });
+    }
 }
 
-fn run_event_loop(event_loop: EventLoop<Events>, state: Arc<Mutex<State>>) {
+fn run_event_loop(
+    event_loop: EventLoop<Events>,
+    state: Arc<Mutex<State>>,
+    network_tx: mpsc::Sender<twitch::Message>,
+    mut connectivity_watcher: connectivity::Watcher,
+    mut notification_sender: notification_queue::Sender,
+) {
     let window = WindowBuilder::new()
         .with_visible(false)
         .build(&event_loop)
         .expect("Valid window.");
 
-    let mut tray_icon = TrayIconBuilder::new()
-        .sender_winit(event_loop.create_proxy())
-        .icon_from_buffer(include_bytes!("../resources/twitch.ico"))
-        .tooltip("Taskbar Twitch")
-        .on_click(Events::ClickTrayIcon)
-        .on_double_click(Events::DoubleClickTrayIcon)
-        .menu(create_tray_menu(&state))
-        .build()
-        .expect("Couldn't create a tray icon menu!");
+    if let Err(e) = platform::listen_for_system_events(&window, event_loop.create_proxy()) {
+        diagnostics::record_error(&format!("Couldn't listen for the system events: {}", e));
+    }
+
+    let theme = platform::taskbar_theme();
+
+    let mut tray_icon = platform::TrayIcon::build(
+        event_loop.create_proxy(),
+        default_icon(theme, false),
+        TOOLTIP,
+        &create_tray_menu(&state),
+    )
+    .expect("Couldn't create a tray icon menu!");
+
+    let mut current_icon = IconState {
+        custom_icon: None,
+        theme,
+        degraded: false,
+        badge: 0,
+    };
+    update_tray_icon(&mut tray_icon, &state, &mut current_icon);
+
+    // To exit once an update is installed.
+    let update_proxy = event_loop.create_proxy();
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
@@ -102,48 +331,429 @@ fn run_event_loop(event_loop: EventLoop<Events>, state: Arc<Mutex<State>>) {
             } if window_id == window.id() => {
                 *control_flow = ControlFlow::Exit;
             }
+            // Sent when the system settings change, the taskbar might have a different theme now.
+            Event::WindowEvent {
+                event: WindowEvent::ThemeChanged(_),
+                window_id,
+            } if window_id == window.id() => {
+                update_tray_icon(&mut tray_icon, &state, &mut current_icon);
+            }
+            Event::LoopDestroyed => {
+                connectivity_watcher.stop();
+                notification_sender.stop();
+            }
 
             // User events
             Event::UserEvent(e) => match e {
                 Events::OpenChannelsFile => {
-                    let local_state = state.lock().unwrap();
+                    let config_file = state.lock_or_recover().config_file.clone();
+
+                    launch::in_background(move || {
+                        open::that(config_file.as_str()).ok();
+                    });
+                }
+                Events::ToggleStartAtLogin => {
+                    {
+                        let mut local_state = state.lock_or_recover();
+
+                        let result = if local_state.start_at_login {
+                            startup::disable()
+                        } else {
+                            startup::enable(&local_state.config_file)
+                        };
+
+                        match result {
+                            Ok(_) => local_state.start_at_login = !local_state.start_at_login,
+                            Err(e) => {
+                                let message =
+                                    format!("Couldn't change the startup settings: {}", e);
+
+                                diagnostics::record_error(&message);
+                                send_notification(
+                                    "Couldn't change the startup settings.",
+                                    &e.to_string(),
+                                );
+                            }
+                        }
+                    }
 
-                    open::that(local_state.config_file.as_str()).ok();
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                }
+                Events::OpenChannel(ref name)
+                | Events::OpenStreamAndChat(ref name)
+                | Events::AutoOpenChannel(ref name) => {
+                    let with_chat = matches!(e, Events::OpenStreamAndChat(_));
+
+                    open_channel(&state, name, with_chat);
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                    update_tray_icon(&mut tray_icon, &state, &mut current_icon);
                 }
-                Events::OpenChannel(index) => {
-                    let local_state = state.lock().unwrap();
+                Events::OpenWatchGroup(group) => {
+                    let mut local_state = state.lock_or_recover();
 
-                    let current_player = local_state.session_player.unwrap_or(local_state.player);
+                    // The group might have been removed from the config after the menu was built.
+                    let members = match local_state.watch_groups.get(&group) {
+                        Some(members) => members.clone(),
+                        None => return,
+                    };
 
-                    match current_player {
-                        config::OpenStreamUsing::Browser => {
-                            let mut result = String::from("https://twitch.tv/");
-                            result.push_str(local_state.channels[index].name.as_str());
+                    let mut live = Vec::new();
+                    let mut offline = Vec::new();
 
-                            open::that(result).unwrap();
+                    for name in members {
+                        match local_state.find_channel_mut(&name) {
+                            Some(channel) if channel.is_online() => {
+                                channel.unseen = false;
+                                live.push(channel.clone());
+                            }
+                            _ => offline.push(name),
                         }
-                        config::OpenStreamUsing::Mpv => {
-                            let mut args = String::from("https://twitch.tv/");
-                            args.push_str(local_state.channels[index].name.as_str());
-                            args.push_str(" --ytdl-format=best");
+                    }
+
+                    let config = local_state.clone();
+
+                    drop(local_state);
+
+                    launch::in_background(move || {
+                        launch::open_watch_group(&config, &group, &live, &offline)
+                    });
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                    update_tray_icon(&mut tray_icon, &state, &mut current_icon);
+                }
+                Events::CleanUpInactive => {
+                    let config = state.lock_or_recover().clone();
+
+                    // The confirmation waits for the user, the config file is then read again as usual.
+                    std::thread::spawn(move || clean_up_inactive(&config));
+                }
+                Events::ExportAppData => {
+                    // The dialog waits for the user.
+                    std::thread::spawn(export_app_data);
+                }
+                Events::ImportAppData => {
+                    let state = state.clone();
+                    let update_proxy = update_proxy.clone();
+
+                    std::thread::spawn(move || import_app_data(&state, &update_proxy));
+                }
+                Events::WatchFrom(name, rewind) => {
+                    let mut local_state = state.lock_or_recover();
 
-                            open::with(args, "mpv").unwrap();
+                    let channel = match local_state.find_channel_mut(&name) {
+                        Some(channel) => {
+                            channel.unseen = false;
+                            channel.clone()
                         }
-                        config::OpenStreamUsing::Streamlink => {
-                            let mut args = String::from("twitch.tv/");
-                            args.push_str(local_state.channels[index].name.as_str());
-                            args.push_str(" best");
+                        None => return,
+                    };
+
+                    let config = local_state.clone();
+
+                    drop(local_state);
+
+                    launch::in_background(move || {
+                        launch::open_rewound_stream(&config, &channel, rewind)
+                    });
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                    update_tray_icon(&mut tray_icon, &state, &mut current_icon);
+                }
+                Events::OpenChat(name) => {
+                    let config = state.lock_or_recover().clone();
+
+                    let channel = match config.find_channel(&name) {
+                        Some(channel) => channel.clone(),
+                        None => return,
+                    };
 
-                            open::with(args, "streamlink").unwrap();
+                    launch::in_background(move || {
+                        if let Err(e) = launch::open_chat(&config, &channel) {
+                            log::warn!("Couldn't open the chat of {}: {}", name, e);
+
+                            send_notification("Couldn't open the chat.", &e.to_string());
                         }
+                    });
+                }
+                Events::OpenReleasePage => {
+                    let config = state.lock_or_recover().clone();
+
+                    let release = match &config.available_update {
+                        Some(release) => release.clone(),
+                        None => return,
+                    };
+
+                    if config.self_update && self_update::release_asset(&release).is_some() {
+                        send_notification(
+                            &format!("Installing v{}…", release.version),
+                            "It will restart once it's done.",
+                        );
+
+                        let update_proxy = update_proxy.clone();
+
+                        // The download could take a while.
+                        std::thread::spawn(move || {
+                            match self_update::install(&release, &config.config_file) {
+                                Ok(()) => {
+                                    update_proxy.send_event(Events::Exit).ok();
+                                }
+                                Err(e) => {
+                                    diagnostics::record_error(&format!(
+                                        "Couldn't install the update: {}",
+                                        e
+                                    ));
+
+                                    send_notification("Couldn't install the update.", &e);
+                                }
+                            }
+                        });
+                    } else {
+                        launch::in_background(move || {
+                            if let Err(e) = launch::open_in_browser(&config, &release.url) {
+                                log::warn!("Couldn't open the release page: {}", e);
+
+                                send_notification(
+                                    "Couldn't open the release page.",
+                                    &e.to_string(),
+                                );
+                            }
+                        });
                     }
                 }
-                Events::UpdatedChannels => {
+                Events::ResetCounters => {
+                    METRICS.reset();
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                }
+                Events::MarkAllSeen => {
+                    for channel in &mut state.lock_or_recover().channels {
+                        channel.unseen = false;
+                    }
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                    update_tray_icon(&mut tray_icon, &state, &mut current_icon);
+                }
+                Events::ToggleNotification(name, kind) => {
+                    let result = {
+                        let mut local_state = state.lock_or_recover();
+
+                        match local_state.find_channel(&name).map(|c| c.name.clone()) {
+                            Some(name) => {
+                                local_state.toggle_notification(&name, kind);
+
+                                Some(config::save_notifications(&local_state))
+                            }
+                            None => None,
+                        }
+                    };
+
+                    if result.is_none() {
+                        send_notification(
+                            "Couldn't change the notifications.",
+                            &format!("{} isn't in the channels anymore.", name),
+                        );
+                    }
+
+                    if let Some(Err(e)) = result {
+                        diagnostics::record_error(&format!("Couldn't save the config file: {}", e));
+                        send_notification("Couldn't save the config file.", &e.to_string());
+                    }
+
                     tray_icon.set_menu(&create_tray_menu(&state)).ok();
                 }
+                Events::ToggleNotifications => {
+                    let result = {
+                        let mut local_state = state.lock_or_recover();
+
+                        local_state.notifications_enabled = !local_state.notifications_enabled;
+                        platform::set_notifications_enabled(local_state.notifications_enabled);
+
+                        log::info!(
+                            "Notifications {}",
+                            if local_state.notifications_enabled {
+                                "enabled"
+                            } else {
+                                "disabled"
+                            }
+                        );
+
+                        config::save_notifications_enabled(&local_state)
+                    };
+
+                    if let Err(e) = result {
+                        diagnostics::record_error(&format!("Couldn't save the config file: {}", e));
+                        send_notification("Couldn't save the config file.", &e.to_string());
+                    }
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                }
+                Events::Snooze(name, seconds) => {
+                    let until = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|duration| duration.as_secs())
+                        .unwrap_or_default()
+                        + seconds;
+
+                    log::info!("Snoozed {} for {} seconds", name, seconds);
+
+                    let result = {
+                        let mut local_state = state.lock_or_recover();
+
+                        local_state.snoozed.insert(name, until);
+
+                        app_state::write_snoozed(&local_state.snoozed, &local_state.channels)
+                    };
+
+                    if let Err(e) = result {
+                        log::warn!("Couldn't save the snoozed channels: {}", e);
+                    }
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                }
+                Events::Unsnooze(name) => {
+                    log::info!("Unsnoozed {}", name);
+
+                    let result = {
+                        let mut local_state = state.lock_or_recover();
+
+                        local_state.snoozed.remove(&name);
+
+                        app_state::write_snoozed(&local_state.snoozed, &local_state.channels)
+                    };
+
+                    if let Err(e) = result {
+                        log::warn!("Couldn't save the snoozed channels: {}", e);
+                    }
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                }
+                Events::Reauthenticate => {
+                    // The network thread handles the request after the current poll (if any) finishes.
+                    network_tx.send(twitch::Message::Reauthenticate).ok();
+                }
+                Events::RefreshChannels => {
+                    // Several clicks in a row only update once, see `twitch::listen_for_events`.
+                    network_tx.send(twitch::Message::Refresh).ok();
+                }
+                Events::SystemResumed => {
+                    network_tx.send(twitch::Message::Resumed).ok();
+                }
+                Events::SessionLocked => {
+                    network_tx.send(twitch::Message::Locked).ok();
+                }
+                Events::SessionUnlocked => {
+                    network_tx.send(twitch::Message::Unlocked).ok();
+                }
+                Events::SessionEnding => {
+                    // Otherwise the icon might still be there (until it's hovered) the next time the user logs in.
+                    tray_icon.remove();
+
+                    *control_flow = ControlFlow::Exit;
+                }
+                Events::ReportProblem { include_channels } => {
+                    let url = {
+                        let local_state = state.lock_or_recover();
+
+                        diagnostics::report_url(
+                            &local_state,
+                            APP_VERSION,
+                            &platform::os_version(),
+                            &diagnostics::recent_errors(5),
+                            include_channels,
+                        )
+                    };
+
+                    launch::in_background(move || {
+                        open::that(url).ok();
+                    });
+                }
+                Events::CopyDebugInfo => {
+                    let debug_info = {
+                        let local_state = state.lock_or_recover();
+
+                        diagnostics::debug_info(
+                            &local_state,
+                            APP_VERSION,
+                            &platform::os_version(),
+                            &diagnostics::recent_errors(3),
+                            false,
+                        )
+                    };
+
+                    match platform::copy_to_clipboard(&debug_info) {
+                        Ok(_) => send_notification(
+                            "Copied the debug info.",
+                            "Your credentials and channel names aren't included.",
+                        ),
+                        Err(e) => {
+                            diagnostics::record_error(&format!(
+                                "Couldn't copy the debug info: {}",
+                                e
+                            ));
+                            send_notification("Couldn't copy the debug info.", &e.to_string());
+                        }
+                    }
+                }
+                Events::SwitchProfile(config_file) => {
+                    // A broken profile shouldn't take the application down, we stay on the current one.
+                    let mut new_config = match config::try_read_file(&config_file) {
+                        Ok(new_config) => new_config,
+                        Err(e) => {
+                            diagnostics::record_error(&format!(
+                                "Couldn't switch to the profile {}: {}",
+                                config_file, e
+                            ));
+                            send_notification("Couldn't switch to the profile.", &e);
+
+                            // The profile that is checked in the menu has to be the current one again.
+                            tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                            return;
+                        }
+                    };
+
+                    app_state::restore_title_history(&mut new_config.channels);
+                    app_state::restore_broadcasters(&mut new_config.channels);
+                    app_state::restore_live_history(&mut new_config.channels);
+                    new_config.snoozed = app_state::restore_snoozed(&new_config.channels);
+
+                    log::info!("Switched to the profile: {}", config_file);
+
+                    logging::set_level(new_config.log_level);
+                    platform::set_notifications_enabled(new_config.notifications_enabled);
+
+                    config::warn_invalid_channels(&new_config);
+                    config::warn_unmatched_notify_entries(&new_config);
+                    config::warn_unmatched_group_members(&new_config);
+                    config::warn_unknown_placeholders(&new_config);
+                    config::warn_missing_browser(&new_config);
+
+                    config::switch(&state, new_config);
+
+                    let app_state = app_state::AppState {
+                        profile: Some(config_file),
+                        ..app_state::read()
+                    };
+
+                    if let Err(e) = app_state::write(&app_state) {
+                        diagnostics::record_error(&format!("Couldn't save the app state: {}", e));
+                    }
+
+                    // The channels of the new profile have to be fetched.
+                    network_tx.send(twitch::Message::ConfigChanged).ok();
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                }
+                Events::UpdatedChannels | Events::UpdateFailed | Events::UpdateAvailable => {
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+
+                    // The config might have a different icon now, and we also use it to notice theme changes
+                    //  that aren't sent to our window.
+                    update_tray_icon(&mut tray_icon, &state, &mut current_icon);
+                }
                 Events::ChangeCurrentPlayer(player) => {
                     {
-                        let mut local_state = state.lock().unwrap();
+                        let mut local_state = state.lock_or_recover();
 
                         local_state.session_player = Some(player);
                     }
@@ -151,6 +761,24 @@ fn run_event_loop(event_loop: EventLoop<Events>, state: Arc<Mutex<State>>) {
                     // We need to drop the mutex, and now the GUI can be updated.
                     tray_icon.set_menu(&create_tray_menu(&state)).ok();
                 }
+                Events::ToggleAppWindow => {
+                    {
+                        let mut local_state = state.lock_or_recover();
+
+                        local_state.session_app_window = Some(!local_state.app_window());
+                    }
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                }
+                Events::ToggleIncognito => {
+                    {
+                        let mut local_state = state.lock_or_recover();
+
+                        local_state.session_incognito = Some(!local_state.incognito());
+                    }
+
+                    tray_icon.set_menu(&create_tray_menu(&state)).ok();
+                }
                 Events::Exit => *control_flow = ControlFlow::Exit,
                 _ => {}
             },
@@ -159,20 +787,246 @@ fn run_event_loop(event_loop: EventLoop<Events>, state: Arc<Mutex<State>>) {
     });
 }
 
+/// What decides the icon that is currently in use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct IconState {
+    // From the config.
+    custom_icon: Option<String>,
+    theme: platform::Theme,
+    // Whether the updates have been failing for a while.
+    degraded: bool,
+    // The number of unseen channels on the icon, if any (see the `badge` module).
+    badge: usize,
+}
+
+/// The icon that is used when there isn't a custom one, it has to be visible on top of the taskbar.
+fn default_icon(theme: platform::Theme, degraded: bool) -> &'static [u8] {
+    match (theme, degraded) {
+        (platform::Theme::Light, false) => LIGHT_THEME_ICON,
+        (platform::Theme::Dark, false) => DARK_THEME_ICON,
+        (platform::Theme::Light, true) => LIGHT_THEME_DEGRADED_ICON,
+        (platform::Theme::Dark, true) => DARK_THEME_DEGRADED_ICON,
+    }
+}
+
+/// Sets the icon from the config, falling back to the default one if it can't be loaded.
+/// While the updates are failing the default icon is greyed out, even if there's a custom one.
+/// Nothing is done unless the icon in the config, the taskbar theme, the updates or the unseen channels
+///  are different from the current ones.
+fn update_tray_icon(
+    tray_icon: &mut platform::TrayIcon,
+    state: &Arc<Mutex<State>>,
+    current_icon: &mut IconState,
+) {
+    let (config_file, custom_icon, failure, loading, unseen, unseen_badge, metered) = {
+        let local_state = state.lock_or_recover();
+
+        (
+            local_state.config_file.clone(),
+            local_state.tray_icon.clone(),
+            local_state.update_failure.clone(),
+            local_state.is_loading(),
+            local_state.unseen_count(),
+            local_state.unseen_badge,
+            connectivity::metered_behavior(&local_state) != config::MeteredBehavior::Normal,
+        )
+    };
+
+    let failure = failure.filter(|failure| failure.since.elapsed() >= DEGRADED_ICON_TIME);
+    let failing_tasks = diagnostics::failing_tasks();
+
+    let mut tooltip = match &failure {
+        Some(failure) => format!(
+            "{} - updates failing for {} ({})",
+            TOOLTIP,
+            format_duration(failure.since.elapsed()),
+            failure.reason
+        ),
+        None if !failing_tasks.is_empty() => {
+            format!("{} - {} keeps failing", TOOLTIP, failing_tasks.join(", "))
+        }
+        None if loading => format!("{} - updating…", TOOLTIP),
+        None if unseen > 0 => format!("{} - {} new", TOOLTIP, unseen),
+        None => String::from(TOOLTIP),
+    };
+
+    if metered {
+        tooltip.push_str(" (metered mode)");
+    }
+
+    tray_icon.set_tooltip(&tooltip).ok();
+
+    let new_icon = IconState {
+        custom_icon,
+        theme: platform::taskbar_theme(),
+        degraded: failure.is_some() || !failing_tasks.is_empty(),
+        badge: if unseen_badge {
+            unseen.min(badge::MAX_BADGE_COUNT)
+        } else {
+            0
+        },
+    };
+
+    if new_icon == *current_icon {
+        return;
+    }
+
+    *current_icon = new_icon.clone();
+
+    // If the badge can't be drawn (e.g. the icon only has PNG images), the icon is used as is.
+    let with_badge = |icon: &'static [u8]| match new_icon.badge {
+        0 => icon,
+        count => badge::with_badge(icon, count).unwrap_or(icon),
+    };
+
+    if let (Some(path), false) = (&new_icon.custom_icon, new_icon.degraded) {
+        let path = config::resolve_path(&config_file, path);
+
+        if let Some(icon) = load_custom_icon(&path) {
+            match tray_icon.set_icon(with_badge(icon)) {
+                Ok(_) => return,
+                Err(e) => custom_icon_failed(&path, &e),
+            }
+        }
+    }
+
+    tray_icon
+        .set_icon(with_badge(default_icon(new_icon.theme, new_icon.degraded)))
+        .ok();
+}
+
+/// The custom icon is only read again when its path changes.
+fn load_custom_icon(path: &Path) -> Option<&'static [u8]> {
+    let mut custom_icon = CUSTOM_ICON.lock_or_recover();
+
+    if let Some((loaded, icon)) = custom_icon.as_ref() {
+        if loaded == path {
+            return *icon;
+        }
+    }
+
+    let icon = match read_icon(path) {
+        Ok(icon) => Some(icon),
+        Err(e) => {
+            report_custom_icon(path, &e);
+            None
+        }
+    };
+
+    *custom_icon = Some((path.to_path_buf(), icon));
+
+    icon
+}
+
+/// So that a broken icon is only reported once, instead of on every badge or theme change.
+fn custom_icon_failed(path: &Path, e: &str) {
+    *CUSTOM_ICON.lock_or_recover() = Some((path.to_path_buf(), None));
+
+    report_custom_icon(path, e);
+}
+
+fn report_custom_icon(path: &Path, e: &str) {
+    let message = format!("{} ({})", path.display(), e);
+
+    diagnostics::record_error(&format!("Couldn't load the tray icon: {}", message));
+    send_notification("Couldn't load the custom tray icon.", &message);
+}
+
+fn read_icon(path: &Path) -> Result<&'static [u8], String> {
+    let buffer = std::fs::read(path).map_err(|e| e.to_string())?;
+
+    // The tray icon needs a static buffer, as it's cached by path (see `CUSTOM_ICON`), we can leak it.
+    Ok(Box::leak(buffer.into_boxed_slice()))
+}
+
 fn create_tray_menu(config: &Arc<Mutex<State>>) -> MenuBuilder<Events> {
-    let channels = create_channels_menu(&config);
-    let players = create_players_menu(&config);
+    let channels = create_channels_menu(config);
+    let players = create_players_menu(config);
+    let twitch = create_twitch_menu(config);
+    let profiles = create_profiles_menu(config);
+    let watch_groups = create_watch_groups_menu(config);
+
+    let (
+        start_at_login,
+        notifications_enabled,
+        needs_setup,
+        available_update,
+        debug_menu,
+        inactive,
+    ) = {
+        let config = config.lock_or_recover();
+
+        (
+            config.start_at_login,
+            config.notifications_enabled,
+            config.needs_setup(),
+            config.available_update.clone(),
+            config.debug_menu || config.log_level == LogLevel::Debug,
+            !config.inactive_channels().is_empty(),
+        )
+    };
 
     MenuBuilder::new()
         .with(MenuItem::Item {
             name: String::from(APP_VERSION),
             disabled: true,
             id: Events::ClickTrayIcon,
-            icon: None,
+        })
+        .when(|menu| match available_update {
+            Some(release) => menu.item(
+                &format!("Update available: v{}", release.version),
+                Events::OpenReleasePage,
+            ),
+            None => menu,
+        })
+        // The channels are updated as soon as the config file has the credentials.
+        .when(|menu| {
+            if needs_setup {
+                menu.item("Set up Twitch credentials…", Events::OpenChannelsFile)
+            } else {
+                menu
+            }
         })
         .item("Open channels file", Events::OpenChannelsFile)
+        .when(|menu| {
+            if inactive {
+                menu.item("Clean up inactive channels…", Events::CleanUpInactive)
+            } else {
+                menu
+            }
+        })
+        .item("Export app data…", Events::ExportAppData)
+        .item("Import app data…", Events::ImportAppData)
         .submenu("Channels", channels)
+        .item("Refresh now", Events::RefreshChannels)
+        .when(|menu| match watch_groups {
+            Some(watch_groups) => menu.submenu("Watch groups", watch_groups),
+            None => menu,
+        })
         .submenu("Player", players)
+        .when(|menu| match profiles {
+            Some(profiles) => menu.submenu("Profile", profiles),
+            None => menu,
+        })
+        .submenu("Twitch", twitch)
+        .when(|menu| {
+            if debug_menu {
+                menu.submenu("Debug", create_debug_menu())
+            } else {
+                menu
+            }
+        })
+        .separator()
+        .checkable(
+            "Notifications",
+            notifications_enabled,
+            Events::ToggleNotifications,
+        )
+        .checkable(
+            platform::START_AT_LOGIN_LABEL,
+            start_at_login,
+            Events::ToggleStartAtLogin,
+        )
         .separator()
         .item("E&xit", Events::Exit)
 }
@@ -180,41 +1034,553 @@ fn create_tray_menu(config: &Arc<Mutex<State>>) -> MenuBuilder<Events> {
 fn create_channels_menu(config: &Arc<Mutex<State>>) -> MenuBuilder<Events> {
     let mut menu_builder: MenuBuilder<Events> = MenuBuilder::new();
 
-    let config = config.lock().unwrap();
+    let config = config.lock_or_recover();
+
+    for task in diagnostics::failing_tasks() {
+        menu_builder = menu_builder.with(MenuItem::Item {
+            name: format!("⚠ The {} task keeps failing - restarting it", task),
+            disabled: true,
+            id: Events::ClickTrayIcon,
+        });
+    }
+
+    // The retries absorb the temporary failures, this is only shown when they didn't help.
+    if let Some(failure) = &config.update_failure {
+        let name = format!(
+            "⚠ Updates failing for {} ({}) - retrying",
+            format_duration(failure.since.elapsed()),
+            failure.reason
+        );
+
+        menu_builder = menu_builder
+            .with(MenuItem::Item {
+                name,
+                disabled: true,
+                id: Events::ClickTrayIcon,
+            })
+            .separator();
+    } else if config.is_loading() {
+        // Until then the channels are neither live nor offline.
+        menu_builder = menu_builder
+            .with(MenuItem::Item {
+                name: String::from("Fetching stream status…"),
+                disabled: true,
+                id: Events::ClickTrayIcon,
+            })
+            .separator();
+    } else if let Some(name) = schedule_label(*twitch::SCHEDULE.lock_or_recover()) {
+        menu_builder = menu_builder
+            .with(MenuItem::Item {
+                name,
+                disabled: true,
+                id: Events::ClickTrayIcon,
+            })
+            .separator();
+    }
+
+    // Only needed for the start times, which are shown in the local time.
+    let formats_have_since = [&config.menu_format, &config.menu_format_offline]
+        .iter()
+        .any(|format| format.as_deref().unwrap_or_default().contains("{since}"));
+
+    let utc_offset =
+        if config.live_time_display != config::LiveTimeDisplay::Uptime || formats_have_since {
+            logging::utc_offset()
+        } else {
+            None
+        };
+
+    // Only the digits from 1 to 9 can be used as access keys.
+    const MAX_ACCELERATORS: usize = 9;
+
+    // It looks for streamlink (for VLC), only once for all the channels.
+    let can_rewind = launch::can_rewind(&config);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    if config.unseen_count() > 0 {
+        menu_builder = menu_builder
+            .item("Mark all as seen", Events::MarkAllSeen)
+            .separator();
+    }
 
     for (index, channel) in config.channels.iter().enumerate() {
-        let mut result = channel.name.to_string();
+        let format = if channel.is_online() {
+            &config.menu_format
+        } else {
+            &config.menu_format_offline
+        };
+
+        let mut result = match format {
+            Some(format) => format_channel(format, channel, &config, utc_offset),
+            None => {
+                let mut result = channel.name.to_string();
+
+                if let Some(badge) = broadcaster_badge(channel, &config) {
+                    result.push(' ');
+                    result.push_str(badge);
+                }
+
+                if channel.is_online() {
+                    //result.push_str(" (LIVE)");
+
+                    // e.g. "Title [Just Chatting]", long titles would make the menu as wide as the screen.
+                    let details: Vec<String> = channel
+                        .title
+                        .iter()
+                        .cloned()
+                        .chain(channel.game.iter().map(|game| format!("[{}]", game)))
+                        .collect();
+
+                    if !details.is_empty() {
+                        result.push_str(" - ");
+                        result.push_str(&text::sanitize(
+                            &details.join(" "),
+                            config.menu_title_length,
+                        ));
+                    }
+
+                    if let Some(viewers) = channel.viewers {
+                        result.push_str(" (");
+                        result.push_str(config.viewer_format.format(viewers).as_str());
+                        result.push_str(" viewers");
+
+                        if let Some(trend) = viewer_trend(channel, &config) {
+                            result.push(' ');
+                            result.push_str(trend);
+                        }
+
+                        result.push(')');
+                    };
 
-        if channel.is_online {
-            //result.push_str(" (LIVE)");
+                    if let Some(started_at) = channel.started_at {
+                        result.push_str(&live_time(
+                            config.live_time_display,
+                            started_at,
+                            utc_offset,
+                        ));
+                    }
+                }
+
+                result
+            }
+        };
 
-            if let Some(title) = &channel.title {
-                result.push_str(" - ");
-                result.push_str(title.as_str());
-            };
+        if channel.unseen {
+            result.push_str(" (new)");
+        }
 
-            if let Some(viewers) = channel.viewers {
-                result.push_str(" (");
-                result.push_str(viewers.to_string().as_str());
-                result.push_str(" viewers)");
-            };
+        if channel.not_found() {
+            result.push_str(" (not found)");
         }
 
-        menu_builder = menu_builder.clone().with(MenuItem::Item {
-            id: Events::OpenChannel(index),
-            name: result,
-            disabled: !channel.is_online,
-            icon: None,
-        });
+        if channel.is_inactive(config.inactive_after_days, now) {
+            result.push_str(" (inactive)");
+        }
+
+        // The titles can have their own '&', which would become access keys.
+        let mut result = platform::escape_label(&result);
+
+        if config.menu_accelerators && index < MAX_ACCELERATORS {
+            result = format!("&{} {}", index + 1, result);
+        }
+
+        let mut channel_menu = MenuBuilder::new()
+            .when(|menu| match &channel.note {
+                Some(note) => menu.with(MenuItem::Item {
+                    name: format!("Note: {}", platform::escape_label(note)),
+                    disabled: true,
+                    id: Events::ClickTrayIcon,
+                }),
+                None => menu,
+            })
+            .with(MenuItem::Item {
+                id: Events::OpenChannel(channel.name.clone()),
+                name: String::from("Open stream"),
+                // If we don't know, the user might.
+                disabled: matches!(channel.status, config::ChannelStatus::Offline { .. }),
+            })
+            .with(MenuItem::Item {
+                id: Events::OpenStreamAndChat(channel.name.clone()),
+                name: String::from("Open stream + chat"),
+                disabled: matches!(channel.status, config::ChannelStatus::Offline { .. }),
+            })
+            .when(|menu| {
+                if channel.is_online() {
+                    menu.submenu(
+                        "Watch from the start",
+                        create_rewind_menu(channel, &config, can_rewind),
+                    )
+                } else {
+                    menu
+                }
+            })
+            .item("Open chat", Events::OpenChat(channel.name.clone()))
+            .separator();
+
+        if !channel.title_history.is_empty() {
+            channel_menu = channel_menu
+                .submenu("Title history", create_title_history_menu(channel))
+                .separator();
+        }
+
+        for kind in NotificationKind::into_enum_iter() {
+            let is_enabled = config.is_notification_enabled(&channel.name, kind);
+            let event = Events::ToggleNotification(channel.name.clone(), kind);
+
+            channel_menu = channel_menu.checkable(&kind.to_string(), is_enabled, event);
+        }
+
+        channel_menu = channel_menu.separator();
+
+        channel_menu = match config.snoozed_until(&channel.name) {
+            Some(until) => channel_menu
+                .with(MenuItem::Item {
+                    name: snoozed_label(until),
+                    disabled: true,
+                    id: Events::ClickTrayIcon,
+                })
+                .item("Unsnooze", Events::Unsnooze(channel.name.clone())),
+            None => channel_menu.submenu("Snooze", create_snooze_menu(&channel.name)),
+        };
+
+        menu_builder = menu_builder.submenu(&result, channel_menu);
     }
 
     menu_builder
 }
 
+/// The "↑" or "↓" of the viewers, with "viewer_trend".
+fn viewer_trend(channel: &config::Channel, config: &State) -> Option<&'static str> {
+    if !config.viewer_trend {
+        return None;
+    }
+
+    channel.viewer_trend.symbol()
+}
+
+/// Only enabled with a player that can rewind (see `launch::can_rewind`), otherwise it says why.
+fn create_rewind_menu(
+    channel: &config::Channel,
+    config: &State,
+    can_rewind: bool,
+) -> MenuBuilder<Events> {
+    let menu = MenuBuilder::new().when(|menu| {
+        if can_rewind {
+            menu
+        } else {
+            menu.with(MenuItem::Item {
+                name: String::from("Needs streamlink as the player"),
+                disabled: true,
+                id: Events::ClickTrayIcon,
+            })
+            .separator()
+        }
+    });
+
+    let menu = menu.with(MenuItem::Item {
+        name: String::from("From the start"),
+        disabled: !can_rewind,
+        id: Events::WatchFrom(channel.name.clone(), Rewind::Start),
+    });
+
+    config.rewind_offsets.iter().fold(menu, |menu, minutes| {
+        menu.with(MenuItem::Item {
+            name: format!("Rewind {} min", minutes),
+            disabled: !can_rewind,
+            id: Events::WatchFrom(channel.name.clone(), Rewind::Minutes(*minutes)),
+        })
+    })
+}
+
+// How long a channel can be snoozed for, from its submenu.
+const SNOOZE_TIMES: [(&str, u64); 4] = [
+    ("1 hour", 60 * 60),
+    ("4 hours", 4 * 60 * 60),
+    ("12 hours", 12 * 60 * 60),
+    ("1 day", 24 * 60 * 60),
+];
+
+fn create_snooze_menu(channel: &str) -> MenuBuilder<Events> {
+    SNOOZE_TIMES
+        .iter()
+        .fold(MenuBuilder::new(), |menu, (name, seconds)| {
+            menu.item(name, Events::Snooze(String::from(channel), *seconds))
+        })
+}
+
+/// Opens the stream of a channel with the current player, it's opened in the background (see `launch::in_background`).
+/// The channel might have been removed from the config since it was asked (e.g. after the menu was built).
+fn open_channel(state: &Arc<Mutex<State>>, name: &str, with_chat: bool) {
+    let mut local_state = state.lock_or_recover();
+
+    let channel = match local_state.find_channel_mut(name) {
+        Some(channel) => {
+            channel.unseen = false;
+            channel.clone()
+        }
+        None => {
+            send_notification(
+                "Couldn't open the stream.",
+                &format!("{} isn't in the channels anymore.", name),
+            );
+            return;
+        }
+    };
+
+    let config = local_state.clone();
+
+    drop(local_state);
+
+    launch::in_background(move || {
+        if with_chat || channel.also_open_chat {
+            launch::open_stream_with_chat(&config, &channel)
+        } else {
+            launch::open_stream(&config, &channel)
+        }
+    });
+}
+
+/// Asks to remove the inactive channels from the config, and removes them if the user agrees.
+fn clean_up_inactive(config: &State) {
+    let inactive = config.inactive_channels();
+
+    if inactive.is_empty() {
+        return;
+    }
+
+    let names: Vec<&str> = inactive
+        .iter()
+        .map(|channel| channel.name.as_str())
+        .collect();
+
+    let text = format!(
+        "These channels weren't live for {} days:\n\n{}\n\nRemove them from the config? It's backed up first.",
+        config.inactive_after_days,
+        names.join("\n")
+    );
+
+    if !platform::confirm("Clean up inactive channels", &text) {
+        return;
+    }
+
+    let inactive: Vec<config::Channel> = inactive.into_iter().cloned().collect();
+
+    match config::remove_channels(config, &inactive) {
+        Ok(()) => {
+            log::info!("Removed the inactive channels: {}", names.join(", "));
+
+            send_notification(
+                "Removed the inactive channels.",
+                &format!("{} removed: {}", inactive.len(), names.join(", ")),
+            );
+        }
+        Err(e) => {
+            let message = format!("Couldn't remove the inactive channels: {}", e);
+
+            diagnostics::record_error(&message);
+            send_notification("Couldn't remove the inactive channels.", &e.to_string());
+        }
+    }
+}
+
+/// Saves the app data (e.g. the title history) where the user chooses, to import it on another machine.
+fn export_app_data() {
+    let path = match platform::choose_file_to_save("Export app data", "taskbar-twitch-data.json") {
+        Some(path) => path,
+        None => return,
+    };
+
+    match app_state::export(&path) {
+        Ok(()) => {
+            log::info!("Exported the app data to {}", path.display());
+
+            send_notification(
+                "Exported the app data.",
+                "Your credentials and tokens aren't included.",
+            );
+        }
+        Err(e) => {
+            diagnostics::record_error(&format!("Couldn't export the app data: {}", e));
+            send_notification("Couldn't export the app data.", &e.to_string());
+        }
+    }
+}
+
+/// Merges an export of the app data with ours, and gives the channels their imported data.
+fn import_app_data(state: &Arc<Mutex<State>>, update_proxy: &EventLoopProxy<Events>) {
+    let path = match platform::choose_file_to_open("Import app data") {
+        Some(path) => path,
+        None => return,
+    };
+
+    let channels = state.lock_or_recover().channels.clone();
+
+    let imported = match app_state::import(&path, &channels) {
+        Ok(imported) => imported,
+        Err(e) => {
+            diagnostics::record_error(&format!(
+                "Couldn't import the app data from {}: {}",
+                path.display(),
+                e
+            ));
+            send_notification("Couldn't import the app data.", &e);
+            return;
+        }
+    };
+
+    {
+        let mut local_state = state.lock_or_recover();
+
+        app_state::restore_title_history(&mut local_state.channels);
+        app_state::restore_broadcasters(&mut local_state.channels);
+        app_state::restore_live_history(&mut local_state.channels);
+        local_state.snoozed = app_state::restore_snoozed(&local_state.channels);
+    }
+
+    log::info!(
+        "Imported the app data of {} channels from {} ({} skipped)",
+        imported.channels.len(),
+        path.display(),
+        imported.skipped.len()
+    );
+
+    let mut text = format!("{} channels got their data back.", imported.channels.len());
+
+    if !imported.skipped.is_empty() {
+        text.push_str(&format!(
+            " Not in the config: {}",
+            imported.skipped.join(", ")
+        ));
+    }
+
+    send_notification("Imported the app data.", &text);
+
+    update_proxy.send_event(Events::UpdatedChannels).ok();
+}
+
+/// e.g. "Snoozed until 21:05", or for how long if we don't know the local time.
+fn snoozed_label(until: u64) -> String {
+    let until = UNIX_EPOCH + Duration::from_secs(until);
+
+    match logging::utc_offset() {
+        Some(offset) => format!(
+            "Snoozed until {}",
+            logging::format_clock_time(until, offset)
+        ),
+        None => format!(
+            "Snoozed for {}",
+            format_duration(until.duration_since(SystemTime::now()).unwrap_or_default())
+        ),
+    }
+}
+
+/// The text of a channel from the "menu_format" of the config, see `config::MENU_PLACEHOLDERS`.
+fn format_channel(
+    format: &str,
+    channel: &config::Channel,
+    config: &State,
+    utc_offset: Option<i64>,
+) -> String {
+    let uptime = channel
+        .started_at
+        .and_then(|started_at| started_at.elapsed().ok())
+        .map(summary::format_streamed);
+
+    let since = channel
+        .started_at
+        .zip(utc_offset)
+        .map(|(started_at, offset)| logging::format_clock_time(started_at, offset));
+
+    template::render(
+        format,
+        &[
+            ("name", Some(channel.name.clone())),
+            ("title", channel.title.clone()),
+            ("game", channel.game.clone()),
+            (
+                "viewers",
+                channel.viewers.map(|v| config.viewer_format.format(v)),
+            ),
+            ("uptime", uptime),
+            ("since", since),
+            ("note", channel.note.clone()),
+            ("trend", viewer_trend(channel, config).map(String::from)),
+            (
+                "badge",
+                broadcaster_badge(channel, config).map(String::from),
+            ),
+        ],
+    )
+}
+
+/// The "✓" of the partners or the "●" of the affiliates, with "broadcaster_badges".
+fn broadcaster_badge(channel: &config::Channel, config: &State) -> Option<&'static str> {
+    if !config.broadcaster_badges {
+        return None;
+    }
+
+    channel.broadcaster.as_ref()?.broadcaster_type.badge()
+}
+
+/// e.g. " (live 2h 13m, since 19:05)", computed when the menu is built so it doesn't depend on the last update.
+fn live_time(
+    display: config::LiveTimeDisplay,
+    started_at: SystemTime,
+    utc_offset: Option<i64>,
+) -> String {
+    let uptime = started_at
+        .elapsed()
+        .ok()
+        .map(|uptime| format!("live {}", summary::format_streamed(uptime)));
+
+    let start_time = utc_offset
+        .map(|offset| format!("since {}", logging::format_clock_time(started_at, offset)));
+
+    let parts: Vec<String> = match display {
+        config::LiveTimeDisplay::Uptime => uptime.into_iter().collect(),
+        config::LiveTimeDisplay::StartTime => start_time.into_iter().collect(),
+        config::LiveTimeDisplay::Both => uptime.into_iter().chain(start_time).collect(),
+    };
+
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", parts.join(", "))
+    }
+}
+
+/// The newest titles come first, e.g. "Programming a game (5 minutes ago)".
+fn create_title_history_menu(channel: &config::Channel) -> MenuBuilder<Events> {
+    let now = SystemTime::now();
+
+    channel
+        .title_history
+        .iter()
+        .rev()
+        .fold(MenuBuilder::new(), |menu, change| {
+            let at = UNIX_EPOCH + Duration::from_secs(change.at);
+            let ago = now.duration_since(at).unwrap_or_default();
+
+            menu.with(MenuItem::Item {
+                name: format!(
+                    "{} ({} ago)",
+                    platform::escape_label(&change.title),
+                    format_duration(ago)
+                ),
+                disabled: true,
+                id: Events::ClickTrayIcon,
+            })
+        })
+}
+
 fn create_players_menu(config: &Arc<Mutex<State>>) -> MenuBuilder<Events> {
     let mut menu_builder: MenuBuilder<Events> = MenuBuilder::new();
 
-    let config = config.lock().unwrap();
+    let config = config.lock_or_recover();
 
     for player in OpenStreamUsing::into_enum_iter() {
         // If we already selected a player for the current session, use it.
@@ -227,55 +1593,299 @@ fn create_players_menu(config: &Arc<Mutex<State>>) -> MenuBuilder<Events> {
 
         let event = Events::ChangeCurrentPlayer(player);
 
+        // It can't be used without its command, the label tells why.
+        if player == OpenStreamUsing::Custom && config.custom_player().is_none() {
+            menu_builder = menu_builder.with(MenuItem::Checkable {
+                id: event,
+                name: format!("{} (needs a \"custom_player\")", player),
+                is_checked: is_selected,
+                disabled: true,
+            });
+
+            continue;
+        }
+
         menu_builder = menu_builder.checkable(&player.to_string(), is_selected, event);
     }
 
+    // Only used by the Browser and Popout players.
     menu_builder
+        .separator()
+        .checkable(
+            "Open as an app window",
+            config.app_window(),
+            Events::ToggleAppWindow,
+        )
+        .checkable(
+            "Open in a private window",
+            config.incognito(),
+            Events::ToggleIncognito,
+        )
 }
 
-fn send_notification(title: &str, text: &str) {
-    let icon_path = std::fs::canonicalize("./resources/twitch.ico")
-        .map(|path| remove_extended_path_prefix(path))
-        .unwrap_or_default();
+/// Only available if the config has "watch_groups".
+/// e.g. "Squad (2 of 3 live)", a group without any live channel can't be opened.
+fn create_watch_groups_menu(config: &Arc<Mutex<State>>) -> Option<MenuBuilder<Events>> {
+    let config = config.lock_or_recover();
+
+    if config.watch_groups.is_empty() {
+        return None;
+    }
+
+    let menu_builder =
+        config
+            .watch_groups
+            .iter()
+            .fold(MenuBuilder::new(), |menu, (group, members)| {
+                let live = members
+                    .iter()
+                    .filter(|name| config.find_channel(name).is_some_and(|c| c.is_online()))
+                    .count();
+
+                menu.with(MenuItem::Item {
+                    name: format!(
+                        "{} ({} of {} live)",
+                        platform::escape_label(group),
+                        live,
+                        members.len()
+                    ),
+                    disabled: live == 0,
+                    id: Events::OpenWatchGroup(group.clone()),
+                })
+            });
+
+    Some(menu_builder)
+}
+
+/// Only available if there's at least one profile other than the default one.
+fn create_profiles_menu(config: &Arc<Mutex<State>>) -> Option<MenuBuilder<Events>> {
+    let profiles = config::profiles();
+
+    if profiles.len() <= 1 {
+        return None;
+    }
+
+    let mut menu_builder: MenuBuilder<Events> = MenuBuilder::new();
+
+    let config = config.lock_or_recover();
+
+    for (name, config_file) in profiles {
+        let is_selected = config.config_file == config_file;
+
+        let event = Events::SwitchProfile(config_file);
+
+        menu_builder = menu_builder.checkable(&name, is_selected, event);
+    }
+
+    Some(menu_builder)
+}
+
+fn create_twitch_menu(config: &Arc<Mutex<State>>) -> MenuBuilder<Events> {
+    let config = config.lock_or_recover();
+
+    let token_description = match &config.token {
+        Some(token) => {
+            let age = token.obtained_at.elapsed();
+
+            let mut result = format!("Token obtained {} ago", format_duration(age));
+
+            if let Some(expires_in) = token.expires_in {
+                let remaining = expires_in.saturating_sub(age);
+
+                result.push_str(
+                    format!(", expires in about {}", format_duration(remaining)).as_str(),
+                );
+            }
 
-    // As we don't have an 'AppUserModeID', we'll just steal an appropriate one.
-    Toast::new("Microsoft.Windows.MediaPlayer32")
-        .icon(
-            &Path::new(&icon_path),
-            winrt_notification::IconCrop::Circular,
-            "application icon",
+            result
+        }
+        None => String::from("No token obtained yet"),
+    };
+
+    MenuBuilder::new()
+        .with(MenuItem::Item {
+            name: token_description,
+            disabled: true,
+            id: Events::ClickTrayIcon,
+        })
+        .item("Re-authenticate", Events::Reauthenticate)
+        .separator()
+        .item(
+            "Report a problem",
+            Events::ReportProblem {
+                include_channels: false,
+            },
+        )
+        .item(
+            "Report a problem (include channel names)",
+            Events::ReportProblem {
+                include_channels: true,
+            },
         )
-        .title(title)
-        .text1(text)
-        .sound(Some(winrt_notification::Sound::Reminder))
-        .duration(winrt_notification::Duration::Short)
-        .show()
-        .expect("Unable to create the notification.");
+        .item("Copy debug info", Events::CopyDebugInfo)
 }
 
-fn remove_extended_path_prefix(path: PathBuf) -> String {
-    const PREFIX: &str = r#"\\?\"#;
+/// The counters of the session, they don't need the lock of the state.
+fn create_debug_menu() -> MenuBuilder<Events> {
+    let mut menu_builder = MenuBuilder::new();
 
-    let p = path.display().to_string();
+    for line in METRICS.lines() {
+        menu_builder = menu_builder.with(MenuItem::Item {
+            name: line,
+            disabled: true,
+            id: Events::ClickTrayIcon,
+        });
+    }
 
-    if p.starts_with(PREFIX) {
-        p[PREFIX.len()..].to_string()
+    menu_builder
+        .separator()
+        .item("Reset counters", Events::ResetCounters)
+}
+
+/// The top of the "Channels" menu, e.g. "Updated 45s ago · next in 15s", as of when the menu is built.
+/// Nothing before the first update.
+fn schedule_label(schedule: twitch::Schedule) -> Option<String> {
+    let short = |duration: Duration| match duration.as_secs() {
+        seconds if seconds < 60 => format!("{}s", seconds),
+        seconds => format!("{}m", seconds / 60),
+    };
+
+    let last_update = schedule.last_update?;
+
+    let next = match schedule.next_update {
+        Some(next_update) => match next_update.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => format!("next in {}", short(remaining)),
+            _ => String::from("updating…"),
+        },
+        None => String::from("paused"),
+    };
+
+    Some(format!(
+        "Updated {} ago · {}",
+        short(last_update.elapsed()),
+        next
+    ))
+}
+
+/// Formats a duration using only its largest unit (e.g. "5 minutes", "59 days").
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    let (value, unit) = if seconds < 60 {
+        (seconds, "second")
+    } else if seconds < 60 * 60 {
+        (seconds / 60, "minute")
+    } else if seconds < 24 * 60 * 60 {
+        (seconds / (60 * 60), "hour")
+    } else {
+        (seconds / (24 * 60 * 60), "day")
+    };
+
+    if value == 1 {
+        format!("{} {}", value, unit)
     } else {
-        p
+        format!("{} {}s", value, unit)
     }
 }
 
-fn set_panic_hook() {
-    std::panic::set_hook(Box::new(|info| {
-        let mut message = String::new();
+/// If the application crashed since it was last started, the crash is included in the bug reports.
+fn check_last_crash() {
+    let mut app_state = app_state::read();
 
-        if let Some(s) = info.payload().downcast_ref::<&str>() {
-            message.push_str(s);
-        } else if let Some(s) = info.payload().downcast_ref::<String>() {
-            message.push_str(s);
-        } else {
-            message.push_str("Unknown error.");
+    let seconds = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default()
+    };
+
+    if let (Some(last_start), Some(crashed_at)) = (app_state.last_start, logging::last_crash()) {
+        if seconds(crashed_at) >= last_start {
+            let crashed_at = logging::format_time(crashed_at);
+
+            log::warn!("The previous session crashed at {}", crashed_at);
+
+            diagnostics::record_crash(&crashed_at);
         }
+    }
+
+    app_state.last_start = Some(seconds(SystemTime::now()));
+
+    if let Err(e) = app_state::write(&app_state) {
+        diagnostics::record_error(&format!("Couldn't save the app state: {}", e));
+    }
+}
+
+// The panics of the background tasks are only notified once.
+static PANIC_NOTIFIED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // Set while running a background task, its panics are handled by `supervise`.
+    static SUPERVISED: Cell<bool> = const { Cell::new(false) };
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        String::from(*s)
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        String::from("Unknown error.")
+    }
+}
+
+/// Runs a background task, which is restarted if it panics (the panic itself was already logged by the hook),
+///  so that a single bad update doesn't stop the updates while the tray icon keeps looking fine.
+/// Only the first panic is notified, the tasks keep restarting and the menu shows the ones that keep failing.
+/// The process only exits when the event loop itself panics.
+fn supervise<F: Fn()>(name: &'static str, task: F) {
+    SUPERVISED.with(|supervised| supervised.set(true));
+
+    let mut restarts = 0;
+    let mut restart_time = TASK_RESTART_TIME;
+
+    loop {
+        let started_at = Instant::now();
+
+        let payload = match std::panic::catch_unwind(AssertUnwindSafe(&task)) {
+            Ok(()) => return,
+            Err(payload) => payload,
+        };
+
+        let message = panic_message(payload.as_ref());
+
+        if started_at.elapsed() >= TASK_STABLE_TIME {
+            restarts = 0;
+            restart_time = TASK_RESTART_TIME;
+
+            diagnostics::clear_failing_task(name);
+        }
+
+        restarts += 1;
+
+        diagnostics::record_error(&format!("The {} task panicked: {}", name, message));
+
+        if !PANIC_NOTIFIED.swap(true, Ordering::Relaxed) {
+            send_notification(
+                "A runtime error occurred.",
+                &format!("The {} task will be restarted. ({})", name, message),
+            );
+        }
+
+        if restarts == MAX_TASK_RESTARTS {
+            diagnostics::record_failing_task(name);
+        }
+
+        log::warn!("Restarting the {} task in {:?}", name, restart_time);
+
+        std::thread::sleep(restart_time);
+
+        restart_time = (restart_time * 2).min(MAX_TASK_RESTART_TIME);
+    }
+}
+
+fn set_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let mut message = panic_message(info.payload());
 
         if let Some(location) = info.location() {
             message.push_str(
@@ -283,6 +1893,30 @@ fn set_panic_hook() {
             );
         }
 
+        log::error!("A runtime error occurred: {}", message);
+
+        // Only captured if it's enabled (RUST_BACKTRACE), otherwise it's too slow.
+        let backtrace = std::backtrace::Backtrace::capture();
+
+        let mut report = format!("[{}] {}\n{}\n", logging::timestamp(), APP_VERSION, message);
+
+        if backtrace.status() == std::backtrace::BacktraceStatus::Captured {
+            report.push_str(format!("{}\n", backtrace).as_str());
+        }
+
+        logging::write_crash(&report);
+
+        // The task will be restarted.
+        if SUPERVISED.with(|supervised| supervised.get()) {
+            return;
+        }
+
+        // The log is written by another thread, make sure that the error is on disk before we exit.
+        log::logger().flush();
+
+        // Even with "notifications_enabled": false, we are exiting and the user should know why.
+        platform::set_notifications_enabled(true);
+
         send_notification("A runtime error occurred.", message.as_str());
 
         std::process::exit(1)
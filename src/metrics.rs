@@ -0,0 +1,144 @@
+//! Counters of the current session, shown in the "Debug" submenu and in the debug info.
+//! They are atomics so that the menu can read them without the lock of the state.
+
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::NotificationKind;
+
+// The values that we didn't get yet.
+const NONE: i64 = -1;
+
+pub struct Metrics {
+    polls: AtomicU64,
+    failed_polls: AtomicU64,
+    last_status: AtomicI64,
+    rate_limit_remaining: AtomicI64,
+    // In seconds since the Unix epoch.
+    token_obtained_at: AtomicI64,
+    config_reloads: AtomicU64,
+    live_notifications: AtomicU64,
+    title_notifications: AtomicU64,
+    game_notifications: AtomicU64,
+    offline_notifications: AtomicU64,
+}
+
+pub static METRICS: Metrics = Metrics {
+    polls: AtomicU64::new(0),
+    failed_polls: AtomicU64::new(0),
+    last_status: AtomicI64::new(NONE),
+    rate_limit_remaining: AtomicI64::new(NONE),
+    token_obtained_at: AtomicI64::new(NONE),
+    config_reloads: AtomicU64::new(0),
+    live_notifications: AtomicU64::new(0),
+    title_notifications: AtomicU64::new(0),
+    game_notifications: AtomicU64::new(0),
+    offline_notifications: AtomicU64::new(0),
+};
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn value(counter: &AtomicI64) -> Option<i64> {
+    Some(counter.load(Ordering::Relaxed)).filter(|value| *value != NONE)
+}
+
+impl Metrics {
+    /// An update of the Twitch channels, after its retries.
+    pub fn record_poll(&self, failed: bool) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+
+        if failed {
+            self.failed_polls.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The status of the last response from Twitch, with what was left of the rate limit.
+    pub fn record_response(&self, status: u16, rate_limit_remaining: Option<i64>) {
+        self.last_status.store(status as i64, Ordering::Relaxed);
+
+        if let Some(remaining) = rate_limit_remaining {
+            self.rate_limit_remaining
+                .store(remaining, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_token(&self) {
+        self.token_obtained_at.store(now(), Ordering::Relaxed);
+    }
+
+    pub fn record_config_reload(&self) {
+        self.config_reloads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_notification(&self, kind: NotificationKind) {
+        let counter = match kind {
+            NotificationKind::Live => &self.live_notifications,
+            NotificationKind::TitleChanged => &self.title_notifications,
+            NotificationKind::GameChanged => &self.game_notifications,
+            NotificationKind::Offline => &self.offline_notifications,
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The token isn't a counter, it's kept.
+    pub fn reset(&self) {
+        for counter in [
+            &self.polls,
+            &self.failed_polls,
+            &self.config_reloads,
+            &self.live_notifications,
+            &self.title_notifications,
+            &self.game_notifications,
+            &self.offline_notifications,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+
+        self.last_status.store(NONE, Ordering::Relaxed);
+        self.rate_limit_remaining.store(NONE, Ordering::Relaxed);
+    }
+
+    /// One line per metric, e.g. "Polls: 12".
+    pub fn lines(&self) -> Vec<String> {
+        let optional = |value: Option<i64>| match value {
+            Some(value) => value.to_string(),
+            None => String::from("none yet"),
+        };
+
+        let token_age = match value(&self.token_obtained_at) {
+            Some(obtained_at) => format!("{} minutes", (now() - obtained_at).max(0) / 60),
+            None => String::from("not obtained"),
+        };
+
+        vec![
+            format!("Polls: {}", self.polls.load(Ordering::Relaxed)),
+            format!(
+                "Failed polls: {}",
+                self.failed_polls.load(Ordering::Relaxed)
+            ),
+            format!("Last HTTP status: {}", optional(value(&self.last_status))),
+            format!(
+                "Ratelimit-Remaining: {}",
+                optional(value(&self.rate_limit_remaining))
+            ),
+            format!("Token age: {}", token_age),
+            format!(
+                "Config reloads: {}",
+                self.config_reloads.load(Ordering::Relaxed)
+            ),
+            format!(
+                "Notifications: {} live, {} title, {} game, {} offline",
+                self.live_notifications.load(Ordering::Relaxed),
+                self.title_notifications.load(Ordering::Relaxed),
+                self.game_notifications.load(Ordering::Relaxed),
+                self.offline_notifications.load(Ordering::Relaxed)
+            ),
+        ]
+    }
+}
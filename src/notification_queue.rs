@@ -0,0 +1,156 @@
+//! The notifications of the channels are shown a few seconds apart ("notification_gap"), in order,
+//!  instead of on top of each other (with their sounds stacking up).
+//! While one is waiting, a newer notification for the same channel takes its place (e.g. it went live and then offline).
+//! The other notifications (e.g. the errors, or the panics) are shown right away.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use crate::config::State;
+use crate::lock::RecoverLock;
+use crate::platform;
+
+struct Queued {
+    title: String,
+    text: String,
+    tag: Option<String>,
+    quiet: bool,
+    long: bool,
+    icon: Option<PathBuf>,
+    link: Option<String>,
+}
+
+impl Queued {
+    fn show(&self) {
+        platform::show_notification(&platform::Notification {
+            title: &self.title,
+            text: &self.text,
+            tag: self.tag.as_deref(),
+            quiet: self.quiet,
+            long: self.long,
+            icon: self.icon.as_deref(),
+            link: self.link.as_deref(),
+        });
+    }
+}
+
+static QUEUE: Mutex<VecDeque<Queued>> = Mutex::new(VecDeque::new());
+static QUEUED: Condvar = Condvar::new();
+
+// Without the thread (e.g. in the headless mode), the notifications are shown right away.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Shows a notification about a channel once the previous ones had their time on screen.
+pub fn push(notification: &platform::Notification) {
+    let queued = Queued {
+        title: String::from(notification.title),
+        text: String::from(notification.text),
+        tag: notification.tag.map(String::from),
+        quiet: notification.quiet,
+        long: notification.long,
+        icon: notification.icon.map(PathBuf::from),
+        link: notification.link.map(String::from),
+    };
+
+    if !RUNNING.load(Ordering::Relaxed) {
+        queued.show();
+        return;
+    }
+
+    let mut queue = QUEUE.lock_or_recover();
+
+    let outdated = queued.tag.as_ref().and_then(|tag| {
+        queue
+            .iter_mut()
+            .find(|other| other.tag.as_ref() == Some(tag))
+    });
+
+    match outdated {
+        Some(outdated) => {
+            debug!("Replaced the queued notification: {}", outdated.text);
+            *outdated = queued;
+        }
+        None => queue.push_back(queued),
+    }
+
+    QUEUED.notify_one();
+}
+
+/// Shows the queued notifications until it's stopped.
+pub struct Sender {
+    thread: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Sender {
+    pub fn spawn(config: Arc<Mutex<State>>) -> Sender {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        RUNNING.store(true, Ordering::Relaxed);
+
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || send(config, thread_stop));
+
+        Sender {
+            thread: Some(thread),
+            stop,
+        }
+    }
+
+    /// The notifications that are still queued are dropped, we are exiting.
+    pub fn stop(&mut self) {
+        RUNNING.store(false, Ordering::Relaxed);
+        self.stop.store(true, Ordering::Relaxed);
+
+        QUEUE.lock_or_recover().clear();
+        QUEUED.notify_one();
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn send(config: Arc<Mutex<State>>, stop: Arc<AtomicBool>) {
+    // When the last notification was shown.
+    let mut last_shown: Option<Instant> = None;
+
+    loop {
+        // Before the queue is locked, the channels queue their notifications while the config is locked.
+        let gap = Duration::from_secs(config.lock_or_recover().notification_gap);
+
+        let mut queue = QUEUE.lock_or_recover();
+
+        while queue.is_empty() && !stop.load(Ordering::Relaxed) {
+            queue = QUEUED.wait(queue).unwrap_or_else(|e| e.into_inner());
+        }
+
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let wait = last_shown.map_or(Duration::ZERO, |shown| gap.saturating_sub(shown.elapsed()));
+
+        if !wait.is_zero() {
+            // It's woken up early when it's stopped, or if something else is queued (which doesn't change the wait).
+            drop(QUEUED.wait_timeout(queue, wait));
+            continue;
+        }
+
+        let notification = queue.pop_front();
+
+        // So that more can be queued while it's shown.
+        drop(queue);
+
+        if let Some(notification) = notification {
+            notification.show();
+            last_shown = Some(Instant::now());
+        }
+    }
+}
@@ -0,0 +1,364 @@
+pub mod startup;
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use winit::event_loop::EventLoopProxy;
+use winit::window::Window;
+
+use super::{LocalTime, MenuBuilder, MenuItem, Notification, Theme, TrayIconBase};
+use crate::Events;
+
+// The notifications always have the icon of the application here.
+pub const NOTIFICATION_AVATARS: bool = false;
+
+pub const START_AT_LOGIN_LABEL: &str = "Start at login";
+
+/// A StatusNotifierItem, which is supported by KDE and by GNOME (with the AppIndicator extension).
+pub struct TrayIcon {
+    handle: ksni::Handle<Tray>,
+}
+
+struct Tray {
+    proxy: EventLoopProxy<Events>,
+    tooltip: String,
+    icon: Vec<ksni::Icon>,
+    menu: MenuBuilder<Events>,
+}
+
+impl ksni::Tray for Tray {
+    fn id(&self) -> String {
+        String::from("taskbar-twitch")
+    }
+
+    fn title(&self) -> String {
+        self.tooltip.clone()
+    }
+
+    fn icon_pixmap(&self) -> Vec<ksni::Icon> {
+        self.icon.clone()
+    }
+
+    fn tool_tip(&self) -> ksni::ToolTip {
+        ksni::ToolTip {
+            title: self.tooltip.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.proxy.send_event(Events::ClickTrayIcon).ok();
+    }
+
+    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+        convert_menu(&self.menu)
+    }
+}
+
+impl TrayIconBase for TrayIcon {
+    fn build(
+        proxy: EventLoopProxy<Events>,
+        icon: &'static [u8],
+        tooltip: &str,
+        menu: &MenuBuilder<Events>,
+    ) -> Result<Self, String> {
+        let service = ksni::TrayService::new(Tray {
+            proxy,
+            tooltip: String::from(tooltip),
+            icon: vec![decode_icon(icon)?],
+            menu: menu.clone(),
+        });
+
+        let handle = service.handle();
+        service.spawn();
+
+        Ok(TrayIcon { handle })
+    }
+
+    fn set_menu(&mut self, menu: &MenuBuilder<Events>) -> Result<(), String> {
+        let menu = menu.clone();
+
+        self.handle.update(move |tray| tray.menu = menu);
+
+        Ok(())
+    }
+
+    fn set_icon(&mut self, icon: &'static [u8]) -> Result<(), String> {
+        let icon = decode_icon(icon)?;
+
+        self.handle.update(move |tray| tray.icon = vec![icon]);
+
+        Ok(())
+    }
+
+    fn set_tooltip(&mut self, tooltip: &str) -> Result<(), String> {
+        let tooltip = String::from(tooltip);
+
+        self.handle.update(move |tray| tray.tooltip = tooltip);
+
+        Ok(())
+    }
+
+    fn remove(&mut self) {
+        self.handle.shutdown();
+    }
+}
+
+/// The tray expects ARGB32 pixels.
+fn decode_icon(buffer: &[u8]) -> Result<ksni::Icon, String> {
+    let image = super::decode_largest_icon(buffer)?;
+
+    let mut data = image.rgba_data().to_vec();
+    for pixel in data.chunks_exact_mut(4) {
+        pixel.rotate_right(1);
+    }
+
+    Ok(ksni::Icon {
+        width: image.width() as i32,
+        height: image.height() as i32,
+        data,
+    })
+}
+
+/// The menus use '&' for the access keys (e.g. "E&xit"), D-Bus menus use '_' instead.
+fn convert_label(name: &str) -> String {
+    let mut result = String::new();
+    let mut characters = name.chars().peekable();
+
+    while let Some(c) = characters.next() {
+        match c {
+            '&' if characters.peek() == Some(&'&') => {
+                characters.next();
+                result.push('&');
+            }
+            '&' => result.push('_'),
+            '_' => result.push_str("__"),
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+fn convert_menu(menu: &MenuBuilder<Events>) -> Vec<ksni::MenuItem<Tray>> {
+    menu.items()
+        .iter()
+        .map(|item| match item {
+            MenuItem::Separator => ksni::MenuItem::Separator,
+            MenuItem::Item { id, name, disabled } => {
+                let event = id.clone();
+
+                ksni::menu::StandardItem {
+                    label: convert_label(name),
+                    enabled: !*disabled,
+                    activate: Box::new(move |tray: &mut Tray| {
+                        tray.proxy.send_event(event.clone()).ok();
+                    }),
+                    ..Default::default()
+                }
+                .into()
+            }
+            MenuItem::Checkable {
+                id,
+                name,
+                is_checked,
+                disabled,
+            } => {
+                let event = id.clone();
+
+                ksni::menu::CheckmarkItem {
+                    label: convert_label(name),
+                    enabled: !*disabled,
+                    checked: *is_checked,
+                    activate: Box::new(move |tray: &mut Tray| {
+                        tray.proxy.send_event(event.clone()).ok();
+                    }),
+                    ..Default::default()
+                }
+                .into()
+            }
+            MenuItem::Submenu {
+                name,
+                children,
+                disabled,
+            } => ksni::menu::SubMenu {
+                label: convert_label(name),
+                enabled: !*disabled,
+                submenu: convert_menu(children),
+                ..Default::default()
+            }
+            .into(),
+        })
+        .collect()
+}
+
+pub fn send_notification(title: &str, text: &str) {
+    if !super::notification_allowed(title, text) {
+        return;
+    }
+
+    notify_rust::Notification::new()
+        .appname("Taskbar Twitch")
+        .summary(title)
+        .body(text)
+        .show()
+        .expect("Unable to create the notification.");
+}
+
+/// Asks a yes or no question with zenity (or kdialog), it waits for the answer.
+/// Without either of them, the answer is no.
+pub fn confirm(title: &str, text: &str) -> bool {
+    let zenity = std::process::Command::new("zenity")
+        .args(["--question", "--title", title, "--text", text])
+        .status();
+
+    let status = match zenity {
+        Ok(status) => Ok(status),
+        Err(_) => std::process::Command::new("kdialog")
+            .args(["--title", title, "--yesno", text])
+            .status(),
+    };
+
+    status.is_ok_and(|status| status.success())
+}
+
+/// Asks where to save a file with zenity (or kdialog), starting with `file_name`, it waits for the answer.
+pub fn choose_file_to_save(title: &str, file_name: &str) -> Option<PathBuf> {
+    file_dialog(
+        &[
+            "--file-selection",
+            "--save",
+            "--confirm-overwrite",
+            "--title",
+            title,
+            "--filename",
+            file_name,
+        ],
+        &["--title", title, "--getsavefilename", file_name],
+    )
+}
+
+/// Asks for a file to open with zenity (or kdialog), it waits for the answer.
+pub fn choose_file_to_open(title: &str) -> Option<PathBuf> {
+    file_dialog(
+        &["--file-selection", "--title", title],
+        &["--title", title, "--getopenfilename"],
+    )
+}
+
+/// The chosen file is printed, nothing is when the dialog is cancelled.
+fn file_dialog(zenity: &[&str], kdialog: &[&str]) -> Option<PathBuf> {
+    let output = std::process::Command::new("zenity")
+        .args(zenity)
+        .output()
+        .or_else(|_| std::process::Command::new("kdialog").args(kdialog).output())
+        .ok()?;
+
+    let path = String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string();
+
+    if output.status.success() && !path.is_empty() {
+        Some(PathBuf::from(path))
+    } else {
+        None
+    }
+}
+
+/// The notifications can't replace each other or skip the popup here.
+pub fn show_notification(notification: &Notification) {
+    send_notification(notification.title, notification.text);
+}
+
+/// Uses the clipboard tool of the session: `wl-copy` on Wayland, otherwise `xclip`.
+pub fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        pipe_to_command("wl-copy", &[], text)
+    } else {
+        pipe_to_command("xclip", &["-selection", "clipboard"], text)
+    }
+}
+
+/// Sends the text to a command that reads it from stdin.
+fn pipe_to_command(command: &str, args: &[&str], text: &str) -> std::io::Result<()> {
+    let mut child = std::process::Command::new(command)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+
+    if let Some(stdin) = child.stdin.as_mut() {
+        stdin.write_all(text.as_bytes())?;
+    }
+
+    let status = child.wait()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "{} failed ({})",
+            command, status
+        )))
+    }
+}
+
+/// We are always started with a console (if any), there's nothing to do.
+pub fn attach_console() {}
+
+/// The directory where we keep our own files ($XDG_DATA_HOME/taskbar-twitch).
+pub fn data_directory() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+        })?;
+
+    Some(data_home.join("taskbar-twitch"))
+}
+
+pub fn local_time() -> Option<LocalTime> {
+    super::local_time_from_date()
+}
+
+/// VLC is always in the PATH, when it's installed.
+pub fn vlc_location() -> Option<PathBuf> {
+    None
+}
+
+/// Same as VLC, it's in the PATH (the AppImage has to be put there by the user).
+pub fn chatterino_location() -> Option<PathBuf> {
+    None
+}
+
+pub fn os_version() -> String {
+    let os_release = std::fs::read_to_string("/etc/os-release").unwrap_or_default();
+
+    os_release
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|name| name.trim_matches('"').to_string())
+        .unwrap_or_else(|| String::from(std::env::consts::OS))
+}
+
+/// There's no standard way to know the theme of the panel, most of them are dark.
+pub fn taskbar_theme() -> Theme {
+    Theme::Dark
+}
+
+/// We don't receive the power events yet, after resuming from sleep the channels are updated on the next poll.
+pub fn listen_for_system_events(
+    _window: &Window,
+    _proxy: EventLoopProxy<Events>,
+) -> Result<(), String> {
+    Ok(())
+}
+
+/// We can't tell yet, the connection is never considered metered.
+pub fn is_metered_connection() -> bool {
+    false
+}
+
+/// Same as the power events, the connection changes are only noticed by the `connectivity` module.
+pub fn listen_for_network_changes(_on_change: impl FnMut() + Send + 'static) -> Result<(), String> {
+    Ok(())
+}
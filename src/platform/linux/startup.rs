@@ -0,0 +1,65 @@
+use std::io;
+use std::path::PathBuf;
+
+const DESKTOP_FILE: &str = "taskbar-twitch.desktop";
+
+/// Applications in the autostart directory ($XDG_CONFIG_HOME/autostart) are started at login.
+fn autostart_file() -> io::Result<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "Couldn't find the config directory.",
+            )
+        })?;
+
+    Ok(config_home.join("autostart").join(DESKTOP_FILE))
+}
+
+/// The desktop entry that will be started at login: the current executable,
+///  pointing at the absolute path of the active config file.
+fn desktop_entry(config_file: &str) -> io::Result<String> {
+    let exe = std::env::current_exe()?;
+    let config_file = std::fs::canonicalize(config_file)?;
+
+    Ok(format!(
+        "[Desktop Entry]\nType=Application\nName=Taskbar Twitch\nExec=\"{}\" --file \"{}\"\n",
+        exe.display(),
+        config_file.display()
+    ))
+}
+
+pub fn enable(config_file: &str) -> io::Result<()> {
+    let file = autostart_file()?;
+
+    if let Some(directory) = file.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+
+    std::fs::write(file, desktop_entry(config_file)?)
+}
+
+pub fn disable() -> io::Result<()> {
+    match std::fs::remove_file(autostart_file()?) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Checks if the application is set to start at login.
+/// If the executable or the config file were moved since the entry was created, it's updated.
+pub fn refresh(config_file: &str) -> io::Result<bool> {
+    let current = match std::fs::read_to_string(autostart_file()?) {
+        Ok(current) => current,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    if current != desktop_entry(config_file)? {
+        enable(config_file)?;
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,340 @@
+pub mod startup;
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuId, PredefinedMenuItem, Submenu};
+use winit::event_loop::EventLoopProxy;
+use winit::window::Window;
+
+use super::{LocalTime, MenuBuilder, MenuItem, Notification, Theme, TrayIconBase};
+use crate::lock::RecoverLock;
+use crate::Events;
+
+// The notifications always have the icon of the application here.
+pub const NOTIFICATION_AVATARS: bool = false;
+
+pub const START_AT_LOGIN_LABEL: &str = "Start at login";
+
+// The menu items only have an id, this is the event that each one of them sends.
+type MenuEvents = Arc<Mutex<HashMap<MenuId, Events>>>;
+
+/// A menu bar extra, clicking it opens the menu.
+pub struct TrayIcon {
+    // Dropping it removes the icon from the menu bar.
+    tray_icon: Option<tray_icon::TrayIcon>,
+    events: MenuEvents,
+}
+
+impl TrayIconBase for TrayIcon {
+    fn build(
+        proxy: EventLoopProxy<Events>,
+        icon: &'static [u8],
+        tooltip: &str,
+        menu: &MenuBuilder<Events>,
+    ) -> Result<Self, String> {
+        let events: MenuEvents = Arc::new(Mutex::new(HashMap::new()));
+
+        let menu = convert_menu(menu, &mut events.lock_or_recover())?;
+
+        let tray_icon = tray_icon::TrayIconBuilder::new()
+            .with_icon(decode_icon(icon)?)
+            .with_tooltip(tooltip)
+            .with_menu(Box::new(menu))
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let handler_events = events.clone();
+        let proxy = Mutex::new(proxy);
+        MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+            let event = handler_events.lock_or_recover().get(&event.id).cloned();
+
+            if let Some(event) = event {
+                proxy.lock_or_recover().send_event(event).ok();
+            }
+        }));
+
+        Ok(TrayIcon {
+            tray_icon: Some(tray_icon),
+            events,
+        })
+    }
+
+    fn set_menu(&mut self, menu: &MenuBuilder<Events>) -> Result<(), String> {
+        let mut events = self.events.lock_or_recover();
+
+        events.clear();
+        let menu = convert_menu(menu, &mut events)?;
+
+        if let Some(tray_icon) = &self.tray_icon {
+            tray_icon.set_menu(Some(Box::new(menu)));
+        }
+
+        Ok(())
+    }
+
+    fn set_icon(&mut self, icon: &'static [u8]) -> Result<(), String> {
+        match &self.tray_icon {
+            Some(tray_icon) => tray_icon
+                .set_icon(Some(decode_icon(icon)?))
+                .map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    fn set_tooltip(&mut self, tooltip: &str) -> Result<(), String> {
+        match &self.tray_icon {
+            Some(tray_icon) => tray_icon
+                .set_tooltip(Some(tooltip))
+                .map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    fn remove(&mut self) {
+        self.tray_icon = None;
+    }
+}
+
+fn decode_icon(buffer: &[u8]) -> Result<tray_icon::Icon, String> {
+    let image = super::decode_largest_icon(buffer)?;
+
+    tray_icon::Icon::from_rgba(image.rgba_data().to_vec(), image.width(), image.height())
+        .map_err(|e| e.to_string())
+}
+
+fn convert_menu(
+    menu: &MenuBuilder<Events>,
+    events: &mut HashMap<MenuId, Events>,
+) -> Result<Menu, String> {
+    let result = Menu::new();
+
+    append_items(menu, events, |item| result.append(item))?;
+
+    Ok(result)
+}
+
+/// Menus and submenus don't share a trait to append items, so the caller provides it.
+fn append_items<F>(
+    menu: &MenuBuilder<Events>,
+    events: &mut HashMap<MenuId, Events>,
+    append: F,
+) -> Result<(), String>
+where
+    F: Fn(&dyn tray_icon::menu::IsMenuItem) -> tray_icon::menu::Result<()>,
+{
+    for item in menu.items() {
+        let result = match item {
+            MenuItem::Separator => append(&PredefinedMenuItem::separator()),
+            MenuItem::Item { id, name, disabled } => {
+                let item = tray_icon::menu::MenuItem::new(name, !*disabled, None);
+                events.insert(item.id().clone(), id.clone());
+
+                append(&item)
+            }
+            MenuItem::Checkable {
+                id,
+                name,
+                is_checked,
+                disabled,
+            } => {
+                let item = CheckMenuItem::new(name, !*disabled, *is_checked, None);
+                events.insert(item.id().clone(), id.clone());
+
+                append(&item)
+            }
+            MenuItem::Submenu {
+                name,
+                children,
+                disabled,
+            } => {
+                let submenu = Submenu::new(name, !*disabled);
+                append_items(children, events, |item| submenu.append(item))?;
+
+                append(&submenu)
+            }
+        };
+
+        result.map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+pub fn send_notification(title: &str, text: &str) {
+    if !super::notification_allowed(title, text) {
+        return;
+    }
+
+    notify_rust::Notification::new()
+        .summary(title)
+        .body(text)
+        .show()
+        .expect("Unable to create the notification.");
+}
+
+/// Asks a yes or no question in a dialog (through AppleScript), it waits for the answer.
+pub fn confirm(title: &str, text: &str) -> bool {
+    let script = format!(
+        "display dialog \"{}\" with title \"{}\" buttons {{\"No\", \"Yes\"}} default button \"Yes\"",
+        escape_script(text),
+        escape_script(title)
+    );
+
+    // Answering "No" cancels the dialog, which is an error for osascript.
+    std::process::Command::new("osascript")
+        .args(["-e", script.as_str()])
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Asks where to save a file (through AppleScript), starting with `file_name`, it waits for the answer.
+pub fn choose_file_to_save(title: &str, file_name: &str) -> Option<PathBuf> {
+    file_dialog(&format!(
+        "POSIX path of (choose file name with prompt \"{}\" default name \"{}\")",
+        escape_script(title),
+        escape_script(file_name)
+    ))
+}
+
+/// Asks for a file to open (through AppleScript), it waits for the answer.
+pub fn choose_file_to_open(title: &str) -> Option<PathBuf> {
+    file_dialog(&format!(
+        "POSIX path of (choose file with prompt \"{}\")",
+        escape_script(title)
+    ))
+}
+
+/// The chosen file is printed, cancelling the dialog is an error for osascript.
+fn file_dialog(script: &str) -> Option<PathBuf> {
+    let output = std::process::Command::new("osascript")
+        .args(["-e", script])
+        .output()
+        .ok()?;
+
+    let path = String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string();
+
+    if output.status.success() && !path.is_empty() {
+        Some(PathBuf::from(path))
+    } else {
+        None
+    }
+}
+
+/// A string of AppleScript, between quotes.
+fn escape_script(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The notifications can't replace each other or skip the popup here.
+pub fn show_notification(notification: &Notification) {
+    send_notification(notification.title, notification.text);
+}
+
+/// The directory where we keep our own files (~/Library/Application Support/taskbar-twitch).
+pub fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    pipe_to_command("pbcopy", &[], text)
+}
+
+/// Sends the text to a command that reads it from stdin.
+fn pipe_to_command(command: &str, args: &[&str], text: &str) -> std::io::Result<()> {
+    let mut child = std::process::Command::new(command)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+
+    if let Some(stdin) = child.stdin.as_mut() {
+        stdin.write_all(text.as_bytes())?;
+    }
+
+    let status = child.wait()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "{} failed ({})",
+            command, status
+        )))
+    }
+}
+
+/// We are always started with a console (if any), there's nothing to do.
+pub fn attach_console() {}
+
+pub fn data_directory() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| {
+        PathBuf::from(home)
+            .join("Library/Application Support")
+            .join("taskbar-twitch")
+    })
+}
+
+pub fn local_time() -> Option<LocalTime> {
+    super::local_time_from_date()
+}
+
+/// Where VLC is usually installed, as it isn't in the PATH.
+pub fn vlc_location() -> Option<PathBuf> {
+    let path = PathBuf::from("/Applications/VLC.app/Contents/MacOS/VLC");
+
+    Some(path).filter(|path| path.is_file())
+}
+
+pub fn chatterino_location() -> Option<PathBuf> {
+    let path = PathBuf::from("/Applications/chatterino.app/Contents/MacOS/chatterino");
+
+    Some(path).filter(|path| path.is_file())
+}
+
+pub fn os_version() -> String {
+    let sw_vers = |argument: &str| {
+        std::process::Command::new("sw_vers")
+            .arg(argument)
+            .output()
+            .ok()
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|output| output.trim().to_string())
+            .filter(|output| !output.is_empty())
+    };
+
+    match (sw_vers("-productName"), sw_vers("-productVersion")) {
+        (Some(name), Some(version)) => format!("{} {}", name, version),
+        (Some(name), None) => name,
+        _ => String::from("Unknown macOS version"),
+    }
+}
+
+/// The menu bar follows the appearance of the system, the setting only exists in dark mode.
+pub fn taskbar_theme() -> Theme {
+    let output = std::process::Command::new("defaults")
+        .args(["read", "-g", "AppleInterfaceStyle"])
+        .output();
+
+    match output {
+        Ok(output) if String::from_utf8_lossy(&output.stdout).trim() == "Dark" => Theme::Dark,
+        _ => Theme::Light,
+    }
+}
+
+/// We don't receive the power events yet, after resuming from sleep the channels are updated on the next poll.
+pub fn listen_for_system_events(
+    _window: &Window,
+    _proxy: EventLoopProxy<Events>,
+) -> Result<(), String> {
+    Ok(())
+}
+
+/// We can't tell yet, the connection is never considered metered.
+pub fn is_metered_connection() -> bool {
+    false
+}
+
+/// Same as the power events, the connection changes are only noticed by the `connectivity` module.
+pub fn listen_for_network_changes(_on_change: impl FnMut() + Send + 'static) -> Result<(), String> {
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use std::io;
+use std::path::PathBuf;
+
+use super::super::escape_xml;
+
+const LAUNCH_AGENT_LABEL: &str = "com.hazyalex.taskbar-twitch";
+
+/// Launch agents in ~/Library/LaunchAgents are started at login.
+fn launch_agent_file() -> io::Result<PathBuf> {
+    let home = std::env::var_os("HOME").ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "Couldn't find the home directory.")
+    })?;
+
+    Ok(PathBuf::from(home)
+        .join("Library/LaunchAgents")
+        .join(format!("{}.plist", LAUNCH_AGENT_LABEL)))
+}
+
+/// The launch agent that will be started at login: the current executable,
+///  pointing at the absolute path of the active config file.
+fn launch_agent(config_file: &str) -> io::Result<String> {
+    let exe = std::env::current_exe()?;
+    let config_file = std::fs::canonicalize(config_file)?;
+
+    let mut result = String::new();
+
+    result.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    result.push_str("<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n");
+    result.push_str("<plist version=\"1.0\">\n<dict>\n");
+    result.push_str(
+        format!(
+            "  <key>Label</key>\n  <string>{}</string>\n",
+            LAUNCH_AGENT_LABEL
+        )
+        .as_str(),
+    );
+    result.push_str("  <key>ProgramArguments</key>\n  <array>\n");
+
+    for argument in [
+        exe.display().to_string(),
+        String::from("--file"),
+        config_file.display().to_string(),
+    ] {
+        result.push_str(format!("    <string>{}</string>\n", escape_xml(&argument)).as_str());
+    }
+
+    result.push_str("  </array>\n");
+    result.push_str("  <key>RunAtLoad</key>\n  <true/>\n");
+    result.push_str("</dict>\n</plist>\n");
+
+    Ok(result)
+}
+
+pub fn enable(config_file: &str) -> io::Result<()> {
+    let file = launch_agent_file()?;
+
+    if let Some(directory) = file.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+
+    std::fs::write(file, launch_agent(config_file)?)
+}
+
+pub fn disable() -> io::Result<()> {
+    match std::fs::remove_file(launch_agent_file()?) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Checks if the application is set to start at login.
+/// If the executable or the config file were moved since the launch agent was created, it's updated.
+pub fn refresh(config_file: &str) -> io::Result<bool> {
+    let current = match std::fs::read_to_string(launch_agent_file()?) {
+        Ok(current) => current,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    if current != launch_agent(config_file)? {
+        enable(config_file)?;
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,102 @@
+/// The menu model shared by every platform, each tray icon implementation converts it to its own.
+/// It mirrors the API of the `trayicon` crate, which is what we used before supporting other platforms.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MenuItem<T>
+where
+    T: PartialEq + Clone + 'static,
+{
+    Separator,
+    Item {
+        id: T,
+        name: String,
+        disabled: bool,
+    },
+    Checkable {
+        id: T,
+        name: String,
+        is_checked: bool,
+        disabled: bool,
+    },
+    Submenu {
+        name: String,
+        children: MenuBuilder<T>,
+        disabled: bool,
+    },
+}
+
+/// The labels use '&' for the access keys (e.g. "E&xit"), text that comes from elsewhere (e.g. a title)
+///  has to be escaped so that it's shown as is.
+pub fn escape_label(text: &str) -> String {
+    text.replace('&', "&&")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MenuBuilder<T>
+where
+    T: PartialEq + Clone + 'static,
+{
+    items: Vec<MenuItem<T>>,
+}
+
+impl<T> Default for MenuBuilder<T>
+where
+    T: PartialEq + Clone + 'static,
+{
+    fn default() -> Self {
+        MenuBuilder::new()
+    }
+}
+
+impl<T> MenuBuilder<T>
+where
+    T: PartialEq + Clone + 'static,
+{
+    pub fn new() -> MenuBuilder<T> {
+        MenuBuilder { items: Vec::new() }
+    }
+
+    pub fn items(&self) -> &[MenuItem<T>] {
+        &self.items
+    }
+
+    pub fn when<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        f(self)
+    }
+
+    pub fn with(mut self, item: MenuItem<T>) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    pub fn separator(self) -> Self {
+        self.with(MenuItem::Separator)
+    }
+
+    pub fn item(self, name: &str, id: T) -> Self {
+        self.with(MenuItem::Item {
+            id,
+            name: String::from(name),
+            disabled: false,
+        })
+    }
+
+    pub fn checkable(self, name: &str, is_checked: bool, id: T) -> Self {
+        self.with(MenuItem::Checkable {
+            id,
+            name: String::from(name),
+            is_checked,
+            disabled: false,
+        })
+    }
+
+    pub fn submenu(self, name: &str, menu: MenuBuilder<T>) -> Self {
+        self.with(MenuItem::Submenu {
+            name: String::from(name),
+            children: menu,
+            disabled: false,
+        })
+    }
+}
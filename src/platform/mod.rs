@@ -0,0 +1,154 @@
+//! Everything that depends on the operating system: the tray icon, notifications,
+//!  starting at login and where we keep our files.
+//! Every platform module exports the same items, so the rest of the application doesn't need to know which one is used.
+
+mod menu;
+pub use menu::{escape_label, MenuBuilder, MenuItem};
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+pub use self::windows::*;
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "macos")]
+pub use self::macos::*;
+
+// Other unix systems use the freedesktop (StatusNotifierItem + D-Bus notifications) implementation.
+#[cfg(not(any(windows, target_os = "macos")))]
+mod linux;
+#[cfg(not(any(windows, target_os = "macos")))]
+pub use self::linux::*;
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use winit::event_loop::EventLoopProxy;
+
+use crate::diagnostics;
+use crate::Events;
+
+// "notifications_enabled", the notifications are sent from everywhere (and without the state).
+static NOTIFICATIONS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+pub fn set_notifications_enabled(enabled: bool) {
+    NOTIFICATIONS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether a notification can be shown, the ones that can't are still recorded for the diagnostics.
+fn notification_allowed(title: &str, text: &str) -> bool {
+    if NOTIFICATIONS_ENABLED.load(Ordering::Relaxed) {
+        return true;
+    }
+
+    diagnostics::record_disabled_notification(title, text);
+
+    false
+}
+
+/// A notification about the channels, unlike the ones from `send_notification` (e.g. errors),
+///  they can replace each other or skip the popup.
+pub struct Notification<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+    // A newer notification with the same tag (e.g. the channel name) replaces the previous one.
+    pub tag: Option<&'a str>,
+    // Only shown in the notification center, without a popup.
+    pub quiet: bool,
+    // Stays on screen for longer, only on Windows (the other platforms decide on their own).
+    pub long: bool,
+    // Instead of the icon of the application (e.g. the avatar of the channel), see `NOTIFICATION_AVATARS`.
+    pub icon: Option<&'a Path>,
+    // Opened (in the browser) when the notification is clicked, only on Windows.
+    pub link: Option<&'a str>,
+}
+
+/// The theme of the taskbar (or panel), the tray icon has to be visible on top of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+/// The local date and time (to the minute), we only need it to do something at a time of the day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl LocalTime {
+    pub fn date(&self) -> (u16, u8, u8) {
+        (self.year, self.month, self.day)
+    }
+}
+
+/// Implemented by the tray icon of every platform.
+pub trait TrayIconBase: Sized {
+    /// Creates the tray icon, clicking it (or one of its menu items) sends the events to the proxy.
+    fn build(
+        proxy: EventLoopProxy<Events>,
+        icon: &'static [u8],
+        tooltip: &str,
+        menu: &MenuBuilder<Events>,
+    ) -> Result<Self, String>;
+
+    fn set_menu(&mut self, menu: &MenuBuilder<Events>) -> Result<(), String>;
+
+    /// Sets the icon from the contents of an '.ico' file.
+    fn set_icon(&mut self, icon: &'static [u8]) -> Result<(), String>;
+
+    fn set_tooltip(&mut self, tooltip: &str) -> Result<(), String>;
+
+    /// Removes the icon right away, if the session is ending we might not get to exit normally.
+    /// Nothing else can be done with it afterwards.
+    fn remove(&mut self);
+}
+
+/// Escapes the text (or attribute value) of an XML element.
+#[cfg(any(windows, target_os = "macos"))]
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// There's no API for it without another dependency, so we ask `date` (e.g. "2021-08-14 21:03").
+#[cfg(not(windows))]
+fn local_time_from_date() -> Option<LocalTime> {
+    let output = std::process::Command::new("date")
+        .arg("+%Y %m %d %H %M")
+        .output()
+        .ok()?;
+
+    let output = String::from_utf8(output.stdout).ok()?;
+    let mut fields = output.split_whitespace();
+
+    Some(LocalTime {
+        year: fields.next()?.parse().ok()?,
+        month: fields.next()?.parse().ok()?,
+        day: fields.next()?.parse().ok()?,
+        hour: fields.next()?.parse().ok()?,
+        minute: fields.next()?.parse().ok()?,
+    })
+}
+
+/// Only Windows can use an '.ico' file directly, the other platforms use its largest image.
+#[cfg(not(windows))]
+fn decode_largest_icon(buffer: &[u8]) -> Result<ico::IconImage, String> {
+    let icon_dir = ico::IconDir::read(std::io::Cursor::new(buffer)).map_err(|e| e.to_string())?;
+
+    let entry = icon_dir
+        .entries()
+        .iter()
+        .max_by_key(|entry| entry.width())
+        .ok_or_else(|| String::from("The icon doesn't have any images."))?;
+
+    entry.decode().map_err(|e| e.to_string())
+}
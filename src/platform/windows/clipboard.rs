@@ -0,0 +1,56 @@
+use std::io;
+
+use windows::Win32::Foundation::{HANDLE, HWND};
+use windows::Win32::System::DataExchange::{
+    CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData,
+};
+use windows::Win32::System::Memory::{
+    GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock, GMEM_MOVEABLE,
+};
+use windows::Win32::System::SystemServices::CF_UNICODETEXT;
+
+/// Replaces the contents of the clipboard with the text.
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let text: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+
+    if !unsafe { OpenClipboard(HWND(0)) }.as_bool() {
+        return Err(io::Error::last_os_error());
+    }
+
+    let result = unsafe { set_text(&text) };
+
+    unsafe { CloseClipboard() };
+
+    result
+}
+
+/// The clipboard has to be open, it takes ownership of the memory once the data is set.
+unsafe fn set_text(text: &[u16]) -> io::Result<()> {
+    if !EmptyClipboard().as_bool() {
+        return Err(io::Error::last_os_error());
+    }
+
+    let memory = GlobalAlloc(GMEM_MOVEABLE, std::mem::size_of_val(text));
+
+    if memory == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let destination = GlobalLock(memory) as *mut u16;
+
+    if destination.is_null() {
+        GlobalFree(memory);
+        return Err(io::Error::last_os_error());
+    }
+
+    std::ptr::copy_nonoverlapping(text.as_ptr(), destination, text.len());
+    GlobalUnlock(memory);
+
+    if SetClipboardData(CF_UNICODETEXT.0, HANDLE(memory)).0 == 0 {
+        let error = io::Error::last_os_error();
+        GlobalFree(memory);
+        return Err(error);
+    }
+
+    Ok(())
+}
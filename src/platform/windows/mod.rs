@@ -0,0 +1,530 @@
+mod clipboard;
+mod network;
+mod registry;
+pub mod startup;
+mod system_events;
+mod toast;
+
+pub use clipboard::copy_to_clipboard;
+pub use network::{is_metered_connection, listen_for_network_changes};
+pub use system_events::listen_for_system_events;
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use windows::Win32::Foundation::SYSTEMTIME;
+use windows::Win32::Foundation::{HWND, MAX_PATH, PWSTR};
+use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+use windows::Win32::System::SystemInformation::GetLocalTime;
+use windows::Win32::UI::Controls::Dialogs::{
+    GetOpenFileNameW, GetSaveFileNameW, OFN_FILEMUSTEXIST, OFN_NOCHANGEDIR, OFN_OVERWRITEPROMPT,
+    OPENFILENAMEW,
+};
+use windows::Win32::UI::HiDpi::{GetDpiForSystem, GetSystemMetricsForDpi};
+use windows::Win32::UI::WindowsAndMessaging::{
+    MessageBoxW, IDYES, MB_ICONQUESTION, MB_SETFOREGROUND, MB_YESNO, SM_CXSMICON,
+};
+
+use winit::event_loop::EventLoopProxy;
+
+use super::{LocalTime, MenuBuilder, MenuItem, Notification, Theme, TrayIconBase};
+use crate::Events;
+
+// The toasts can have another image instead of our icon, the avatars of the channels are downloaded for them.
+pub const NOTIFICATION_AVATARS: bool = true;
+
+pub const START_AT_LOGIN_LABEL: &str = "Start with Windows";
+
+pub struct TrayIcon {
+    // Dropping it removes the icon from the tray.
+    tray_icon: Option<trayicon::TrayIcon<Events>>,
+}
+
+impl TrayIconBase for TrayIcon {
+    fn build(
+        proxy: EventLoopProxy<Events>,
+        icon: &'static [u8],
+        tooltip: &str,
+        menu: &MenuBuilder<Events>,
+    ) -> Result<Self, String> {
+        let tray_icon = trayicon::TrayIconBuilder::new()
+            .sender_winit(proxy)
+            .icon(load_icon(icon, IconUsage::Tray)?)
+            .tooltip(tooltip)
+            .on_click(Events::ClickTrayIcon)
+            .on_double_click(Events::DoubleClickTrayIcon)
+            .menu(convert_menu(menu))
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(TrayIcon {
+            tray_icon: Some(tray_icon),
+        })
+    }
+
+    fn set_menu(&mut self, menu: &MenuBuilder<Events>) -> Result<(), String> {
+        match &mut self.tray_icon {
+            Some(tray_icon) => tray_icon
+                .set_menu(&convert_menu(menu))
+                .map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    fn set_icon(&mut self, icon: &'static [u8]) -> Result<(), String> {
+        let icon = load_icon(icon, IconUsage::Tray)?;
+
+        match &mut self.tray_icon {
+            Some(tray_icon) => tray_icon.set_icon(&icon).map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    fn set_tooltip(&mut self, tooltip: &str) -> Result<(), String> {
+        match &mut self.tray_icon {
+            Some(tray_icon) => tray_icon.set_tooltip(tooltip).map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    fn remove(&mut self) {
+        self.tray_icon = None;
+    }
+}
+
+/// Where an icon is shown, they don't have the same size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IconUsage {
+    Tray,
+    Notification,
+}
+
+/// The size (in pixels) of an icon, taking the display scaling into account.
+fn icon_size(usage: IconUsage) -> u32 {
+    let dpi = unsafe { GetDpiForSystem() };
+
+    match usage {
+        IconUsage::Tray => unsafe { GetSystemMetricsForDpi(SM_CXSMICON.0 as i32, dpi) as u32 },
+        // The application logo of a notification is 48x48 at 100% scaling.
+        IconUsage::Notification => 48 * dpi / 96,
+    }
+}
+
+/// Uses the image of an '.ico' file with the right size, otherwise Windows scales up a small one (which is blurry).
+fn load_icon(buffer: &'static [u8], usage: IconUsage) -> Result<trayicon::Icon, String> {
+    let size = icon_size(usage);
+
+    trayicon::Icon::from_buffer(buffer, Some(size), Some(size)).map_err(|e| e.to_string())
+}
+
+/// Picks the image of an '.ico' file that fits best in `size` pixels: the smallest one that is at least that big,
+///  or the largest one if all of them are smaller. Returns an '.ico' file with only that image.
+fn select_icon_image(buffer: &[u8], size: u32) -> Option<Vec<u8>> {
+    const HEADER_SIZE: usize = 6;
+    const ENTRY_SIZE: usize = 16;
+
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let bytes = buffer.get(offset..offset + 2)?;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = buffer.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    let count = read_u16(4)? as usize;
+
+    if buffer.len() < HEADER_SIZE + count * ENTRY_SIZE {
+        return None;
+    }
+
+    // A width of 0 means 256 pixels.
+    let entries = (0..count).map(|index| HEADER_SIZE + index * ENTRY_SIZE);
+    let width = |entry: &usize| match buffer[*entry] {
+        0 => 256,
+        width => width as u32,
+    };
+
+    let entry = entries
+        .clone()
+        .filter(|entry| width(entry) >= size)
+        .min_by_key(width)
+        .or_else(|| entries.max_by_key(width))?;
+
+    let image_size = read_u32(entry + 8)? as usize;
+    let image_offset = read_u32(entry + 12)? as usize;
+    let image = buffer.get(image_offset..image_offset + image_size)?;
+
+    let mut result = Vec::with_capacity(HEADER_SIZE + ENTRY_SIZE + image_size);
+
+    // The header (with a single image), the entry (pointing right after it) and the image.
+    result.extend_from_slice(&buffer[..4]);
+    result.extend_from_slice(&1u16.to_le_bytes());
+    result.extend_from_slice(&buffer[entry..entry + 12]);
+    result.extend_from_slice(&((HEADER_SIZE + ENTRY_SIZE) as u32).to_le_bytes());
+    result.extend_from_slice(image);
+
+    Some(result)
+}
+
+/// The notifications only accept the path of an icon, we save the image with the right size in our directory.
+/// Falls back to the whole '.ico' file if that's not possible.
+fn notification_icon_path() -> &'static str {
+    static PATH: OnceLock<String> = OnceLock::new();
+
+    PATH.get_or_init(|| {
+        let icon_path = std::fs::canonicalize("./resources/twitch.ico")
+            .map(remove_extended_path_prefix)
+            .unwrap_or_default();
+
+        let image = std::fs::read(&icon_path)
+            .ok()
+            .and_then(|buffer| select_icon_image(&buffer, icon_size(IconUsage::Notification)));
+
+        let directory = data_directory();
+
+        match (image, directory) {
+            (Some(image), Some(directory)) => {
+                let path = directory.join("notification.ico");
+
+                let result =
+                    std::fs::create_dir_all(&directory).and_then(|_| std::fs::write(&path, image));
+
+                match result {
+                    Ok(_) => path.display().to_string(),
+                    Err(_) => icon_path,
+                }
+            }
+            _ => icon_path,
+        }
+    })
+}
+
+fn convert_menu(menu: &MenuBuilder<Events>) -> trayicon::MenuBuilder<Events> {
+    let mut result = trayicon::MenuBuilder::new();
+
+    for item in menu.items() {
+        let item = match item {
+            MenuItem::Separator => trayicon::MenuItem::Separator,
+            MenuItem::Item { id, name, disabled } => trayicon::MenuItem::Item {
+                id: id.clone(),
+                name: name.clone(),
+                disabled: *disabled,
+                icon: None,
+            },
+            MenuItem::Checkable {
+                id,
+                name,
+                is_checked,
+                disabled,
+            } => trayicon::MenuItem::Checkable {
+                id: id.clone(),
+                name: name.clone(),
+                is_checked: *is_checked,
+                disabled: *disabled,
+                icon: None,
+            },
+            MenuItem::Submenu {
+                name,
+                children,
+                disabled,
+            } => trayicon::MenuItem::Submenu {
+                id: None,
+                name: name.clone(),
+                children: convert_menu(children),
+                disabled: *disabled,
+                icon: None,
+            },
+        };
+
+        result = result.with(item);
+    }
+
+    result
+}
+
+pub fn send_notification(title: &str, text: &str) {
+    if !super::notification_allowed(title, text) {
+        return;
+    }
+
+    toast::Toast {
+        title,
+        text,
+        icon_path: notification_icon_path(),
+        tag: None,
+        link: None,
+        suppress_popup: false,
+        // The errors are worth reading.
+        long: true,
+    }
+    .show()
+    .expect("Unable to create the notification.");
+}
+
+pub fn show_notification(notification: &Notification) {
+    if !super::notification_allowed(notification.title, notification.text) {
+        return;
+    }
+
+    // The avatar might have been removed since the notification was queued.
+    let icon_path = notification
+        .icon
+        .filter(|icon| icon.is_file())
+        .and_then(|icon| icon.to_str())
+        .unwrap_or_else(|| notification_icon_path());
+
+    toast::Toast {
+        title: notification.title,
+        text: notification.text,
+        icon_path,
+        tag: notification.tag,
+        link: notification.link,
+        suppress_popup: notification.quiet,
+        long: notification.long,
+    }
+    .show()
+    .expect("Unable to create the notification.");
+}
+
+/// Asks a yes or no question in a message box, it waits for the answer.
+pub fn confirm(title: &str, text: &str) -> bool {
+    let result = unsafe {
+        MessageBoxW(
+            HWND::default(),
+            text,
+            title,
+            MB_YESNO | MB_ICONQUESTION | MB_SETFOREGROUND,
+        )
+    };
+
+    result == IDYES
+}
+
+/// Asks where to save a JSON file, starting with `file_name`, it waits for the answer.
+pub fn choose_file_to_save(title: &str, file_name: &str) -> Option<PathBuf> {
+    file_dialog(title, file_name, true)
+}
+
+/// Asks for a JSON file to open, it waits for the answer.
+pub fn choose_file_to_open(title: &str) -> Option<PathBuf> {
+    file_dialog(title, "", false)
+}
+
+fn file_dialog(title: &str, file_name: &str, save: bool) -> Option<PathBuf> {
+    // The dialog writes the chosen file over the suggested name.
+    let mut file: Vec<u16> = file_name.encode_utf16().collect();
+    file.resize(MAX_PATH as usize, 0);
+
+    // Pairs of a description and a pattern, the list ends with an empty one.
+    let mut filter: Vec<u16> = "JSON files\0*.json\0All files\0*.*\0\0"
+        .encode_utf16()
+        .collect();
+
+    let mut title: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut extension: Vec<u16> = "json".encode_utf16().chain(std::iter::once(0)).collect();
+
+    let mut dialog = OPENFILENAMEW {
+        lStructSize: std::mem::size_of::<OPENFILENAMEW>() as u32,
+        lpstrFilter: PWSTR(filter.as_mut_ptr()),
+        lpstrFile: PWSTR(file.as_mut_ptr()),
+        nMaxFile: file.len() as u32,
+        lpstrTitle: PWSTR(title.as_mut_ptr()),
+        lpstrDefExt: PWSTR(extension.as_mut_ptr()),
+        Flags: OFN_NOCHANGEDIR
+            | if save {
+                OFN_OVERWRITEPROMPT
+            } else {
+                OFN_FILEMUSTEXIST
+            },
+        ..Default::default()
+    };
+
+    // False when it's cancelled.
+    let chosen = unsafe {
+        if save {
+            GetSaveFileNameW(&mut dialog)
+        } else {
+            GetOpenFileNameW(&mut dialog)
+        }
+    };
+
+    if !chosen.as_bool() {
+        return None;
+    }
+
+    // Remove the null terminator.
+    let length = file.iter().position(|c| *c == 0).unwrap_or(file.len());
+
+    Some(PathBuf::from(String::from_utf16_lossy(&file[..length])))
+}
+
+/// The directory where we keep our own files (%LOCALAPPDATA%\taskbar-twitch).
+/// As we don't have a console of our own (see `windows_subsystem`), we use the one that started us (if any),
+///  so that the output of the headless modes can be read.
+pub fn attach_console() {
+    unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+pub fn data_directory() -> Option<PathBuf> {
+    std::env::var_os("LOCALAPPDATA")
+        .map(|directory| PathBuf::from(directory).join("taskbar-twitch"))
+}
+
+/// Where VLC was installed, from its installer (it isn't added to the PATH).
+pub fn vlc_location() -> Option<PathBuf> {
+    const KEY: &str = r"SOFTWARE\VideoLAN\VLC";
+
+    let from_registry = registry::read_string(registry::HKEY_LOCAL_MACHINE, KEY, "InstallDir")
+        .ok()
+        .flatten()
+        .map(|directory| PathBuf::from(directory).join("vlc.exe"));
+
+    let from_program_files = ["ProgramFiles", "ProgramFiles(x86)"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(|directory| PathBuf::from(directory).join(r"VideoLAN\VLC\vlc.exe"));
+
+    from_registry
+        .into_iter()
+        .chain(from_program_files)
+        .find(|path| path.is_file())
+}
+
+/// Where the installer of Chatterino puts it.
+pub fn chatterino_location() -> Option<PathBuf> {
+    ["ProgramFiles", "LOCALAPPDATA"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .flat_map(|directory| {
+            ["Chatterino", "Chatterino2"]
+                .iter()
+                .map(move |name| PathBuf::from(&directory).join(name).join("chatterino.exe"))
+        })
+        .find(|path| path.is_file())
+}
+
+pub fn os_version() -> String {
+    const KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion";
+
+    let read = |name: &str| {
+        registry::read_string(registry::HKEY_LOCAL_MACHINE, KEY, name)
+            .ok()
+            .flatten()
+    };
+
+    let mut result = read("ProductName").unwrap_or_else(|| String::from("Unknown Windows version"));
+
+    if let Some(version) = read("DisplayVersion") {
+        result.push_str(format!(" {}", version).as_str());
+    }
+
+    if let Some(build) = read("CurrentBuild") {
+        result.push_str(format!(" (build {})", build).as_str());
+    }
+
+    result
+}
+
+pub fn local_time() -> Option<LocalTime> {
+    let mut time = SYSTEMTIME::default();
+
+    unsafe { GetLocalTime(&mut time) };
+
+    Some(LocalTime {
+        year: time.wYear,
+        month: time.wMonth as u8,
+        day: time.wDay as u8,
+        hour: time.wHour as u8,
+        minute: time.wMinute as u8,
+    })
+}
+
+/// The taskbar has its own theme, which can be different from the one used by the applications.
+pub fn taskbar_theme() -> Theme {
+    const KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+
+    // Older versions of Windows don't have the value, their taskbar is always dark.
+    match registry::read_dword(registry::HKEY_CURRENT_USER, KEY, "SystemUsesLightTheme") {
+        Ok(Some(value)) if value != 0 => Theme::Light,
+        _ => Theme::Dark,
+    }
+}
+
+fn remove_extended_path_prefix(path: PathBuf) -> String {
+    const PREFIX: &str = r#"\\?\"#;
+
+    let p = path.display().to_string();
+
+    match p.strip_prefix(PREFIX) {
+        Some(p) => p.to_string(),
+        None => p,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An '.ico' file with an image of each width, the image is 4 bytes of its width.
+    fn ico(widths: &[u8]) -> Vec<u8> {
+        let count = widths.len() as u16;
+        let images_offset = 6 + 16 * widths.len() as u32;
+
+        let mut buffer = vec![0, 0, 1, 0];
+        buffer.extend_from_slice(&count.to_le_bytes());
+
+        for (index, width) in widths.iter().enumerate() {
+            buffer.extend_from_slice(&[*width, *width, 0, 0, 1, 0, 32, 0]);
+            buffer.extend_from_slice(&4u32.to_le_bytes());
+            buffer.extend_from_slice(&(images_offset + 4 * index as u32).to_le_bytes());
+        }
+
+        for width in widths {
+            buffer.extend_from_slice(&[*width; 4]);
+        }
+
+        buffer
+    }
+
+    #[test]
+    fn icon_image_sizes() {
+        // A width of 0 is 256 pixels.
+        let buffer = ico(&[32, 16, 0, 48]);
+
+        // The size that we need, and the width of the image that should be picked.
+        let cases = [
+            (16, 16),
+            (20, 32),
+            (32, 32),
+            (40, 48),
+            (48, 48),
+            (64, 0),
+            (256, 0),
+            (512, 0),
+        ];
+
+        for (size, expected) in cases {
+            let result = select_icon_image(&buffer, size).expect("a valid icon");
+
+            // A single image, right after its entry.
+            assert_eq!(result[4..6], 1u16.to_le_bytes(), "{}", size);
+            assert_eq!(result[6], expected, "{}", size);
+            assert_eq!(result[18..22], 22u32.to_le_bytes(), "{}", size);
+            assert_eq!(result[22..], [expected; 4], "{}", size);
+        }
+
+        // All of them are smaller, the largest one is scaled up.
+        assert_eq!(select_icon_image(&ico(&[16, 32]), 48).unwrap()[6], 32);
+    }
+
+    #[test]
+    fn invalid_icons() {
+        let buffer = ico(&[16, 32]);
+
+        assert_eq!(select_icon_image(&[], 16), None);
+        assert_eq!(select_icon_image(&buffer[..20], 16), None);
+        assert_eq!(select_icon_image(&buffer[..buffer.len() - 1], 32), None);
+        assert_eq!(select_icon_image(&ico(&[]), 16), None);
+    }
+}
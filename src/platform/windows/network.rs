@@ -0,0 +1,36 @@
+use windows::Networking::Connectivity::{
+    NetworkCostType, NetworkInformation, NetworkStatusChangedEventHandler,
+};
+
+/// Whether Windows considers the connection to the internet metered (e.g. a phone, or a limited data plan).
+/// Without a connection (or if we can't tell), it's not.
+pub fn is_metered_connection() -> bool {
+    let cost = match NetworkInformation::GetInternetConnectionProfile()
+        .and_then(|profile| profile.GetConnectionCost())
+    {
+        Ok(cost) => cost,
+        Err(_) => return false,
+    };
+
+    let cost_type = cost.NetworkCostType().unwrap_or(NetworkCostType::Unknown);
+
+    cost_type == NetworkCostType::Fixed
+        || cost_type == NetworkCostType::Variable
+        || cost.Roaming().unwrap_or_default()
+        || cost.OverDataLimit().unwrap_or_default()
+}
+
+/// Calls `on_change` whenever the connection changes (e.g. switching to another Wi-Fi network), from another thread.
+/// It's kept until we exit.
+pub fn listen_for_network_changes(
+    mut on_change: impl FnMut() + Send + 'static,
+) -> Result<(), String> {
+    let handler = NetworkStatusChangedEventHandler::new(move |_| {
+        on_change();
+        Ok(())
+    });
+
+    NetworkInformation::NetworkStatusChanged(handler)
+        .map(|_| ())
+        .map_err(|e| e.message())
+}
@@ -0,0 +1,132 @@
+use std::io;
+
+use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS, LSTATUS};
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegDeleteValueW, RegGetValueW, RegOpenKeyExW, RegSetValueExW, HKEY, KEY_SET_VALUE,
+    REG_SZ, RRF_RT_REG_DWORD, RRF_RT_REG_SZ,
+};
+
+pub use windows::Win32::System::Registry::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+
+fn to_result(status: LSTATUS) -> io::Result<()> {
+    if status.0 == ERROR_SUCCESS.0 as i32 {
+        Ok(())
+    } else {
+        Err(io::Error::from_raw_os_error(status.0))
+    }
+}
+
+fn is_not_found(status: LSTATUS) -> bool {
+    status.0 == ERROR_FILE_NOT_FOUND.0 as i32
+}
+
+/// Reads a string value, returns `None` if it doesn't exist.
+pub fn read_string(root: HKEY, key: &str, name: &str) -> io::Result<Option<String>> {
+    let mut size: u32 = 0;
+
+    // The first call only gets the size of the value (in bytes).
+    let status = unsafe {
+        RegGetValueW(
+            root,
+            key,
+            name,
+            RRF_RT_REG_SZ,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut size,
+        )
+    };
+
+    if is_not_found(status) {
+        return Ok(None);
+    }
+    to_result(status)?;
+
+    let mut buffer: Vec<u16> = vec![0; (size as usize).div_ceil(2)];
+
+    to_result(unsafe {
+        RegGetValueW(
+            root,
+            key,
+            name,
+            RRF_RT_REG_SZ,
+            std::ptr::null_mut(),
+            buffer.as_mut_ptr() as *mut std::ffi::c_void,
+            &mut size,
+        )
+    })?;
+
+    // Remove the null terminator.
+    let length = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+
+    Ok(Some(String::from_utf16_lossy(&buffer[..length])))
+}
+
+/// Reads a DWORD value, returns `None` if it doesn't exist.
+pub fn read_dword(root: HKEY, key: &str, name: &str) -> io::Result<Option<u32>> {
+    let mut value: u32 = 0;
+    let mut size = std::mem::size_of::<u32>() as u32;
+
+    let status = unsafe {
+        RegGetValueW(
+            root,
+            key,
+            name,
+            RRF_RT_REG_DWORD,
+            std::ptr::null_mut(),
+            &mut value as *mut u32 as *mut std::ffi::c_void,
+            &mut size,
+        )
+    };
+
+    if is_not_found(status) {
+        return Ok(None);
+    }
+    to_result(status)?;
+
+    Ok(Some(value))
+}
+
+fn open_for_writing(root: HKEY, key: &str) -> io::Result<HKEY> {
+    let mut result = HKEY::default();
+
+    to_result(unsafe { RegOpenKeyExW(root, key, 0, KEY_SET_VALUE, &mut result) })?;
+
+    Ok(result)
+}
+
+pub fn write_string(root: HKEY, key: &str, name: &str, value: &str) -> io::Result<()> {
+    let data: Vec<u16> = value.encode_utf16().chain(std::iter::once(0)).collect();
+
+    let key = open_for_writing(root, key)?;
+
+    let result = to_result(unsafe {
+        RegSetValueExW(
+            key,
+            name,
+            0,
+            REG_SZ,
+            data.as_ptr() as *const u8,
+            (data.len() * std::mem::size_of::<u16>()) as u32,
+        )
+    });
+
+    unsafe { RegCloseKey(key) };
+
+    result
+}
+
+/// Deletes a value, it's not an error if it doesn't exist.
+pub fn delete_value(root: HKEY, key: &str, name: &str) -> io::Result<()> {
+    let key = open_for_writing(root, key)?;
+
+    let status = unsafe { RegDeleteValueW(key, name) };
+
+    unsafe { RegCloseKey(key) };
+
+    if is_not_found(status) {
+        return Ok(());
+    }
+
+    to_result(status)
+}
@@ -0,0 +1,43 @@
+use std::io;
+
+use super::registry;
+use super::remove_extended_path_prefix;
+
+const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+const VALUE_NAME: &str = "Taskbar Twitch";
+
+/// The command that Windows will run at login: the current executable,
+///  pointing at the absolute path of the active config file.
+fn command(config_file: &str) -> io::Result<String> {
+    let exe = std::env::current_exe().map(remove_extended_path_prefix)?;
+    let config_file = std::fs::canonicalize(config_file).map(remove_extended_path_prefix)?;
+
+    Ok(format!("\"{}\" --file \"{}\"", exe, config_file))
+}
+
+pub fn enable(config_file: &str) -> io::Result<()> {
+    let command = command(config_file)?;
+
+    registry::write_string(registry::HKEY_CURRENT_USER, RUN_KEY, VALUE_NAME, &command)
+}
+
+pub fn disable() -> io::Result<()> {
+    registry::delete_value(registry::HKEY_CURRENT_USER, RUN_KEY, VALUE_NAME)
+}
+
+/// Checks if the application is set to start with Windows.
+/// If the executable or the config file were moved since the entry was created, it's updated.
+pub fn refresh(config_file: &str) -> io::Result<bool> {
+    let current = registry::read_string(registry::HKEY_CURRENT_USER, RUN_KEY, VALUE_NAME)?;
+
+    let current = match current {
+        Some(current) => current,
+        None => return Ok(false),
+    };
+
+    if current != command(config_file)? {
+        enable(config_file)?;
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,86 @@
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::RemoteDesktop::{
+    WTSRegisterSessionNotification, WTSUnRegisterSessionNotification, NOTIFY_FOR_THIS_SESSION,
+};
+use windows::Win32::UI::Shell::{DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass};
+use windows::Win32::UI::WindowsAndMessaging::{
+    PBT_APMRESUMEAUTOMATIC, WM_NCDESTROY, WM_POWERBROADCAST, WM_QUERYENDSESSION,
+    WM_WTSSESSION_CHANGE, WTS_SESSION_LOCK, WTS_SESSION_UNLOCK,
+};
+use winit::event_loop::EventLoopProxy;
+use winit::platform::windows::WindowExtWindows;
+use winit::window::Window;
+
+use crate::Events;
+
+const SUBCLASS_ID: usize = 1;
+
+/// Winit doesn't handle some of the messages that are sent to our (hidden) window, e.g. the power events.
+/// We subclass it to receive them, and send the ones we are interested in to the event loop.
+/// Everything is cleaned up when the window is destroyed.
+pub fn listen_for_system_events(
+    window: &Window,
+    proxy: EventLoopProxy<Events>,
+) -> Result<(), String> {
+    // The subclass is kept until the window is destroyed, which only happens when we exit.
+    let proxy = Box::into_raw(Box::new(proxy));
+
+    let hwnd = HWND(window.hwnd() as isize);
+
+    let result =
+        unsafe { SetWindowSubclass(hwnd, Some(subclass_proc), SUBCLASS_ID, proxy as usize) };
+
+    if !result.as_bool() {
+        unsafe { drop(Box::from_raw(proxy)) };
+
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+
+    // The session (lock/unlock) events are only sent if we ask for them.
+    if !unsafe { WTSRegisterSessionNotification(hwnd, NOTIFY_FOR_THIS_SESSION) }.as_bool() {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+
+    Ok(())
+}
+
+unsafe extern "system" fn subclass_proc(
+    hwnd: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+    _subclass_id: usize,
+    data: usize,
+) -> LRESULT {
+    let proxy = data as *mut EventLoopProxy<Events>;
+
+    match message {
+        // Sent once the system resumes from sleep (or hibernation), even if the user isn't back yet.
+        WM_POWERBROADCAST if wparam.0 == PBT_APMRESUMEAUTOMATIC as usize => {
+            (*proxy).send_event(Events::SystemResumed).ok();
+        }
+        WM_WTSSESSION_CHANGE if wparam.0 == WTS_SESSION_LOCK as usize => {
+            (*proxy).send_event(Events::SessionLocked).ok();
+        }
+        WM_WTSSESSION_CHANGE if wparam.0 == WTS_SESSION_UNLOCK as usize => {
+            (*proxy).send_event(Events::SessionUnlocked).ok();
+        }
+        // Sent to every application before logging off or shutting down, once all of them answer
+        //  the session ends (and we are killed), so we have to clean up before that.
+        // We let the message through, which allows the session to end, but the event loop
+        //  still gets to run while Windows waits for the other applications.
+        WM_QUERYENDSESSION => {
+            (*proxy).send_event(Events::SessionEnding).ok();
+        }
+        // The last message that the window receives.
+        WM_NCDESTROY => {
+            WTSUnRegisterSessionNotification(hwnd);
+            RemoveWindowSubclass(hwnd, Some(subclass_proc), SUBCLASS_ID);
+
+            drop(Box::from_raw(proxy));
+        }
+        _ => {}
+    }
+
+    DefSubclassProc(hwnd, message, wparam, lparam)
+}
@@ -0,0 +1,125 @@
+use windows::runtime::HSTRING;
+use windows::Data::Xml::Dom::XmlDocument;
+use windows::UI::Notifications::{ToastNotification, ToastNotificationManager};
+
+use super::super::escape_xml;
+
+// As we don't have an 'AppUserModeID', we'll just steal an appropriate one.
+const APP_ID: &str = "Microsoft.Windows.MediaPlayer32";
+
+// Every notification that we tag belongs to this group.
+const CHANNELS_GROUP: &str = "channels";
+
+/// A toast notification, we build the XML ourselves as we need more than what the `winrt_notification` crate exposes.
+pub struct Toast<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+    pub icon_path: &'a str,
+    // A newer notification with the same tag replaces the previous one in the Action Center.
+    pub tag: Option<&'a str>,
+    // Windows opens it when the notification is clicked (even from the Action Center, after we exited).
+    pub link: Option<&'a str>,
+    // Goes straight to the Action Center.
+    pub suppress_popup: bool,
+    // About 25 seconds on screen, instead of about 7.
+    pub long: bool,
+}
+
+impl Toast<'_> {
+    fn xml(&self) -> String {
+        let activation = match self.link {
+            Some(link) => format!(
+                r#" activationType="protocol" launch="{}""#,
+                escape_xml(link)
+            ),
+            None => String::new(),
+        };
+
+        format!(
+            concat!(
+                r#"<toast duration="{}"{}>"#,
+                r#"<visual><binding template="ToastGeneric">"#,
+                r#"<image placement="appLogoOverride" hint-crop="circle" src="file:///{}" alt="application icon" />"#,
+                r#"<text id="1">{}</text>"#,
+                r#"<text id="2">{}</text>"#,
+                r#"</binding></visual>"#,
+                r#"<audio src="ms-winsoundevent:Notification.Reminder" />"#,
+                r#"</toast>"#
+            ),
+            if self.long { "long" } else { "short" },
+            activation,
+            escape_xml(self.icon_path),
+            escape_xml(self.title),
+            escape_xml(self.text),
+        )
+    }
+
+    pub fn show(&self) -> windows::runtime::Result<()> {
+        let document = XmlDocument::new()?;
+        document.LoadXml(HSTRING::from(self.xml()))?;
+
+        let notification = ToastNotification::CreateToastNotification(document)?;
+
+        if let Some(tag) = self.tag {
+            notification.SetTag(HSTRING::from(tag))?;
+            notification.SetGroup(HSTRING::from(CHANNELS_GROUP))?;
+        }
+
+        if self.suppress_popup {
+            notification.SetSuppressPopup(true)?;
+        }
+
+        ToastNotificationManager::CreateToastNotifierWithId(HSTRING::from(APP_ID))?
+            .Show(&notification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toast<'a>(title: &'a str, link: Option<&'a str>, long: bool) -> Toast<'a> {
+        Toast {
+            title,
+            text: "j_blow is live! (1234 viewers)",
+            icon_path: r"C:\taskbar-twitch\notification.ico",
+            tag: Some("j_blow"),
+            link,
+            suppress_popup: false,
+            long,
+        }
+    }
+
+    #[test]
+    fn toast_xml() {
+        let xml = toast("Programming a game", None, false).xml();
+
+        assert!(xml.starts_with(r#"<toast duration="short">"#), "{}", xml);
+        assert!(xml.contains(r#"src="file:///C:\taskbar-twitch\notification.ico""#));
+        assert!(xml.contains(r#"<text id="1">Programming a game</text>"#));
+        assert!(xml.contains(r#"<text id="2">j_blow is live! (1234 viewers)</text>"#));
+        assert!(!xml.contains("activationType"));
+
+        let xml = toast("Programming a game", None, true).xml();
+        assert!(xml.starts_with(r#"<toast duration="long">"#), "{}", xml);
+    }
+
+    #[test]
+    fn toast_xml_is_escaped() {
+        let link = "https://www.twitch.tv/j_blow?a=1&b=\"2\"";
+        let xml = toast("<Rust> & \"C\"", Some(link), false).xml();
+
+        assert!(
+            xml.contains(r#"<text id="1">&lt;Rust&gt; &amp; &quot;C&quot;</text>"#),
+            "{}",
+            xml
+        );
+        assert!(
+            xml.starts_with(
+                r#"<toast duration="short" activationType="protocol" launch="https://www.twitch.tv/j_blow?a=1&amp;b=&quot;2&quot;">"#
+            ),
+            "{}",
+            xml
+        );
+    }
+}
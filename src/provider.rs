@@ -0,0 +1,832 @@
+//! The platforms that the channels stream on, each one is a `StreamProvider` (e.g. `twitch::TwitchProvider`).
+//! The providers only tell which channels are live, the channels are then updated (and the user notified)
+//!  the same way for every platform, so the menu and the notifications don't depend on them.
+
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{debug, info, warn};
+use winit::event_loop::EventLoopProxy;
+
+use crate::app_state;
+use crate::avatars;
+use crate::config;
+use crate::config::{
+    Channel, ChannelStatus, LiveHistory, MeteredBehavior, NotificationDuration, NotificationKind,
+    NotificationStyle, Platform, State, ViewerTrend,
+};
+use crate::connectivity;
+use crate::diagnostics;
+use crate::kick::KickProvider;
+use crate::lock::RecoverLock;
+use crate::logging;
+use crate::metrics::METRICS;
+use crate::notification_queue;
+use crate::platform;
+use crate::platform::send_notification;
+use crate::text;
+use crate::twitch;
+use crate::twitch::TwitchProvider;
+use crate::youtube::YouTubeProvider;
+use crate::Events;
+
+// When we first see a channel that has been live for longer than this (e.g. at startup),
+//  its notification says since when.
+pub const LATE_DISCOVERY_TIME: Duration = Duration::from_secs(10 * 60);
+
+// While a channel is live, when it was last live is only saved this often.
+const LIVE_HISTORY_SAVE_TIME: Duration = Duration::from_secs(60 * 60);
+
+// A failed update of the other platforms is attempted again after this, which doubles after every failure.
+pub const FAILED_UPDATE_TIME: Duration = Duration::from_secs(5);
+
+/// A channel that is live, as a provider sees it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stream {
+    // As the platform writes it, it's compared to the channels without the case.
+    pub name: String,
+    pub title: String,
+    pub viewers: u64,
+    // Without it, we just don't show since when it's live.
+    pub started_at: Option<SystemTime>,
+    // Only Twitch has them (e.g. "DropsEnabled"), for the "tag_alerts".
+    pub tags: Vec<String>,
+    // The game or the category, e.g. "Just Chatting" (YouTube doesn't tell).
+    pub game: Option<String>,
+}
+
+/// A platform that the channels can stream on.
+///
+/// ```
+/// use taskbar_twitch::provider::{Stream, StreamProvider};
+///
+/// // Every channel is always live.
+/// struct AlwaysLive;
+///
+/// impl StreamProvider for AlwaysLive {
+///     type Error = String;
+///
+///     async fn authenticate(&mut self, _client: &reqwest::Client) -> Result<(), String> {
+///         Ok(())
+///     }
+///
+///     async fn fetch_streams(
+///         &mut self,
+///         _client: &reqwest::Client,
+///         channels: &[String],
+///     ) -> Result<Vec<Stream>, String> {
+///         Ok(channels
+///             .iter()
+///             .map(|name| Stream {
+///                 name: name.clone(),
+///                 title: String::from("Always live"),
+///                 viewers: 1,
+///                 started_at: None,
+///                 tags: Vec::new(),
+///                 game: None,
+///             })
+///             .collect())
+///     }
+///
+///     fn watch_url(channel: &str) -> String {
+///         format!("https://example.com/{}", channel)
+///     }
+/// }
+///
+/// let client = reqwest::Client::new();
+/// let streams = futures::executor::block_on(AlwaysLive.fetch_streams(&client, &[String::from("j_blow")]));
+///
+/// assert_eq!(streams.unwrap()[0].name, "j_blow");
+/// assert_eq!(AlwaysLive::watch_url("j_blow"), "https://example.com/j_blow");
+/// ```
+// Its futures are only awaited on our own executor (see `poll`), they don't need to be `Send`.
+#[allow(async_fn_in_trait)]
+pub trait StreamProvider {
+    type Error: Display;
+
+    /// Called before the first update, and again when the platform rejects the credentials.
+    async fn authenticate(&mut self, client: &reqwest::Client) -> Result<(), Self::Error>;
+
+    /// The streams of the channels that are live, the channels that aren't in it are offline.
+    async fn fetch_streams(
+        &mut self,
+        client: &reqwest::Client,
+        channels: &[String],
+    ) -> Result<Vec<Stream>, Self::Error>;
+
+    /// Where the stream can be watched, e.g. "https://twitch.tv/j_blow".
+    fn watch_url(channel: &str) -> String;
+}
+
+impl Platform {
+    pub fn watch_url(self, channel: &str) -> String {
+        match self {
+            Platform::Twitch => TwitchProvider::watch_url(channel),
+            Platform::Kick => KickProvider::watch_url(channel),
+            Platform::YouTube => YouTubeProvider::watch_url(channel),
+        }
+    }
+}
+
+/// The names of the channels of a platform, the providers only need those.
+pub fn channels_of(config: &Arc<Mutex<State>>, platform: Platform) -> Vec<String> {
+    config
+        .lock_or_recover()
+        .channels
+        .iter()
+        .filter(|channel| channel.platform == platform)
+        .map(|channel| channel.name.clone())
+        .collect()
+}
+
+/// Updates the channels of a platform other than Twitch (which has its own loop, see `twitch::listen_for_events`),
+///  on its own so that the issues of one platform don't delay the others. Runs forever.
+/// In the demo, it also replaces the loop of Twitch.
+pub async fn poll<P: StreamProvider>(
+    mut provider: P,
+    platform: Platform,
+    update_time: Duration,
+    config: Arc<Mutex<State>>,
+    proxy: &EventLoopProxy<Events>,
+) {
+    let client = reqwest::Client::new();
+
+    let mut authenticated = false;
+    let mut error_notified = false;
+
+    // How long to wait before the next update while they are failing.
+    let mut failure_backoff: Option<Duration> = None;
+
+    loop {
+        let channels = channels_of(&config, platform);
+        let metered = connectivity::metered_behavior(&config.lock_or_recover());
+
+        if metered == MeteredBehavior::Pause {
+            debug!("Skipped the {} update, the connection is metered", platform);
+        } else if !channels.is_empty() {
+            let mut result = Ok(());
+
+            if !authenticated {
+                result = provider.authenticate(&client).await;
+                authenticated = result.is_ok();
+            }
+
+            let result = match result {
+                Ok(()) => provider.fetch_streams(&client, &channels).await,
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(streams) => {
+                    error_notified = false;
+                    failure_backoff = None;
+
+                    apply_streams(&config, platform, &channels, &streams, false);
+                    send_auto_open(&config, proxy);
+
+                    if platform == Platform::Twitch {
+                        let now = Some(Instant::now());
+
+                        config.lock_or_recover().last_update = now;
+                        twitch::SCHEDULE.lock_or_recover().last_update = now;
+                    }
+                }
+                Err(e) => {
+                    diagnostics::record_error(&format!(
+                        "Couldn't update the {} channels: {}",
+                        platform, e
+                    ));
+
+                    // We don't know which channels are live anymore.
+                    for channel in &mut config.lock_or_recover().channels {
+                        if channel.platform == platform {
+                            channel.set_unknown();
+                        }
+                    }
+
+                    if !error_notified {
+                        error_notified = true;
+
+                        send_notification(
+                            &format!("Couldn't update the {} channels.", platform),
+                            &e.to_string(),
+                        );
+                    }
+
+                    let backoff = failure_backoff.map_or(FAILED_UPDATE_TIME, |backoff| backoff * 2);
+                    failure_backoff = Some(backoff.min(update_time));
+
+                    warn!("Retrying the {} update in {:?}", platform, failure_backoff);
+                }
+            }
+
+            proxy.send_event(Events::UpdatedChannels).ok();
+        }
+
+        let last_attempt = Instant::now();
+        let wait = match metered {
+            MeteredBehavior::Reduce => update_time.max(connectivity::METERED_UPDATE_TIME),
+            _ => update_time,
+        };
+        let wait = failure_backoff.unwrap_or(wait);
+
+        // In the demo, this is the loop of Twitch.
+        if platform == Platform::Twitch {
+            twitch::SCHEDULE.lock_or_recover().next_update = Some(last_attempt + wait);
+        }
+
+        // The new channels are updated right away.
+        while last_attempt.elapsed() < wait && channels_of(&config, platform) == channels {
+            std::thread::sleep(Duration::from_millis(500));
+        }
+    }
+}
+
+/// Asks the event loop to open the streams of "auto_open" that went live, they are opened there
+///  with the current player (see `Events::AutoOpenChannel`).
+pub fn send_auto_open(config: &Arc<Mutex<State>>, proxy: &EventLoopProxy<Events>) {
+    let pending = std::mem::take(&mut config.lock_or_recover().pending_auto_open);
+
+    for name in pending {
+        proxy.send_event(Events::AutoOpenChannel(name)).ok();
+    }
+}
+
+/// Updates the channels of a platform from its streams, and notifies the user of the changes.
+/// If `summarize` is set, there's no notification for each channel that went live, instead they are returned
+///  so that the caller can show a single one (e.g. after the system was asleep for the night).
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// # use std::time::{SystemTime, UNIX_EPOCH};
+///
+/// use taskbar_twitch::config::{Platform, State};
+/// # use taskbar_twitch::config::{LiveHistory, TitleChange};
+/// use taskbar_twitch::provider::{apply_streams, Stream};
+///
+/// let state: State = serde_json::from_str(
+///     r#"{"player": "browser", "channels": ["j_blow"], "notify_already_live": true}"#,
+/// )
+/// .unwrap();
+///
+/// # // Already up to date, so that the example doesn't save them in the app data.
+/// # let mut state = state;
+/// # let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+/// # state.channels[0].live_history = Some(LiveHistory { tracked_since: now, last_live: Some(now) });
+/// # state.channels[0].title_history = vec![TitleChange { title: String::from("Programming a game"), at: now }];
+/// let config = Arc::new(Mutex::new(state));
+///
+/// let stream = Stream {
+///     name: String::from("J_Blow"),
+///     title: String::from("Programming a game"),
+///     viewers: 1200,
+///     started_at: None,
+///     tags: Vec::new(),
+///     game: None,
+/// };
+///
+/// let went_live = apply_streams(&config, Platform::Twitch, &[String::from("j_blow")], &[stream], true);
+///
+/// // As the platform writes the name.
+/// assert_eq!(went_live, ["J_Blow"]);
+/// assert!(config.lock().unwrap().channels[0].is_online());
+/// ```
+pub fn apply_streams(
+    config: &Arc<Mutex<State>>,
+    platform: Platform,
+    requested: &[String],
+    streams: &[Stream],
+    summarize: bool,
+) -> Vec<String> {
+    let local_config: &mut State = &mut config.lock_or_recover();
+
+    // Take the channels out of the state while we update them,
+    //  so that we can still check the notification preferences.
+    let mut channels = std::mem::take(&mut local_config.channels);
+
+    let mut went_live = Vec::new();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let mut history_changed = false;
+    let mut live_history_changed = false;
+
+    // The channels that weren't requested this time (see `twitch::logins_to_update`) are left as they were,
+    //  they aren't offline just because they aren't in the streams.
+    for channel in channels
+        .iter_mut()
+        .filter(|c| c.platform == platform && requested.contains(&c.name))
+    {
+        // Check if we found the channel, not case sensitive.
+        let stream = streams
+            .iter()
+            .find(|stream| stream.name.eq_ignore_ascii_case(&channel.name));
+
+        // From its first update, so that a new channel isn't inactive right away.
+        let live_history = channel.live_history.get_or_insert_with(|| {
+            live_history_changed = true;
+
+            LiveHistory {
+                tracked_since: now,
+                last_live: None,
+            }
+        });
+
+        if stream.is_some() {
+            live_history_changed |= live_history.last_live.is_none_or(|last_live| {
+                now.saturating_sub(last_live) >= LIVE_HISTORY_SAVE_TIME.as_secs()
+            });
+
+            live_history.last_live = Some(now);
+        }
+
+        if let Some(stream) = stream {
+            let name = stream.name.as_str();
+            let title = text::sanitize(&stream.title, text::MAX_TITLE_LENGTH);
+            let viewers = stream.viewers;
+            let game = stream
+                .game
+                .as_deref()
+                .map(|game| text::sanitize(game, text::MAX_TITLE_LENGTH))
+                .filter(|game| !game.is_empty());
+
+            // If the title changed when the channel was live,
+            //  we may want to notify the user based on their preferences.
+            let style =
+                local_config.notification_style(&channel.name, NotificationKind::TitleChanged);
+
+            let title_changed =
+                channel.status == ChannelStatus::Online && channel.title != Some(title.clone());
+
+            let grace = Duration::from_secs(local_config.title_change_grace);
+
+            if title_changed && just_started(channel, stream.started_at, grace) {
+                info!(
+                    "{} changed its title right after going live (no notification): {}",
+                    channel.name, title
+                );
+            } else if title_changed && style != NotificationStyle::Off {
+                let notification_text = format!(
+                    "{} has changed its title! ({} viewers)",
+                    name,
+                    local_config.viewer_format.format(viewers)
+                );
+
+                info!("{} changed its title: {}", channel.name, title);
+
+                send_channel_notification(
+                    channel,
+                    &title,
+                    &notification_text,
+                    NotificationKind::TitleChanged,
+                    style,
+                    local_config
+                        .notification_duration
+                        .of(NotificationKind::TitleChanged),
+                );
+            }
+
+            // Same for the game, going live has its own notification.
+            let style =
+                local_config.notification_style(&channel.name, NotificationKind::GameChanged);
+
+            let previous_game = match (&channel.game, &game) {
+                (Some(previous), Some(game))
+                    if channel.status == ChannelStatus::Online && previous != game =>
+                {
+                    Some(previous)
+                }
+                _ => None,
+            };
+
+            if let (Some(previous_game), Some(game)) = (previous_game, &game) {
+                info!(
+                    "{} switched from {} to {}",
+                    channel.name, previous_game, game
+                );
+
+                if style != NotificationStyle::Off {
+                    let notification_text = format!(
+                        "{} switched from {} to {}! ({} viewers)",
+                        name,
+                        previous_game,
+                        game,
+                        local_config.viewer_format.format(viewers)
+                    );
+
+                    send_channel_notification(
+                        channel,
+                        &title,
+                        &notification_text,
+                        NotificationKind::GameChanged,
+                        style,
+                        local_config
+                            .notification_duration
+                            .of(NotificationKind::GameChanged),
+                    );
+                }
+            }
+
+            // If the channel wasn't live before but is now, notify the user.
+            // If we didn't know (e.g. at startup), it was probably live already.
+            let style = local_config.notification_style(&channel.name, NotificationKind::Live);
+
+            let notify_live = match channel.status {
+                ChannelStatus::Offline { .. } => true,
+                ChannelStatus::Unknown => local_config.notify_already_live,
+                ChannelStatus::Online => false,
+            };
+
+            if channel.status != ChannelStatus::Online {
+                info!("{} is live ({} viewers)", channel.name, viewers);
+            }
+
+            // Same as the notifications, a channel that was already live isn't new.
+            if notify_live {
+                channel.unseen = true;
+            }
+
+            // Only when we saw it go live: not at startup, and not while the user is away (`summarize`).
+            let auto_open = matches!(channel.status, ChannelStatus::Offline { .. })
+                && !summarize
+                && local_config.is_auto_opened(&channel.name);
+
+            if auto_open {
+                info!("Opening the stream of {} (auto_open)", channel.name);
+
+                local_config.pending_auto_open.push(channel.name.clone());
+            }
+
+            if notify_live && style != NotificationStyle::Off {
+                if summarize {
+                    went_live.push(String::from(name));
+                } else {
+                    let mut notification_text = format!(
+                        "{} is live! ({} viewers",
+                        name,
+                        local_config.viewer_format.format(viewers)
+                    );
+
+                    // If it was already live when we first saw it, the user might have missed the start.
+                    let since = match (channel.status, stream.started_at) {
+                        (ChannelStatus::Unknown, Some(started_at)) => late_start(started_at),
+                        _ => None,
+                    };
+
+                    if let Some(since) = since {
+                        notification_text.push_str(", since ");
+                        notification_text.push_str(&since);
+                    }
+
+                    notification_text.push(')');
+
+                    send_channel_notification(
+                        channel,
+                        &title,
+                        &notification_text,
+                        NotificationKind::Live,
+                        style,
+                        local_config
+                            .notification_duration
+                            .of(NotificationKind::Live),
+                    );
+                }
+            }
+
+            local_config
+                .activity
+                .record_live(&channel.name, viewers, &title);
+
+            send_tag_alerts(local_config, channel, &title, &stream.tags);
+
+            history_changed |= channel.record_title(&title, now);
+
+            // The first update of a stream has nothing to compare to.
+            channel.viewer_trend = match (channel.status, channel.viewers) {
+                (ChannelStatus::Online, Some(previous)) => ViewerTrend::of(previous, viewers),
+                _ => ViewerTrend::Flat,
+            };
+
+            channel.title = Some(title);
+            channel.viewers = Some(viewers);
+            channel.started_at = stream.started_at;
+            channel.tags = stream.tags.clone();
+            channel.game = game;
+
+            if channel.status != ChannelStatus::Online {
+                channel.live_since = Some(Instant::now());
+            }
+
+            channel.status = ChannelStatus::Online;
+        } else {
+            local_config.activity.record_offline(&channel.name);
+
+            let style = local_config.notification_style(&channel.name, NotificationKind::Offline);
+
+            let was_online = channel.status == ChannelStatus::Online;
+
+            if was_online {
+                info!("{} went offline", channel.name);
+            }
+
+            channel.unseen = false;
+
+            if was_online && style != NotificationStyle::Off {
+                let notification_text = format!("{} is now offline.", channel.name);
+
+                send_channel_notification(
+                    channel,
+                    "Stream ended",
+                    &notification_text,
+                    NotificationKind::Offline,
+                    style,
+                    local_config
+                        .notification_duration
+                        .of(NotificationKind::Offline),
+                );
+            }
+
+            if !matches!(channel.status, ChannelStatus::Offline { .. }) {
+                // Keeps the fields of the last stream.
+                channel.set_unknown();
+                channel.alerted_tags.clear();
+                channel.status = ChannelStatus::Offline {
+                    since: Instant::now(),
+                };
+            }
+        }
+    }
+
+    let (live, total) = channels
+        .iter()
+        .filter(|channel| channel.platform == platform)
+        .fold((0, 0), |(live, total), channel| {
+            (live + channel.is_online() as usize, total + 1)
+        });
+
+    info!(
+        "Updated the {} channels ({} of {} live)",
+        platform, live, total
+    );
+
+    if history_changed {
+        if let Err(e) = app_state::write_title_history(&channels) {
+            diagnostics::record_error(&format!("Couldn't save the title history: {}", e));
+        }
+    }
+
+    if live_history_changed {
+        if let Err(e) = app_state::write_live_history(&channels) {
+            diagnostics::record_error(&format!("Couldn't save the live history: {}", e));
+        }
+    }
+
+    local_config.channels = channels;
+
+    went_live
+}
+
+/// The local time at which a stream started (e.g. "19:05"), unless it started recently.
+fn late_start(started_at: SystemTime) -> Option<String> {
+    let uptime = started_at.elapsed().ok()?;
+
+    if uptime < LATE_DISCOVERY_TIME {
+        return None;
+    }
+
+    Some(logging::format_clock_time(
+        started_at,
+        logging::utc_offset()?,
+    ))
+}
+
+/// Whether a stream started less than `grace` ago, from when the platform says it started (or when we saw it).
+fn just_started(channel: &Channel, started_at: Option<SystemTime>, grace: Duration) -> bool {
+    let live_for = match started_at {
+        Some(started_at) => started_at.elapsed().ok(),
+        None => channel.live_since.map(|live_since| live_since.elapsed()),
+    };
+
+    live_for.is_some_and(|live_for| live_for < grace)
+}
+
+/// Notifies of the "tag_alerts" of a live channel, a tag only notifies once per stream (even if the channel
+///  removes it and adds it back), the first time that it's seen (e.g. right when it goes live).
+fn send_tag_alerts(config: &State, channel: &mut Channel, title: &str, tags: &[String]) {
+    let new_tags: Vec<&str> =
+        tags.iter()
+            .filter(|tag| {
+                config.tag_alerts.iter().any(|alert| {
+                    alert.applies_to(&channel.name) && config::same_tag(&alert.tag, tag)
+                })
+            })
+            .filter(|tag| {
+                !channel
+                    .alerted_tags
+                    .iter()
+                    .any(|alerted| config::same_tag(alerted, tag))
+            })
+            .map(String::as_str)
+            .collect();
+
+    if new_tags.is_empty() {
+        return;
+    }
+
+    channel
+        .alerted_tags
+        .extend(new_tags.iter().map(|tag| String::from(*tag)));
+
+    info!("{} has the tags: {}", channel.name, new_tags.join(", "));
+
+    let style = config.notification_style.live;
+
+    if style == NotificationStyle::Off || config.snoozed_until(&channel.name).is_some() {
+        return;
+    }
+
+    notification_queue::push(&platform::Notification {
+        title: &format!("{} has {}", channel.name, new_tags.join(", ")),
+        text: title,
+        // Not the tag of the channel, so that it doesn't replace its other notifications.
+        tag: Some(&format!("{}:tags", channel.name)),
+        quiet: style == NotificationStyle::Quiet,
+        long: config.notification_duration.live == NotificationDuration::Long,
+        icon: None,
+        link: None,
+    });
+}
+
+/// A newer notification for the same channel replaces the previous one.
+fn send_channel_notification(
+    channel: &Channel,
+    title: &str,
+    text: &str,
+    kind: NotificationKind,
+    style: NotificationStyle,
+    duration: NotificationDuration,
+) {
+    info!("Notification for {} ({:?}): {}", channel.name, style, text);
+
+    METRICS.record_notification(kind);
+
+    // Only if it was already downloaded, see the `avatars` module.
+    let avatar = match kind {
+        NotificationKind::Live | NotificationKind::TitleChanged | NotificationKind::GameChanged
+            if platform::NOTIFICATION_AVATARS && channel.platform == Platform::Twitch =>
+        {
+            avatars::cached(&channel.name)
+        }
+        _ => None,
+    };
+
+    // Clicking "is live!" opens the stream, the other notifications don't need to.
+    let link = match kind {
+        NotificationKind::Live => Some(channel.platform.watch_url(&channel.name)),
+        _ => None,
+    };
+
+    notification_queue::push(&platform::Notification {
+        title,
+        text,
+        tag: Some(&channel.name),
+        quiet: style == NotificationStyle::Quiet,
+        long: duration == NotificationDuration::Long,
+        icon: avatar.as_deref(),
+        link: link.as_deref(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::{test_state, TitleChange};
+
+    const TITLE: &str = "Programming a game";
+
+    fn state(status: ChannelStatus, notify_already_live: bool) -> Arc<Mutex<State>> {
+        let mut state = test_state(serde_json::json!({
+            "channels": ["j_blow"],
+            "notify_already_live": notify_already_live,
+        }));
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        // Already up to date, so that the update doesn't save them.
+        let channel = &mut state.channels[0];
+        channel.status = status;
+        channel.live_history = Some(LiveHistory {
+            tracked_since: now,
+            last_live: Some(now),
+        });
+        channel.title_history = vec![TitleChange {
+            title: String::from(TITLE),
+            at: now,
+        }];
+
+        if status == ChannelStatus::Online {
+            channel.title = Some(String::from(TITLE));
+            channel.viewers = Some(100);
+        }
+
+        Arc::new(Mutex::new(state))
+    }
+
+    fn stream() -> Stream {
+        Stream {
+            name: String::from("J_Blow"),
+            title: String::from(TITLE),
+            viewers: 120,
+            started_at: None,
+            tags: Vec::new(),
+            game: None,
+        }
+    }
+
+    #[test]
+    fn status_transitions() {
+        let long_ago = Instant::now() - Duration::from_secs(60);
+        let offline = ChannelStatus::Offline { since: long_ago };
+
+        // The status before, whether it's live, "notify_already_live", the status after, and whether it went live.
+        let cases = [
+            (ChannelStatus::Unknown, true, false, "online", false),
+            (ChannelStatus::Unknown, true, true, "online", true),
+            (offline, true, false, "online", true),
+            (ChannelStatus::Online, true, true, "online", false),
+            (ChannelStatus::Unknown, false, false, "offline", false),
+            (ChannelStatus::Online, false, false, "offline", false),
+            (offline, false, false, "offline", false),
+        ];
+
+        for (before, live, notify_already_live, after, went_live) in cases {
+            let config = state(before, notify_already_live);
+            let streams = if live { vec![stream()] } else { Vec::new() };
+
+            // Summarized, so that the channels that went live are returned instead of notified.
+            let result = apply_streams(
+                &config,
+                Platform::Twitch,
+                &[String::from("j_blow")],
+                &streams,
+                true,
+            );
+
+            let state = config.lock_or_recover();
+            let channel = &state.channels[0];
+            let case = format!("{:?} (live: {})", before, live);
+
+            let status = match channel.status {
+                ChannelStatus::Online => "online",
+                ChannelStatus::Offline { .. } => "offline",
+                ChannelStatus::Unknown => "unknown",
+            };
+
+            assert_eq!(status, after, "{}", case);
+            assert_eq!(!result.is_empty(), went_live, "{}", case);
+            assert_eq!(channel.is_online(), channel.viewers.is_some(), "{}", case);
+
+            // A channel that stays offline is offline since the first time.
+            if before == offline && !live {
+                assert_eq!(channel.status, offline, "{}", case);
+            }
+        }
+    }
+
+    #[test]
+    fn going_offline_keeps_the_last_stream() {
+        let config = state(ChannelStatus::Online, false);
+
+        apply_streams(
+            &config,
+            Platform::Twitch,
+            &[String::from("j_blow")],
+            &[],
+            true,
+        );
+
+        let state = config.lock_or_recover();
+        let channel = &state.channels[0];
+
+        assert_eq!(channel.title, None);
+        assert_eq!(channel.last_title.as_deref(), Some(TITLE));
+        assert_eq!(channel.last_viewers, Some(100));
+    }
+
+    #[test]
+    fn channels_that_werent_requested_are_left_as_they_were() {
+        let config = state(ChannelStatus::Online, false);
+
+        apply_streams(&config, Platform::Twitch, &[], &[], true);
+
+        assert!(config.lock_or_recover().channels[0].is_online());
+    }
+}
@@ -0,0 +1,261 @@
+//! Replaces the running executable with the one of a newer release, when "self_update" is enabled.
+//! A running executable can't be overwritten (on Windows), but it can be renamed: the current one becomes
+//!  "<name>.old", the download takes its place, and the new one is started (it removes the old one).
+//! Nothing is changed until the download is checked, and if anything fails after that the current one is put back.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::sha256;
+use crate::updates::{Asset, Release, USER_AGENT};
+
+// So that clicking the menu again doesn't start another download.
+static INSTALLING: AtomicBool = AtomicBool::new(false);
+
+// One per second.
+pub const REMOVE_ATTEMPTS: u32 = 10;
+
+/// The file of the release for this system, e.g. "taskbar-twitch-x86_64.exe".
+/// If there's only one executable, that's the one.
+pub fn release_asset(release: &Release) -> Option<&Asset> {
+    let executables: Vec<&Asset> = release
+        .assets
+        .iter()
+        .filter(|asset| is_executable(&asset.name))
+        .collect();
+
+    let for_architecture = executables
+        .iter()
+        .find(|asset| asset.name.to_lowercase().contains(std::env::consts::ARCH));
+
+    match for_architecture {
+        Some(asset) => Some(asset),
+        None if executables.len() == 1 => executables.first().copied(),
+        None => None,
+    }
+}
+
+fn is_executable(name: &str) -> bool {
+    let name = name.to_lowercase();
+
+    if cfg!(windows) {
+        return name.ends_with(".exe");
+    }
+
+    // The other files are archives (or checksums), which we can't install.
+    name.contains(std::env::consts::OS)
+        && ![".sha256", ".zip", ".tar.gz", ".dmg", ".deb", ".txt"]
+            .iter()
+            .any(|extension| name.ends_with(extension))
+}
+
+/// Downloads, checks and installs a release, then starts it with the same config file (or profile).
+/// It's called from another thread, the caller exits once it returns `Ok`.
+pub fn install(release: &Release, config_file: &str) -> Result<(), String> {
+    if INSTALLING.swap(true, Ordering::Relaxed) {
+        return Err(String::from("The update is already being installed."));
+    }
+
+    let result = download_and_replace(release, config_file);
+
+    INSTALLING.store(false, Ordering::Relaxed);
+
+    result
+}
+
+fn download_and_replace(release: &Release, config_file: &str) -> Result<(), String> {
+    let asset = release_asset(release).ok_or_else(|| {
+        format!(
+            "The release v{} doesn't have a file for this system ({}).",
+            release.version,
+            std::env::consts::ARCH
+        )
+    })?;
+
+    let client = reqwest::Client::new();
+
+    // The size alone isn't enough to replace the running executable.
+    let expected = expected_digest(&client, release, asset).ok_or_else(|| {
+        format!(
+            "The release v{} doesn't have a checksum for {}, it can't be installed.",
+            release.version, asset.name
+        )
+    })?;
+
+    info!("Downloading {} ({} bytes)", asset.name, asset.size);
+
+    let data = futures::executor::block_on(download(&client, &asset.browser_download_url))
+        .map_err(|e| format!("Couldn't download {}: {}", asset.name, e))?;
+
+    if data.len() as u64 != asset.size {
+        return Err(format!(
+            "The download of {} is incomplete ({} of {} bytes).",
+            asset.name,
+            data.len(),
+            asset.size
+        ));
+    }
+
+    if sha256::hex_digest(&data) != expected {
+        return Err(format!(
+            "The checksum of {} doesn't match the release.",
+            asset.name
+        ));
+    }
+
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Couldn't find the running executable: {}", e))?;
+
+    replace_executable(&exe, &data)?;
+
+    info!("Installed v{}, restarting", release.version);
+
+    if let Err(e) = Command::new(&exe)
+        .args(relaunch_arguments(config_file))
+        .spawn()
+    {
+        // The new version can't even start, so the current one is put back.
+        restore_executable(&exe);
+
+        return Err(format!("Couldn't start the new version: {}", e));
+    }
+
+    Ok(())
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, reqwest::Error> {
+    let response = client
+        .get(url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// The SHA-256 of an asset, from GitHub (the newer releases have it) or from a "<name>.sha256" asset.
+fn expected_digest(client: &reqwest::Client, release: &Release, asset: &Asset) -> Option<String> {
+    if let Some(digest) = &asset.digest {
+        return digest
+            .strip_prefix("sha256:")
+            .map(|digest| digest.trim().to_lowercase());
+    }
+
+    let checksum_name = format!("{}.sha256", asset.name);
+    let checksum = release
+        .assets
+        .iter()
+        .find(|other| other.name.eq_ignore_ascii_case(&checksum_name))?;
+
+    let text = match futures::executor::block_on(download(client, &checksum.browser_download_url)) {
+        Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+        Err(e) => {
+            warn!("Couldn't download {}: {}", checksum.name, e);
+            return None;
+        }
+    };
+
+    // e.g. "9f86d08...  taskbar-twitch.exe"
+    text.split_whitespace().next().map(str::to_lowercase)
+}
+
+fn sibling(exe: &Path, suffix: &str) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+
+    exe.with_file_name(name)
+}
+
+/// Writes the new executable next to the current one, then swaps them.
+fn replace_executable(exe: &Path, data: &[u8]) -> Result<(), String> {
+    let new = sibling(exe, ".new");
+    let old = sibling(exe, ".old");
+
+    std::fs::write(&new, data).map_err(|e| format!("Couldn't write {}: {}", new.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Err(e) = std::fs::set_permissions(&new, std::fs::Permissions::from_mode(0o755)) {
+            std::fs::remove_file(&new).ok();
+            return Err(format!("Couldn't make {} executable: {}", new.display(), e));
+        }
+    }
+
+    // Left by a previous update, if it couldn't be removed.
+    std::fs::remove_file(&old).ok();
+
+    if let Err(e) = std::fs::rename(exe, &old) {
+        std::fs::remove_file(&new).ok();
+        return Err(format!("Couldn't rename {}: {}", exe.display(), e));
+    }
+
+    if let Err(e) = std::fs::rename(&new, exe) {
+        std::fs::rename(&old, exe).ok();
+        std::fs::remove_file(&new).ok();
+        return Err(format!("Couldn't replace {}: {}", exe.display(), e));
+    }
+
+    Ok(())
+}
+
+fn restore_executable(exe: &Path) {
+    let old = sibling(exe, ".old");
+
+    if let Err(e) = std::fs::remove_file(exe).and_then(|_| std::fs::rename(&old, exe)) {
+        warn!("Couldn't put back {}: {}", exe.display(), e);
+    }
+}
+
+/// The arguments of the current process, with the config file that is in use (which changes with the profiles).
+fn relaunch_arguments(config_file: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = std::env::args().skip(1);
+
+    while let Some(argument) = current.next() {
+        if argument == "-f" || argument == "--file" {
+            current.next();
+        } else if !argument.starts_with("--file=") {
+            arguments.push(argument);
+        }
+    }
+
+    arguments.push(String::from("--file"));
+    arguments.push(String::from(config_file));
+
+    arguments
+}
+
+/// Removes the executable that was replaced by the last update, it can't be removed while it's running
+///  so it's attempted a few times (right after an update, the previous version is still exiting).
+pub fn remove_previous_version() {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(_) => return,
+    };
+
+    let old = sibling(&exe, ".old");
+
+    for attempt in 1..=REMOVE_ATTEMPTS {
+        if !old.exists() {
+            return;
+        }
+
+        match std::fs::remove_file(&old) {
+            Ok(()) => {
+                info!("Removed the previous version ({})", old.display());
+                return;
+            }
+            Err(e) if attempt == REMOVE_ATTEMPTS => {
+                warn!("Couldn't remove {}: {}", old.display(), e)
+            }
+            Err(_) => std::thread::sleep(Duration::from_secs(1)),
+        }
+    }
+}
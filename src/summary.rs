@@ -0,0 +1,200 @@
+//! The daily "what you missed" notification, built from what we saw while running:
+//!  who streamed, for how long, with how many viewers and which titles.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use crate::config::State;
+use crate::diagnostics;
+use crate::lock::RecoverLock;
+use crate::platform;
+use crate::platform::send_notification;
+
+// A longer gap between two updates (e.g. the system was asleep) isn't counted as streaming time.
+pub const MAX_UPDATE_GAP: Duration = Duration::from_secs(15 * 60);
+
+// The notification only has room for a few lines, the rest are in the debug info.
+pub const MAX_NOTIFICATION_CHANNELS: usize = 4;
+
+#[derive(Clone, Debug)]
+struct ChannelActivity {
+    name: String,
+    streamed: Duration,
+    peak_viewers: u64,
+    titles: Vec<String>,
+    // When we last saw it live, unless it went offline since.
+    last_seen: Option<Instant>,
+}
+
+/// What the channels did since the last summary.
+#[derive(Clone, Debug, Default)]
+pub struct Activity {
+    channels: Vec<ChannelActivity>,
+}
+
+impl Activity {
+    /// Called after every update, for each channel that is live.
+    pub fn record_live(&mut self, name: &str, viewers: u64, title: &str) {
+        let now = Instant::now();
+
+        let index = match self
+            .channels
+            .iter()
+            .position(|channel| channel.name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.channels.push(ChannelActivity {
+                    name: String::from(name),
+                    streamed: Duration::ZERO,
+                    peak_viewers: 0,
+                    titles: Vec::new(),
+                    last_seen: None,
+                });
+
+                self.channels.len() - 1
+            }
+        };
+
+        let channel = &mut self.channels[index];
+
+        if let Some(last_seen) = channel.last_seen {
+            let elapsed = now.duration_since(last_seen);
+
+            if elapsed <= MAX_UPDATE_GAP {
+                channel.streamed += elapsed;
+            }
+        }
+
+        channel.last_seen = Some(now);
+        channel.peak_viewers = channel.peak_viewers.max(viewers);
+
+        if !channel.titles.iter().any(|t| t == title) {
+            channel.titles.push(String::from(title));
+        }
+    }
+
+    /// The time until the next update isn't counted once a channel goes offline.
+    pub fn record_offline(&mut self, name: &str) {
+        if let Some(channel) = self.channels.iter_mut().find(|c| c.name == name) {
+            channel.last_seen = None;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// The channels that streamed the longest come first.
+    fn sorted(&self) -> Vec<&ChannelActivity> {
+        let mut channels: Vec<&ChannelActivity> = self.channels.iter().collect();
+
+        channels.sort_by_key(|channel| std::cmp::Reverse(channel.streamed));
+        channels
+    }
+
+    /// One line per channel, e.g. "j_blow: 3h 20m, up to 1234 viewers".
+    pub fn notification_text(&self) -> String {
+        let channels = self.sorted();
+
+        let mut lines: Vec<String> = channels
+            .iter()
+            .take(MAX_NOTIFICATION_CHANNELS)
+            .map(|channel| {
+                format!(
+                    "{}: {}, up to {} viewers",
+                    channel.name,
+                    format_streamed(channel.streamed),
+                    channel.peak_viewers
+                )
+            })
+            .collect();
+
+        if channels.len() > MAX_NOTIFICATION_CHANNELS {
+            lines.push(format!(
+                "and {} more",
+                channels.len() - MAX_NOTIFICATION_CHANNELS
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Same as the notification, but with every channel and their titles.
+    pub fn report(&self) -> String {
+        let mut result = String::new();
+
+        for channel in self.sorted() {
+            result.push_str(
+                format!(
+                    "{}: streamed for {}, up to {} viewers\n",
+                    channel.name,
+                    format_streamed(channel.streamed),
+                    channel.peak_viewers
+                )
+                .as_str(),
+            );
+
+            for title in &channel.titles {
+                result.push_str(format!("  {}\n", title).as_str());
+            }
+        }
+
+        result
+    }
+}
+
+/// e.g. "3h 20m", "45m"
+pub fn format_streamed(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// The day (year, month, day) on which the summary would be sent, if it's past the configured time.
+/// When we start after that time, the summary of the day counts as sent (there's nothing to summarize yet).
+pub fn due_on(config: &Arc<Mutex<State>>) -> Option<(u16, u8, u8)> {
+    let time = config.lock_or_recover().daily_summary?.time;
+    let now = platform::local_time()?;
+
+    if (now.hour, now.minute) >= (time.hour, time.minute) {
+        Some(now.date())
+    } else {
+        None
+    }
+}
+
+/// Sends the summary once a day, once it's past the configured time (or as soon as we can,
+///  e.g. if the system was asleep). `sent_on` is the last day it was sent.
+/// Days without any activity don't have a summary.
+pub fn send_if_due(config: &Arc<Mutex<State>>, sent_on: &mut Option<(u16, u8, u8)>) {
+    let today = match due_on(config) {
+        Some(today) => today,
+        None => return,
+    };
+
+    if *sent_on == Some(today) {
+        return;
+    }
+
+    *sent_on = Some(today);
+
+    let activity = std::mem::take(&mut config.lock_or_recover().activity);
+
+    if activity.is_empty() {
+        info!("Nothing to summarize today");
+        return;
+    }
+
+    info!("Sending the daily summary");
+
+    diagnostics::record_summary(&activity.report());
+
+    send_notification("What you missed today", &activity.notification_text());
+}
@@ -0,0 +1,58 @@
+//! The templates of the config (e.g. `"menu_format": "{name} - {title} ({viewers})"`).
+//! The placeholders are names between braces, the values that are missing (e.g. the viewers
+//!  of an offline channel) are left empty.
+
+/// Replaces the placeholders that have a value, the unknown ones are kept as they are.
+pub fn render(template: &str, values: &[(&str, Option<String>)]) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+
+        let after = &rest[start + 1..];
+
+        let end = match after.find('}') {
+            Some(end) => end,
+            None => {
+                result.push_str(&rest[start..]);
+                return result;
+            }
+        };
+
+        match values.iter().find(|(name, _)| *name == &after[..end]) {
+            Some((_, value)) => result.push_str(value.as_deref().unwrap_or_default()),
+            None => result.push_str(&rest[start..start + end + 2]),
+        }
+
+        rest = &after[end + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// The placeholders of a template that aren't in `known`, e.g. a typo like "{viewer}".
+pub fn unknown_placeholders(template: &str, known: &[&str]) -> Vec<String> {
+    let mut unknown = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+
+        let end = match after.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+
+        let name = &after[..end];
+
+        if !known.contains(&name) && !unknown.iter().any(|u| u == name) {
+            unknown.push(String::from(name));
+        }
+
+        rest = &after[end + 1..];
+    }
+
+    unknown
+}
@@ -0,0 +1,33 @@
+//! The titles come from the streamers, they can have anything in them: emoji, right-to-left text,
+//!  and sometimes control characters (which break the menu items and the notifications).
+//! They are cleaned up once, when they are received, so that every place that shows them gets the same text.
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Twitch doesn't allow longer titles, the other platforms are cut to the same length.
+pub const MAX_TITLE_LENGTH: usize = 140;
+
+/// Removes the control characters and the repeated whitespace (e.g. "a\n\n b" is "a b"), and cuts the text
+///  to `max_length` user-perceived characters (so that e.g. a flag is never cut in half), followed by "…".
+pub fn sanitize(text: &str, max_length: usize) -> String {
+    let mut cleaned = String::with_capacity(text.len());
+
+    // The control characters become whitespace, which is collapsed with the rest.
+    for c in text.nfc() {
+        if c.is_whitespace() || c.is_control() {
+            if !cleaned.is_empty() && !cleaned.ends_with(' ') {
+                cleaned.push(' ');
+            }
+        } else {
+            cleaned.push(c);
+        }
+    }
+
+    let cleaned = cleaned.trim_end();
+
+    match cleaned.grapheme_indices(true).nth(max_length) {
+        Some((end, _)) => format!("{}…", cleaned[..end].trim_end()),
+        None => String::from(cleaned),
+    }
+}
@@ -1,208 +1,1216 @@
+use crate::app_state;
+use crate::avatars;
 use crate::config;
+use crate::config::AdaptivePolling;
+use crate::config::Broadcaster;
+use crate::config::BroadcasterType;
+use crate::config::ChannelStatus;
+use crate::config::MeteredBehavior;
+use crate::config::NotificationDuration;
+use crate::config::NotificationStyle;
+use crate::config::OnLock;
+use crate::config::Platform;
+use crate::config::Priority;
+use crate::config::Secret;
 use crate::config::State;
-use crate::send_notification;
+use crate::connectivity;
+use crate::diagnostics;
+use crate::lock::RecoverLock;
+use crate::logging;
+use crate::metrics::METRICS;
+use crate::notification_queue;
+use crate::platform;
+use crate::platform::send_notification;
+use crate::provider;
+use crate::provider::{Stream, StreamProvider};
+use crate::summary;
 use crate::Events;
 
+use std::fmt::Display;
 use std::sync::mpsc;
 use std::sync::mpsc::TryRecvError;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
+use log::{debug, info, warn};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
 use serde_json::Value;
 
 use winit::event_loop::EventLoopProxy;
 
+// The default "update_interval_seconds", and the shortest one we accept.
 pub const UPDATE_CHANNELS_TIME: u64 = 60;
+pub const MIN_UPDATE_CHANNELS_TIME: u64 = 15;
+pub const LOCKED_UPDATE_CHANNELS_TIME: u64 = 10 * 60;
 pub const READ_CONFIG_FILE_TIME: Duration = Duration::from_secs(3);
 pub const MAX_RETRIES: u32 = 3;
 
-async fn get_token(client: &reqwest::Client, config: &Arc<Mutex<State>>) -> String {
-    // Get the mutex, build the URL based on the client & secret and unlock it.
-    let url = {
-        let local_config = config.lock().unwrap();
+// The retries wait 1, 2, 4... seconds, plus up to this much more (in percent) so that they don't come in bursts.
+pub const RETRY_JITTER_PERCENT: u32 = 20;
 
-        format!(
-            "https://id.twitch.tv/oauth2/token?client_id={}&client_secret={}&grant_type=client_credentials",
-            local_config.client, local_config.secret
+// After the retries, a failed update is attempted again after this many seconds, which doubles after every
+//  failure (up to the usual time between updates).
+pub const FAILED_UPDATE_TIME: u64 = 5;
+
+// Same, but while Twitch itself is having issues, those outages usually last a while.
+pub const OUTAGE_UPDATE_TIME: u64 = 30;
+pub const MAX_OUTAGE_UPDATE_TIME: u64 = 5 * 60;
+
+// How many logins of a failed request are included in its error message.
+pub const MAX_LOGGED_LOGINS: usize = 5;
+
+// The broadcaster types rarely change, they are looked up again after this long.
+pub const BROADCASTER_REFRESH_TIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// A channel that wasn't found is looked up again after this long, until it's found (or renamed).
+pub const NOT_FOUND_RECHECK_TIME: Duration = Duration::from_secs(60 * 60);
+
+// After this many lookups in a row without finding a channel, we look for its new name.
+pub const NOT_FOUND_CHECKS: u32 = 3;
+
+// A "Refresh now" this soon after an update is ignored, it was probably clicked again while it was running.
+const REFRESH_COALESCE_TIME: Duration = Duration::from_secs(5);
+
+// The most logins that "helix/streams" accepts in a request, which is also the most streams in its answer.
+pub const MAX_LOGINS_PER_REQUEST: usize = 100;
+
+// The most logins that "helix/users" accepts in a request, the others are looked up in the next updates.
+pub const MAX_USERS_PER_REQUEST: usize = 100;
+
+// When we are rate limited, we don't wait longer than this (even if Twitch asks us to).
+pub const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+pub struct TokenInfo {
+    // Not the system clock, which can be changed (e.g. by NTP) while the token is in use.
+    pub obtained_at: Instant,
+    pub expires_in: Option<Duration>,
+}
+
+/// A user of "helix/users", the ID stays the same when the login changes.
+#[derive(Clone, Debug)]
+pub struct User {
+    pub id: String,
+    pub login: String,
+    pub broadcaster_type: BroadcasterType,
+    pub profile_image_url: Option<String>,
+}
+
+/// Set by the network thread while the updates are failing, even after retrying.
+#[derive(Clone, Debug)]
+pub struct UpdateFailure {
+    pub since: Instant,
+    pub reason: &'static str,
+}
+
+/// When the Twitch channels were last updated, and when the next update is due (`None` while they are paused).
+/// The menu shows it, it's kept apart from the state so that it's the one of the network thread.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Schedule {
+    pub last_update: Option<Instant>,
+    pub next_update: Option<Instant>,
+}
+
+pub static SCHEDULE: Mutex<Schedule> = Mutex::new(Schedule {
+    last_update: None,
+    next_update: None,
+});
+
+/// Messages sent to the network thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    // The config has changed, the channels have to be updated.
+    ConfigChanged,
+    // Discard the current token and request a new one.
+    Reauthenticate,
+    // The system resumed from sleep, the channels are probably outdated.
+    Resumed,
+    // We are connected to the internet again, after being offline.
+    Reconnected,
+    // The connection became metered, or stopped being metered.
+    MeteredChanged,
+    // The session was locked or unlocked.
+    Locked,
+    Unlocked,
+    // "Refresh now" in the menu.
+    Refresh,
+}
+
+/// Everything that can go wrong when talking to Twitch.
+#[derive(Debug)]
+pub enum TwitchError {
+    // Couldn't reach Twitch (e.g. we are offline).
+    Network(reqwest::Error),
+    Timeout,
+    // The credentials (or the token) were rejected.
+    Unauthorized(String),
+    // When we can send requests again, if Twitch told us.
+    RateLimited { reset: Option<SystemTime> },
+    ApiError { status: StatusCode, message: String },
+    // Twitch is having an outage (a 5xx, or one of its HTML error pages).
+    ServiceUnavailable { status: StatusCode },
+    // The response isn't what we expected.
+    Decode(String),
+}
+
+// These are the messages shown to the user (and written to the logs).
+impl Display for TwitchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TwitchError::Network(e) => write!(f, "Couldn't reach Twitch: {}", e),
+            TwitchError::Timeout => write!(f, "Twitch took too long to respond."),
+            TwitchError::Unauthorized(message) => write!(f, "Invalid credentials ({}).", message),
+            TwitchError::RateLimited { .. } => write!(f, "Too many requests were sent to Twitch."),
+            TwitchError::ApiError { status, message } => {
+                write!(f, "Twitch rejected the request ({}): {}", status, message)
+            }
+            TwitchError::Decode(message) => write!(f, "Invalid response: {}", message),
+            TwitchError::ServiceUnavailable { status } => {
+                write!(f, "Twitch appears to be having issues ({}).", status)
+            }
+        }
+    }
+}
+
+impl TwitchError {
+    /// Shown in the menu while the updates are failing.
+    pub fn short_reason(&self) -> &'static str {
+        match self {
+            TwitchError::Network(_) | TwitchError::Timeout => "network",
+            TwitchError::Unauthorized(_) => "authentication",
+            TwitchError::RateLimited { .. } => "rate limit",
+            TwitchError::ApiError { .. } | TwitchError::Decode(_) => "invalid response",
+            TwitchError::ServiceUnavailable { .. } => "Twitch appears to be having issues",
+        }
+    }
+}
+
+impl From<reqwest::Error> for TwitchError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            TwitchError::Timeout
+        } else if e.is_decode() {
+            TwitchError::Decode(e.to_string())
+        } else {
+            TwitchError::Network(e)
+        }
+    }
+}
+
+pub async fn get_token(
+    client: &reqwest::Client,
+    config: &Arc<Mutex<State>>,
+) -> Result<Secret, TwitchError> {
+    // Get the mutex, copy the client & secret and unlock it.
+    let (client_id, secret, url) = {
+        let local_config = config.lock_or_recover();
+
+        (
+            local_config.client.clone(),
+            local_config.secret.clone(),
+            config::join_url(&local_config.auth_base_url, "token"),
         )
     };
 
-    let response: Value = client
+    debug!("Requesting a new token (client id: {})", client_id);
+
+    // The credentials are sent in the body, so that the secret never shows up in a URL (e.g. in an error message).
+    let response = client
         .post(url)
+        .form(&[
+            ("client_id", client_id.as_str()),
+            ("client_secret", secret.expose()),
+            ("grant_type", "client_credentials"),
+        ])
         .send()
-        .await
-        .expect("Valid response.")
-        .json::<Value>()
-        .await
-        .expect("Valid JSON message.");
+        .await?;
+
+    let status = response.status();
+    let reset = rate_limit_reset(response.headers());
+
+    let response = read_body(status, &response.text().await?)?;
 
-    if !response.is_object() {
-        panic!("Invalid response: not an object.")
+    // The token endpoint answers with a 400 (or a 403) when the client or the secret are invalid.
+    if status == StatusCode::BAD_REQUEST || status == StatusCode::FORBIDDEN {
+        return Err(TwitchError::Unauthorized(error_reason(status, &response)));
     }
 
-    if !response["access_token"].is_string() {
-        if response["message"].is_string() {
-            panic!("Invalid credentials.")
+    check_response("oauth2/token", status, reset, &response, &[])?;
+
+    let token = response["access_token"].as_str().ok_or_else(|| {
+        TwitchError::Decode(String::from("doesn't have the field 'access_token'."))
+    })?;
+
+    info!("Obtained a new token");
+
+    METRICS.record_token();
+
+    config.lock_or_recover().token = Some(TokenInfo {
+        obtained_at: Instant::now(),
+        expires_in: response["expires_in"].as_u64().map(Duration::from_secs),
+    });
+
+    Ok(Secret::new(format!("Bearer {}", token)))
+}
+
+/// The Twitch API, its token is requested before the first update.
+pub struct TwitchProvider {
+    config: Arc<Mutex<State>>,
+    token: Option<Secret>,
+}
+
+impl TwitchProvider {
+    pub fn new(config: Arc<Mutex<State>>) -> Self {
+        TwitchProvider {
+            config,
+            token: None,
         }
+    }
 
-        panic!("Invalid response: doesn't have the field 'access_token'.")
+    /// A new token will be requested before the next update (e.g. the credentials changed).
+    pub fn discard_token(&mut self) {
+        self.token = None;
     }
 
-    let token = response["access_token"]
-        .as_str()
-        .expect("Valid access token.");
+    async fn token(&mut self, client: &reqwest::Client) -> Result<Secret, TwitchError> {
+        match &self.token {
+            Some(token) => Ok(token.clone()),
+            None => {
+                self.authenticate(client).await?;
+                Ok(self.token.clone().expect("Expected to have a token."))
+            }
+        }
+    }
+
+    /// The users of some logins (or IDs, with "id"), the ones that don't exist (anymore) aren't in the result.
+    pub async fn fetch_users(
+        &mut self,
+        client: &reqwest::Client,
+        parameter: &str,
+        values: &[String],
+    ) -> Result<Vec<User>, TwitchError> {
+        let token = self.token(client).await?;
+
+        let (client_id, mut url) = {
+            let local_config = self.config.lock_or_recover();
+
+            (
+                local_config.client.to_string(),
+                config::join_url(&local_config.api_base_url, "users?"),
+            )
+        };
+
+        for value in values {
+            url.push_str(parameter);
+            url.push('=');
+            url.push_str(value.as_str());
+            url.push('&');
+        }
+
+        debug!("Requesting the users: {}", url);
+
+        let response = client
+            .get(url)
+            .header("Authorization", token.expose())
+            .header("Client-id", client_id)
+            .send()
+            .await?;
 
-    return format!("Bearer {}", token);
+        let status = response.status();
+        let reset = rate_limit_reset(response.headers());
+
+        let response = read_body(status, &response.text().await?)?;
+
+        check_response("helix/users", status, reset, &response, values)?;
+
+        let data = response["data"]
+            .as_array()
+            .ok_or_else(|| TwitchError::Decode(String::from("doesn't have the field 'data'.")))?;
+
+        Ok(data
+            .iter()
+            .filter_map(|user| {
+                Some(User {
+                    id: String::from(user["id"].as_str()?),
+                    login: String::from(user["login"].as_str()?),
+                    broadcaster_type: BroadcasterType::from_twitch(
+                        user["broadcaster_type"].as_str().unwrap_or_default(),
+                    ),
+                    profile_image_url: user["profile_image_url"]
+                        .as_str()
+                        .filter(|url| !url.is_empty())
+                        .map(String::from),
+                })
+            })
+            .collect())
+    }
 }
 
-async fn update_channels(
-    client: &reqwest::Client,
-    token: &String,
-    config: &Arc<Mutex<State>>,
-) -> Result<(), reqwest::Error> {
-    let mut url = String::from("https://api.twitch.tv/helix/streams?");
+impl StreamProvider for TwitchProvider {
+    type Error = TwitchError;
+
+    async fn authenticate(&mut self, client: &reqwest::Client) -> Result<(), TwitchError> {
+        self.token = Some(get_token(client, &self.config).await?);
+
+        Ok(())
+    }
+
+    async fn fetch_streams(
+        &mut self,
+        client: &reqwest::Client,
+        channels: &[String],
+    ) -> Result<Vec<Stream>, TwitchError> {
+        let token = self.token(client).await?;
 
-    let client_id = {
-        let local_config = config.lock().unwrap();
+        let (client_id, mut url) = {
+            let local_config = self.config.lock_or_recover();
 
-        for channel in &local_config.channels {
+            (
+                local_config.client.to_string(),
+                config::join_url(&local_config.api_base_url, "streams?"),
+            )
+        };
+
+        // Otherwise the answer only has the first 20 streams.
+        url.push_str(&format!("first={}&", MAX_LOGINS_PER_REQUEST));
+
+        for channel in channels {
             url.push_str("user_login=");
-            url.push_str(channel.name.as_str());
-            url.push_str("&");
+            url.push_str(channel.as_str());
+            url.push('&');
         }
 
-        local_config.client.to_string()
-    };
+        debug!("Requesting the streams: {}", url);
 
-    let response = client
-        .get(url)
-        .header("Authorization", token)
-        .header("Client-id", client_id)
-        .send()
-        .await?
-        .json::<Value>()
-        .await
-        .expect("Valid JSON message.");
+        let response = client
+            .get(url)
+            .header("Authorization", token.expose())
+            .header("Client-id", client_id)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let reset = rate_limit_reset(response.headers());
+
+        let remaining = response
+            .headers()
+            .get("Ratelimit-Remaining")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+
+        METRICS.record_response(status.as_u16(), remaining);
+
+        // Error responses aren't always JSON, in that case we still want to know the status.
+        let response = read_body(status, &response.text().await?)?;
+
+        check_response("helix/streams", status, reset, &response, channels)?;
 
-    let contents = response
-        .as_object()
-        .expect("Unknown response: not an object.");
+        let data = response["data"]
+            .as_array()
+            .ok_or_else(|| TwitchError::Decode(String::from("doesn't have the field 'data'.")))?;
 
-    if contents.contains_key("error") || !contents.contains_key("data") {
-        panic!("Invalid API response received! Please check if the channels are valid.");
+        let streams = data
+            .iter()
+            .filter_map(|c| {
+                Some(Stream {
+                    name: String::from(c["user_login"].as_str()?),
+                    // New accounts can stream without a title, but otherwise they are required to have one.
+                    title: c["title"]
+                        .as_str()
+                        .unwrap_or("Unknown title")
+                        .trim()
+                        .to_string(),
+                    viewers: c["viewer_count"].as_u64()?,
+                    started_at: c["started_at"].as_str().and_then(logging::parse_time),
+                    tags: c["tags"]
+                        .as_array()
+                        .map(|tags| {
+                            tags.iter()
+                                .filter_map(|tag| Some(String::from(tag.as_str()?)))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    game: c["game_name"].as_str().map(String::from),
+                })
+            })
+            .collect();
+
+        Ok(streams)
+    }
+
+    fn watch_url(channel: &str) -> String {
+        let mut result = String::from("https://twitch.tv/");
+        result.push_str(channel);
+
+        result
+    }
+}
+
+/// The Twitch channels of an update, counting from 0: the low priority ones are only in one update out of
+///  "low_priority_every" (starting with the first one), unless we don't know yet if they are live.
+pub fn logins_to_update(state: &State, update: u64) -> Vec<String> {
+    let low_priority_update = update.is_multiple_of(u64::from(state.low_priority_every.max(1)));
+
+    state
+        .channels
+        .iter()
+        .filter(|channel| channel.platform == Platform::Twitch)
+        .filter(|channel| {
+            channel.priority != Priority::Low
+                || low_priority_update
+                || channel.status == ChannelStatus::Unknown
+        })
+        .map(|channel| channel.name.clone())
+        .collect()
+}
+
+/// Updates the Twitch channels and notifies the user of the changes, see `provider::apply_streams`.
+/// Only the first update (0) has all of them, see `logins_to_update`.
+pub async fn update_channels(
+    client: &reqwest::Client,
+    twitch: &mut TwitchProvider,
+    config: &Arc<Mutex<State>>,
+    update: u64,
+    summarize: bool,
+) -> Result<Vec<String>, TwitchError> {
+    let logins = logins_to_update(&config.lock_or_recover(), update);
+
+    // Without any login, Twitch would answer with the most popular streams.
+    // The streams of every request are applied together, as if they were from a single one.
+    let mut streams = Vec::new();
+
+    for chunk in logins.chunks(MAX_LOGINS_PER_REQUEST) {
+        streams.extend(twitch.fetch_streams(client, chunk).await?);
     }
 
-    let data = contents["data"].as_array().expect("Invalid data.");
+    let went_live = provider::apply_streams(config, Platform::Twitch, &logins, &streams, summarize);
+
+    update_users(client, twitch, config).await;
+
+    Ok(went_live)
+}
+
+/// Whether a channel is due for a lookup in "helix/users", see `update_users`.
+/// Without its avatar, it's looked up as often as the channels that weren't found (the lookup downloads it).
+fn needs_lookup(broadcaster: Option<&Broadcaster>, avatar_missing: bool, now: u64) -> bool {
+    let broadcaster = match broadcaster {
+        Some(broadcaster) => broadcaster,
+        None => return true,
+    };
+
+    let refresh_time = if broadcaster.not_found > 0 || avatar_missing {
+        NOT_FOUND_RECHECK_TIME
+    } else {
+        BROADCASTER_REFRESH_TIME
+    };
+
+    now.saturating_sub(broadcaster.checked_at) >= refresh_time.as_secs()
+}
+
+/// Looks up the channels in "helix/users": their broadcaster types (for the badges), their IDs, and whether
+///  they still exist. The ones that weren't found `NOT_FOUND_CHECKS` times in a row are looked up by their ID,
+///  in case they were renamed (see `rename_channels`).
+/// A failure is just logged, they are looked up again at the next update.
+async fn update_users(
+    client: &reqwest::Client,
+    twitch: &mut TwitchProvider,
+    config: &Arc<Mutex<State>>,
+) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
 
-    let local_config: &mut State = &mut config.lock().unwrap();
+    let logins: Vec<String> = config
+        .lock_or_recover()
+        .channels
+        .iter()
+        .filter(|channel| channel.platform == Platform::Twitch)
+        .filter(|channel| {
+            // It might not have been downloaded, or it was removed since then.
+            let avatar_missing =
+                platform::NOTIFICATION_AVATARS && avatars::cached(&channel.name).is_none();
 
-    for channel in &mut local_config.channels {
-        // Is this channel present in the API response?
-        let mut found: bool = false;
+            needs_lookup(channel.broadcaster.as_ref(), avatar_missing, now)
+        })
+        .map(|channel| channel.name.clone())
+        .take(MAX_USERS_PER_REQUEST)
+        .collect();
 
-        for c in data {
-            let name = &c["user_login"];
-            let mut title = &c["title"];
-            let viewers = &c["viewer_count"];
+    if logins.is_empty() {
+        return;
+    }
 
-            if !name.is_string() || !viewers.is_u64() {
+    let users = match twitch.fetch_users(client, "login", &logins).await {
+        Ok(users) => users,
+        Err(e) => {
+            warn!("Couldn't look up the users: {}", e);
+            return;
+        }
+    };
+
+    let renamed_ids: Vec<String> = {
+        let mut local_config = config.lock_or_recover();
+
+        for channel in &mut local_config.channels {
+            if channel.platform != Platform::Twitch || !logins.contains(&channel.name) {
                 continue;
             }
 
-            // New accounts can stream without a title, but otherwise they are required to have one.
-            let unknown_title = serde_json::Value::String(String::from("Unknown title"));
-            if !title.is_string() {
-                title = &unknown_title;
-            }
+            let broadcaster = match users.iter().find(|user| user.login == channel.name) {
+                Some(user) => Broadcaster {
+                    broadcaster_type: user.broadcaster_type,
+                    checked_at: now,
+                    id: Some(user.id.clone()),
+                    not_found: 0,
+                },
+                // What we knew about it is kept, its ID might tell us its new name.
+                None => {
+                    let previous = channel.broadcaster.take().unwrap_or_default();
 
-            let name = name.as_str().expect("Expected to get an username.");
-            let viewers = viewers.as_u64().expect("Expected to get the viewer count.");
-            let title = title
-                .as_str()
-                .expect("Expected to get a title.")
-                .trim()
-                .to_string();
-
-            // Check if we found the channel, not case sensitive.
-            if channel.name.to_lowercase() == name.to_lowercase() {
-                found = true;
-
-                // If the title changed when the channel was live,
-                //  we may want to notify the user based on their preferences.
-                if channel.is_online
-                    && channel.title != Some(title.clone())
-                    && local_config.notify_title_changed.contains(&channel.name)
-                {
-                    let notification_text =
-                        format!("{} has changed its title! ({} viewers)", name, viewers);
-
-                    send_notification(&title, &notification_text);
+                    if previous.not_found == 0 {
+                        info!("{} wasn't found on Twitch", channel.name);
+                    }
+
+                    Broadcaster {
+                        checked_at: now,
+                        not_found: previous.not_found.saturating_add(1),
+                        ..previous
+                    }
                 }
+            };
 
-                // If the channel wasn't live before but is now, notify the user.
-                if !channel.is_online {
-                    let notification_text = format!("{} is live! ({} viewers)", name, viewers);
+            channel.broadcaster = Some(broadcaster);
+        }
+
+        local_config
+            .channels
+            .iter()
+            .filter(|channel| logins.contains(&channel.name) && channel.not_found())
+            .filter_map(|channel| channel.broadcaster.as_ref()?.id.clone())
+            .collect()
+    };
 
-                    send_notification(&title, &notification_text);
+    debug!("Looked up the users of {} channels", logins.len());
+
+    // The avatars are only nice to have, on a metered connection they wait for a later lookup.
+    let download_avatars = platform::NOTIFICATION_AVATARS
+        && connectivity::metered_behavior(&config.lock_or_recover()) == MeteredBehavior::Normal;
+
+    if download_avatars {
+        for user in &users {
+            if let Some(url) = &user.profile_image_url {
+                if let Err(e) = avatars::download(client, &user.login, url).await {
+                    warn!("Couldn't download the avatar of {}: {}", user.login, e);
                 }
+            }
+        }
+    }
 
-                channel.title = Some(title);
-                channel.viewers = Some(viewers);
-                channel.is_online = true;
+    if !renamed_ids.is_empty() {
+        match twitch.fetch_users(client, "id", &renamed_ids).await {
+            Ok(users) => rename_channels(config, &users),
+            Err(e) => warn!("Couldn't look up the users by their ID: {}", e),
+        }
+    }
+
+    let channels = config.lock_or_recover().channels.clone();
+
+    if let Err(e) = app_state::write_broadcasters(&channels) {
+        diagnostics::record_error(&format!("Couldn't save the broadcaster types: {}", e));
+    }
+}
+
+/// The channels that weren't found, but whose ID now has another login, were renamed: they are renamed
+///  in the state and in the config file (which keeps a backup, see `config::rename_channel`).
+fn rename_channels(config: &Arc<Mutex<State>>, users: &[User]) {
+    let mut local_config = config.lock_or_recover();
+
+    for user in users {
+        // The new login might already be in the list (e.g. the user added it by hand).
+        if local_config.find_channel(&user.login).is_some() {
+            continue;
+        }
+
+        let channel = local_config.channels.iter_mut().find(|channel| {
+            channel.platform == Platform::Twitch
+                && channel.not_found()
+                && channel.broadcaster.as_ref().and_then(|b| b.id.as_ref()) == Some(&user.id)
+        });
+
+        let channel = match channel {
+            Some(channel) => channel,
+            None => continue,
+        };
+
+        let old_name = std::mem::replace(&mut channel.name, user.login.clone());
+
+        channel.broadcaster = Some(Broadcaster {
+            broadcaster_type: user.broadcaster_type,
+            checked_at: channel.broadcaster.as_ref().map_or(0, |b| b.checked_at),
+            id: Some(user.id.clone()),
+            not_found: 0,
+        });
+
+        info!("{} was renamed to {}", old_name, user.login);
+
+        match config::rename_channel(&local_config, &old_name, &user.login) {
+            Ok(()) => send_notification(
+                "A channel was renamed.",
+                &format!(
+                    "{} appears to have renamed to {} — config updated",
+                    old_name, user.login
+                ),
+            ),
+            Err(e) => {
+                diagnostics::record_error(&format!(
+                    "Couldn't rename {} in {}: {}",
+                    old_name, local_config.config_file, e
+                ));
+
+                send_notification(
+                    "A channel was renamed.",
+                    &format!(
+                        "{} appears to have renamed to {}, but the config couldn't be updated: {}",
+                        old_name, user.login, e
+                    ),
+                );
             }
         }
+    }
+}
+
+/// When the rate limit resets, Twitch sends it as a Unix timestamp.
+fn rate_limit_reset(headers: &HeaderMap) -> Option<SystemTime> {
+    let seconds = headers
+        .get("Ratelimit-Reset")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
 
-        if !found {
-            channel.is_online = false;
+/// Twitch answers with a 5xx during its outages, sometimes with an HTML page instead of the usual JSON.
+/// Any other body that isn't JSON is left for `check_response` and the caller to reject.
+fn read_body(status: StatusCode, body: &str) -> Result<Value, TwitchError> {
+    let is_html = body.trim_start().starts_with('<');
+
+    if status.is_server_error() || is_html && !status.is_client_error() {
+        return Err(TwitchError::ServiceUnavailable { status });
+    }
+
+    Ok(serde_json::from_str(body).unwrap_or(Value::Null))
+}
+
+/// The reason that Twitch gave for an error, e.g. "Bad Request - Malformed query params."
+fn error_reason(status: StatusCode, response: &Value) -> String {
+    match (response["error"].as_str(), response["message"].as_str()) {
+        (Some(error), Some(message)) => format!("{} - {}", error, message),
+        (Some(reason), None) | (None, Some(reason)) => String::from(reason),
+        (None, None) => String::from(status.canonical_reason().unwrap_or("Unknown error")),
+    }
+}
+
+/// Checks if Twitch rejected a request, the error includes the endpoint and some of the logins (never the credentials).
+/// e.g. "Bad Request - Malformed query params. (helix/streams, logins: a, b, c, +2 more)"
+fn check_response(
+    endpoint: &str,
+    status: StatusCode,
+    reset: Option<SystemTime>,
+    response: &Value,
+    logins: &[String],
+) -> Result<(), TwitchError> {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(TwitchError::RateLimited { reset });
+    }
+
+    if status == StatusCode::UNAUTHORIZED {
+        return Err(TwitchError::Unauthorized(error_reason(status, response)));
+    }
+
+    if status.is_success() && !response["error"].is_string() {
+        return Ok(());
+    }
+
+    let mut message = format!("{} ({}", error_reason(status, response), endpoint);
+
+    if !logins.is_empty() {
+        let shown: Vec<&str> = logins
+            .iter()
+            .take(MAX_LOGGED_LOGINS)
+            .map(|login| login.as_str())
+            .collect();
+
+        message.push_str(&format!(", logins: {}", shown.join(", ")));
+
+        if logins.len() > MAX_LOGGED_LOGINS {
+            message.push_str(&format!(", +{} more", logins.len() - MAX_LOGGED_LOGINS));
         }
     }
 
-    Ok(())
+    message.push(')');
+
+    Err(TwitchError::ApiError { status, message })
+}
+
+/// How long to wait before retrying after being rate limited.
+fn rate_limit_wait(reset: Option<SystemTime>) -> Duration {
+    reset
+        .and_then(|reset| reset.duration_since(SystemTime::now()).ok())
+        .unwrap_or(Duration::from_secs(1))
+        .min(MAX_RATE_LIMIT_WAIT)
+}
+
+/// How long to wait before a retry, `retry` counts from 0: it doubles every time, up to `max`, with a random jitter.
+fn retry_wait(retry: u32, max: Duration) -> Duration {
+    let wait = Duration::from_secs(1 << retry.min(16)).min(max);
+
+    // Random enough for a jitter.
+    let random = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos())
+        .unwrap_or_default();
+
+    wait + wait * (random % (RETRY_JITTER_PERCENT + 1)) / 100
+}
+
+/// A single notification for every channel that went live, instead of one for each.
+fn send_summary_notification(
+    went_live: &[String],
+    style: NotificationStyle,
+    duration: NotificationDuration,
+) {
+    let notification_text = match went_live {
+        [] => return,
+        [name] => format!("{} is live!", name),
+        [names @ .., last] => format!("{} and {} are live!", names.join(", "), last),
+    };
+
+    info!("Summary notification ({:?}): {}", style, notification_text);
+
+    notification_queue::push(&platform::Notification {
+        title: "Channels went live",
+        text: &notification_text,
+        tag: None,
+        quiet: style == NotificationStyle::Quiet,
+        long: duration == NotificationDuration::Long,
+        icon: None,
+        link: None,
+    });
+}
+
+/// The settings for a locked session only apply while it's locked.
+fn current_on_lock(config: &Arc<Mutex<State>>, locked: bool) -> OnLock {
+    if locked {
+        config.lock_or_recover().on_lock
+    } else {
+        OnLock::Normal
+    }
+}
+
+/// How many seconds to wait between updates, `None` if we shouldn't update at all.
+/// `interval` is the one of the config (see `State::update_interval`), a locked session never updates more often.
+fn update_time(on_lock: OnLock, metered: MeteredBehavior, interval: u64) -> Option<u64> {
+    let update_time = match on_lock {
+        OnLock::Pause => None,
+        OnLock::Slow => Some(LOCKED_UPDATE_CHANNELS_TIME.max(interval)),
+        OnLock::Normal => Some(interval),
+    }?;
+
+    match metered {
+        MeteredBehavior::Pause => None,
+        MeteredBehavior::Reduce => {
+            Some(update_time.max(connectivity::METERED_UPDATE_TIME.as_secs()))
+        }
+        MeteredBehavior::Normal => Some(update_time),
+    }
+}
+
+/// With "adaptive_polling", the updates are further apart while none of the channels are live:
+///  after `after` updates in a row without any, the interval doubles at every update (up to `max_interval`).
+/// It's back to normal as soon as a channel is live, or when something happens (e.g. the config changed).
+#[derive(Debug, Default)]
+pub struct PollingInterval {
+    // The successful updates in a row without any live channel.
+    offline_updates: u32,
+}
+
+impl PollingInterval {
+    pub fn record_update(&mut self, any_live: bool) {
+        if any_live {
+            self.reset();
+        } else {
+            self.offline_updates = self.offline_updates.saturating_add(1);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.offline_updates = 0;
+    }
+
+    /// The seconds to wait instead of `update_time`, which are never less than it.
+    pub fn stretch(&self, update_time: u64, adaptive: Option<AdaptivePolling>) -> u64 {
+        let adaptive = match adaptive {
+            Some(adaptive) if self.offline_updates >= adaptive.after => adaptive,
+            _ => return update_time,
+        };
+
+        // The first stretched update waits twice as long, and so on.
+        let steps = (self.offline_updates - adaptive.after)
+            .saturating_add(1)
+            .min(32);
+
+        update_time
+            .saturating_mul(1 << steps)
+            .min(adaptive.max_interval)
+            .max(update_time)
+    }
 }
 
 pub async fn listen_for_events(
     config: Arc<Mutex<State>>,
     proxy: &EventLoopProxy<Events>,
-    rx: mpsc::Receiver<()>,
+    rx: &mpsc::Receiver<Message>,
 ) {
     let client = reqwest::Client::new();
 
-    let token = get_token(&client, &config).await;
+    // Requests its token before the first update, and after the credentials are set up.
+    let mut twitch = TwitchProvider::new(config.clone());
 
     // Sometimes a request might fail temporarily, we want to retry up to MAX_RETRIES times.
     let mut retry_counter = MAX_RETRIES;
 
+    // Whether the user was already notified that the updates are failing.
+    let mut error_notified = false;
+
+    // How long to wait before the next update while they are failing.
+    let mut failure_backoff: Option<u64> = None;
+
+    // After resuming from sleep, a lot of channels might have gone live at once.
+    let mut summarize = false;
+
+    // While the session is locked (unless the config says otherwise), the channels that went live
+    //  are kept here and shown in a single notification once the user is back.
+    let mut locked = false;
+    let mut went_live_while_away: Vec<String> = Vec::new();
+
+    // The last day that had a daily summary.
+    let mut summary_sent_on = summary::due_on(&config);
+
+    let mut polling_interval = PollingInterval::default();
+
+    // The successful updates, for the low priority channels.
+    let mut update_count: u64 = 0;
+
     loop {
-        match update_channels(&client, &token, &config).await {
-            Ok(_) => {
-                retry_counter = MAX_RETRIES;
-            }
-            Err(_) => {
-                if retry_counter != 0 {
-                    retry_counter -= 1;
-                    std::thread::sleep(Duration::from_secs(1));
-                    continue;
+        let on_lock = current_on_lock(&config, locked);
+
+        // Nothing is sent to the event loop if we didn't update.
+        let mut event = None;
+
+        if config.lock_or_recover().needs_setup() {
+            // Twitch would reject them anyway, we wait until the config file is updated.
+            debug!("Skipped the update, the credentials aren't set up");
+
+            twitch.discard_token();
+        } else if on_lock == OnLock::Pause {
+            debug!("Skipped the update, the session is locked");
+        } else if connectivity::metered_behavior(&config.lock_or_recover())
+            == MeteredBehavior::Pause
+        {
+            debug!("Skipped the update, the connection is metered");
+        } else {
+            let away = on_lock == OnLock::Slow;
+
+            let result = update_channels(
+                &client,
+                &mut twitch,
+                &config,
+                update_count,
+                summarize || away,
+            )
+            .await;
+
+            match result {
+                Ok(went_live) => {
+                    METRICS.record_poll(false);
+
+                    provider::send_auto_open(&config, proxy);
+
+                    update_count += 1;
+
+                    retry_counter = MAX_RETRIES;
+                    error_notified = false;
+                    failure_backoff = None;
+                    event = Some(Events::UpdatedChannels);
+
+                    {
+                        let mut local_config = config.lock_or_recover();
+
+                        local_config.update_failure = None;
+                        local_config.last_update = Some(Instant::now());
+
+                        SCHEDULE.lock_or_recover().last_update = local_config.last_update;
+
+                        polling_interval.record_update(local_config.channels.iter().any(
+                            |channel| channel.platform == Platform::Twitch && channel.is_online(),
+                        ));
+                    }
+
+                    for name in went_live {
+                        if !went_live_while_away.contains(&name) {
+                            went_live_while_away.push(name);
+                        }
+                    }
+
+                    if !away {
+                        let (style, duration) = {
+                            let local_config = config.lock_or_recover();
+
+                            (
+                                local_config.notification_style.live,
+                                local_config.notification_duration.live,
+                            )
+                        };
+
+                        send_summary_notification(&went_live_while_away, style, duration);
+                        went_live_while_away.clear();
+                        summarize = false;
+                    }
                 }
-            }
-        };
+                Err(e) => {
+                    diagnostics::record_error(&format!("Couldn't update the channels: {}", e));
+
+                    let retry = match &e {
+                        // Probably temporary, e.g. the connection dropped.
+                        TwitchError::Network(_) | TwitchError::Timeout => true,
+                        // The token has probably expired, try again with a new one.
+                        TwitchError::Unauthorized(_) => match twitch.authenticate(&client).await {
+                            Ok(()) => true,
+                            Err(e) => {
+                                diagnostics::record_error(&format!(
+                                    "Couldn't re-authenticate: {}",
+                                    e
+                                ));
+                                false
+                            }
+                        },
+                        TwitchError::RateLimited { reset } => {
+                            std::thread::sleep(rate_limit_wait(*reset));
+                            true
+                        }
+                        // Retrying won't help (e.g. an invalid channel name), the user has to fix it.
+                        TwitchError::ApiError { .. } | TwitchError::Decode(_) => false,
+                        // It won't be fixed in a second, we wait longer instead (see below).
+                        TwitchError::ServiceUnavailable { .. } => false,
+                    };
+
+                    if retry && retry_counter != 0 {
+                        let interval = config.lock_or_recover().update_interval();
+                        let wait =
+                            retry_wait(MAX_RETRIES - retry_counter, Duration::from_secs(interval));
+
+                        warn!(
+                            "Retrying the update in {:?} ({} retries left)",
+                            wait, retry_counter
+                        );
+
+                        retry_counter -= 1;
+                        std::thread::sleep(wait);
+                        continue;
+                    }
+
+                    METRICS.record_poll(true);
 
-        let last_update = std::time::SystemTime::now();
+                    let outage = matches!(e, TwitchError::ServiceUnavailable { .. });
 
-        proxy.send_event(Events::UpdatedChannels).ok();
+                    // The retries didn't help, we don't know which channels are live anymore.
+                    // During an outage, the channels are probably the same (we just can't tell),
+                    //  so they are kept as they were.
+                    // Keep the time of the first failure.
+                    {
+                        let mut local_config = config.lock_or_recover();
+
+                        if !outage {
+                            for channel in &mut local_config.channels {
+                                channel.set_unknown();
+                            }
+                        }
+
+                        local_config
+                            .update_failure
+                            .get_or_insert_with(|| UpdateFailure {
+                                since: Instant::now(),
+                                reason: e.short_reason(),
+                            })
+                            .reason = e.short_reason();
+                    }
+
+                    // Only once until an update succeeds, otherwise we would notify on every update.
+                    if !error_notified {
+                        error_notified = true;
+
+                        // E.g. we were started at login, before the network was up. It keeps trying (see below).
+                        let waiting = update_count == 0
+                            && matches!(e, TwitchError::Network(_) | TwitchError::Timeout);
+
+                        if waiting {
+                            send_notification(
+                                "Waiting for the network…",
+                                "The channels will be updated once Twitch can be reached.",
+                            );
+                        } else {
+                            send_notification("Couldn't update the channels.", &e.to_string());
+                        }
+                    }
+
+                    let (initial, max) = if outage {
+                        (OUTAGE_UPDATE_TIME, MAX_OUTAGE_UPDATE_TIME)
+                    } else {
+                        (
+                            FAILED_UPDATE_TIME,
+                            config.lock_or_recover().update_interval(),
+                        )
+                    };
+
+                    let backoff = failure_backoff.map_or(initial, |backoff| backoff * 2);
+                    failure_backoff = Some(backoff.clamp(initial, max));
+
+                    // The channels weren't updated, but the menu shows that the updates are failing.
+                    event = Some(Events::UpdateFailed);
+                }
+            };
+        }
+
+        // If the system was asleep at that time, it's sent now.
+        summary::send_if_due(&config, &mut summary_sent_on);
+
+        // The system clock can change (or jump when resuming from sleep), so we don't rely on it.
+        let last_attempt = Instant::now();
+
+        if let Some(event) = event {
+            proxy.send_event(event).ok();
+        }
 
         loop {
             std::thread::sleep(Duration::from_millis(500));
 
             match rx.try_recv() {
-                Ok(_) => {
+                Ok(Message::ConfigChanged) => {
+                    info!("The config changed, updating the channels");
+
+                    polling_interval.reset();
+
                     // Received a notification, the config must have changed, we have to update the channels.
                     break;
                 }
+                Ok(Message::Reauthenticate) => {
+                    // The config might have new credentials by now, request a new token with them.
+                    match twitch.authenticate(&client).await {
+                        Ok(()) => {
+                            send_notification("Re-authenticated", "A new token was obtained.");
+                        }
+                        Err(e) => {
+                            diagnostics::record_error(&format!("Couldn't re-authenticate: {}", e));
+                            send_notification("Couldn't re-authenticate.", &e.to_string());
+                        }
+                    }
+
+                    break;
+                }
+                Ok(Message::Refresh) => {
+                    if last_attempt.elapsed() < REFRESH_COALESCE_TIME {
+                        debug!("Ignored the refresh, the channels were just updated");
+                        continue;
+                    }
+
+                    info!("Refreshing the channels");
+
+                    polling_interval.reset();
+
+                    break;
+                }
+                Ok(Message::Resumed) => {
+                    info!("The system resumed from sleep");
+
+                    // Whatever failed before the system went to sleep doesn't matter anymore.
+                    retry_counter = MAX_RETRIES;
+                    summarize = true;
+                    polling_interval.reset();
+
+                    break;
+                }
+                Ok(Message::Reconnected) => {
+                    info!("Connected to the internet again");
+
+                    // Don't wait for the next update if the last one failed because we were offline.
+                    retry_counter = MAX_RETRIES;
+
+                    break;
+                }
+                Ok(Message::MeteredChanged) => {
+                    // The tooltip shows it.
+                    proxy.send_event(Events::UpdatedChannels).ok();
+
+                    // Back to normal, there's no need to wait any longer.
+                    if connectivity::metered_behavior(&config.lock_or_recover())
+                        == MeteredBehavior::Normal
+                    {
+                        break;
+                    }
+                }
+                Ok(Message::Locked) => {
+                    info!("The session was locked");
+
+                    // There's no need to update right away, we only wait longer for the next one (if any).
+                    locked = true;
+                }
+                Ok(Message::Unlocked) => {
+                    info!("The session was unlocked");
+
+                    // The user is back, show them what they missed.
+                    locked = false;
+                    summarize = true;
+                    polling_interval.reset();
+
+                    break;
+                }
                 Err(TryRecvError::Empty) => {
-                    // Has it been more than X seconds since the last update?
-                    if let Some(time) = last_update.elapsed().ok() {
-                        if time.as_secs() >= UPDATE_CHANNELS_TIME {
+                    // Has it been more than X seconds since the last update? (or the backoff, if it failed)
+                    // The interval is read every time, so that a change of the config applies right away.
+                    let (metered, interval) = {
+                        let local_config = config.lock_or_recover();
+
+                        (
+                            connectivity::metered_behavior(&local_config),
+                            local_config.update_interval(),
+                        )
+                    };
+
+                    let update_time = update_time(
+                        current_on_lock(&config, locked),
+                        metered,
+                        interval,
+                    )
+                    .map(|update_time| {
+                        failure_backoff.unwrap_or_else(|| {
+                            polling_interval
+                                .stretch(update_time, config.lock_or_recover().adaptive_polling)
+                        })
+                    });
+
+                    SCHEDULE.lock_or_recover().next_update = update_time
+                        .map(|update_time| last_attempt + Duration::from_secs(update_time));
+
+                    if let Some(update_time) = update_time {
+                        if last_attempt.elapsed().as_secs() >= update_time {
                             break; // If so, send the request to update the channels.
                         }
                     }
@@ -219,21 +1227,52 @@ pub async fn listen_for_events(
 pub async fn refresh_config(
     config: Arc<Mutex<State>>,
     proxy: &EventLoopProxy<Events>,
-    update_tx: mpsc::Sender<()>,
+    update_tx: mpsc::Sender<Message>,
 ) {
     loop {
         let old_config = {
             // Copy the config so we can compare it.
-            config.lock().unwrap().clone()
+            config.lock_or_recover().clone()
         };
 
-        let new_config = config::read();
+        let new_config = config::read_file(&old_config.config_file);
+
+        // If the user switched to another profile in the meantime, this config is outdated.
+        let switched = config.lock_or_recover().config_file != new_config.config_file;
+
+        if old_config != new_config && !switched {
+            info!("Reloaded the config file: {}", new_config.config_file);
+
+            logging::set_level(new_config.log_level);
+            platform::set_notifications_enabled(new_config.notifications_enabled);
+
+            // Only if they changed, otherwise we would warn every time the file is saved.
+            if old_config.invalid_channels != new_config.invalid_channels {
+                config::warn_invalid_channels(&new_config);
+            }
+
+            if old_config.unmatched_notify_entries() != new_config.unmatched_notify_entries() {
+                config::warn_unmatched_notify_entries(&new_config);
+            }
+
+            if old_config.unmatched_group_members() != new_config.unmatched_group_members() {
+                config::warn_unmatched_group_members(&new_config);
+            }
+
+            if old_config.unknown_menu_placeholders() != new_config.unknown_menu_placeholders() {
+                config::warn_unknown_placeholders(&new_config);
+            }
+
+            if old_config.missing_browser() != new_config.missing_browser() {
+                config::warn_missing_browser(&new_config);
+            }
 
-        if old_config != new_config {
             config::migrate(&config, new_config);
 
+            METRICS.record_config_reload();
+
             // Notify the network thread that we have to request an update.
-            update_tx.send(()).ok();
+            update_tx.send(Message::ConfigChanged).ok();
 
             proxy.send_event(Events::UpdatedChannels).ok();
         }
@@ -241,3 +1280,166 @@ pub async fn refresh_config(
         std::thread::sleep(READ_CONFIG_FILE_TIME);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polling_interval_doubles_up_to_the_max() {
+        let adaptive = Some(AdaptivePolling {
+            after: 2,
+            max_interval: 600,
+        });
+
+        let mut interval = PollingInterval::default();
+        let mut waits = Vec::new();
+
+        for _ in 0..8 {
+            waits.push(interval.stretch(60, adaptive));
+            interval.record_update(false);
+        }
+
+        assert_eq!(waits, [60, 60, 120, 240, 480, 600, 600, 600]);
+
+        // Back to normal as soon as a channel is live.
+        interval.record_update(true);
+        assert_eq!(interval.stretch(60, adaptive), 60);
+
+        assert_eq!(interval.stretch(60, None), 60);
+    }
+
+    #[test]
+    fn polling_interval_is_never_less_than_the_update_time() {
+        let adaptive = Some(AdaptivePolling {
+            after: 0,
+            max_interval: 30,
+        });
+
+        assert_eq!(PollingInterval::default().stretch(60, adaptive), 60);
+
+        // The doubling doesn't overflow after a very long time without a live channel.
+        let interval = PollingInterval {
+            offline_updates: u32::MAX,
+        };
+        let adaptive = Some(AdaptivePolling {
+            after: 0,
+            max_interval: 3600,
+        });
+
+        assert_eq!(interval.stretch(60, adaptive), 3600);
+        assert_eq!(interval.stretch(u64::MAX, adaptive), u64::MAX);
+    }
+
+    #[test]
+    fn retry_wait_grows_up_to_the_max_with_a_jitter() {
+        let max = Duration::from_secs(60);
+        let mut previous = Duration::ZERO;
+
+        for retry in 0..40 {
+            let base = Duration::from_secs(1 << retry.min(16)).min(max);
+            let wait = retry_wait(retry, max);
+
+            assert!(wait >= base, "retry {}: {:?}", retry, wait);
+            assert!(
+                wait <= base + base * RETRY_JITTER_PERCENT / 100,
+                "retry {}: {:?}",
+                retry,
+                wait
+            );
+
+            assert!(base >= previous);
+            previous = base;
+        }
+
+        assert_eq!(previous, max);
+    }
+
+    /// Same as the requests: the body is read first, then checked.
+    fn failure(status: u16, body: &str) -> Option<TwitchError> {
+        let status = StatusCode::from_u16(status).unwrap();
+
+        read_body(status, body)
+            .and_then(|response| check_response("helix/streams", status, None, &response, &[]))
+            .err()
+    }
+
+    #[test]
+    fn http_failures() {
+        let json_error =
+            r#"{"error": "Bad Request", "status": 400, "message": "Malformed query params."}"#;
+        let html = "<html><body>Service Unavailable</body></html>";
+
+        let cases = [
+            (200, r#"{"data": []}"#, None),
+            (
+                200,
+                r#"{"error": "Internal", "message": "Oops"}"#,
+                Some("invalid response"),
+            ),
+            (200, html, Some("Twitch appears to be having issues")),
+            (400, json_error, Some("invalid response")),
+            (400, html, Some("invalid response")),
+            (401, r#"{"error": "Unauthorized"}"#, Some("authentication")),
+            (401, "", Some("authentication")),
+            (429, "", Some("rate limit")),
+            (500, json_error, Some("Twitch appears to be having issues")),
+            (502, html, Some("Twitch appears to be having issues")),
+            (503, "", Some("Twitch appears to be having issues")),
+        ];
+
+        for (status, body, expected) in cases {
+            let reason = failure(status, body).map(|e| e.short_reason());
+
+            assert_eq!(reason, expected, "{} {}", status, body);
+        }
+    }
+
+    #[test]
+    fn http_failure_messages() {
+        let json_error = r#"{"error": "Bad Request", "message": "Malformed query params."}"#;
+        let logins: Vec<String> = (1..=7).map(|i| format!("login{}", i)).collect();
+        let status = StatusCode::BAD_REQUEST;
+
+        let error = check_response(
+            "helix/streams",
+            status,
+            None,
+            &read_body(status, json_error).unwrap(),
+            &logins,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Twitch rejected the request (400 Bad Request): Bad Request - Malformed query params. \
+             (helix/streams, logins: login1, login2, login3, login4, login5, +2 more)"
+        );
+
+        // Without a reason, the status tells what happened.
+        let error = failure(401, "").unwrap();
+        assert_eq!(error.to_string(), "Invalid credentials (Unauthorized).");
+    }
+
+    #[test]
+    fn rate_limit_reset_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(rate_limit_reset(&headers), None);
+
+        headers.insert("Ratelimit-Reset", "1700000000".parse().unwrap());
+        assert_eq!(
+            rate_limit_reset(&headers),
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+
+        // Already passed, so we only wait a little.
+        assert_eq!(
+            rate_limit_wait(rate_limit_reset(&headers)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            rate_limit_wait(Some(SystemTime::now() + Duration::from_secs(3600))),
+            MAX_RATE_LIMIT_WAIT
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Checks once a day if there's a newer release on GitHub, unless "check_for_updates" is disabled.
+//! The failures are only logged, the check is attempted again the next day.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde::Deserialize;
+use winit::event_loop::EventLoopProxy;
+
+use crate::config::{MeteredBehavior, State};
+use crate::connectivity;
+use crate::lock::RecoverLock;
+use crate::platform::send_notification;
+use crate::Events;
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/HazyAlex/taskbar-twitch/releases/latest";
+
+// GitHub rejects the requests without one.
+pub const USER_AGENT: &str = concat!("taskbar-twitch/", env!("CARGO_PKG_VERSION"));
+
+pub const CHECK_TIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+// So that the check doesn't compete with the first update of the channels.
+pub const FIRST_CHECK_DELAY: Duration = Duration::from_secs(30);
+
+/// A release that is newer than the one running.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Release {
+    // Without the "v", e.g. "1.2.0".
+    pub version: String,
+    // The page of the release.
+    pub url: String,
+    // The files of the release, for the self update.
+    pub assets: Vec<Asset>,
+}
+
+/// A file of a release, e.g. "taskbar-twitch-x86_64.exe".
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Asset {
+    pub name: String,
+    pub size: u64,
+    pub browser_download_url: String,
+    // e.g. "sha256:9f86d08...", GitHub only has it for the newer releases.
+    #[serde(default)]
+    pub digest: Option<String>,
+}
+
+/// The fields that we use of "releases/latest" (the drafts and pre-releases are never the latest one).
+#[derive(Debug, Deserialize)]
+struct ReleaseResponse {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+/// The numbers of a version (e.g. "v1.2.3" is [1, 2, 3]), and whether it's a pre-release (e.g. "1.2.3-beta").
+fn parse_version(version: &str) -> Option<(Vec<u64>, bool)> {
+    let version = version.trim();
+    let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+
+    // The build metadata (e.g. "+ci") doesn't count.
+    let version = version.split('+').next()?;
+
+    let (numbers, prerelease) = match version.split_once('-') {
+        Some((numbers, _)) => (numbers, true),
+        None => (version, false),
+    };
+
+    let numbers = numbers
+        .split('.')
+        .map(|number| number.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+
+    Some((numbers, prerelease))
+}
+
+/// Whether `latest` is newer than `current`, with or without a "v" (e.g. "v1.10.0" is newer than "1.9").
+/// A pre-release is older than its release, the versions that can't be read are never newer.
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    let (latest, current) = match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => (latest, current),
+        _ => return false,
+    };
+
+    let length = latest.0.len().max(current.0.len());
+
+    let numbers = |version: &(Vec<u64>, bool)| {
+        let mut numbers = version.0.clone();
+        numbers.resize(length, 0);
+        numbers
+    };
+
+    match numbers(&latest).cmp(&numbers(&current)) {
+        std::cmp::Ordering::Greater => true,
+        std::cmp::Ordering::Less => false,
+        // e.g. "1.2.0" after "1.2.0-beta"
+        std::cmp::Ordering::Equal => current.1 && !latest.1,
+    }
+}
+
+async fn fetch_latest_release(client: &reqwest::Client) -> Result<Release, reqwest::Error> {
+    let response: ReleaseResponse = client
+        .get(LATEST_RELEASE_URL)
+        .header("User-Agent", USER_AGENT)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let tag = response.tag_name.trim();
+
+    Ok(Release {
+        version: String::from(tag.strip_prefix(['v', 'V']).unwrap_or(tag)),
+        url: response.html_url,
+        assets: response.assets,
+    })
+}
+
+/// Runs forever, the menu shows the release once it's found.
+pub fn watch(config: Arc<Mutex<State>>, proxy: &EventLoopProxy<Events>) {
+    let client = reqwest::Client::new();
+
+    std::thread::sleep(FIRST_CHECK_DELAY);
+
+    loop {
+        let check = {
+            let local_config = config.lock_or_recover();
+
+            // Tried again the next day, it isn't worth the data.
+            local_config.check_for_updates
+                && connectivity::metered_behavior(&local_config) == MeteredBehavior::Normal
+        };
+
+        if check {
+            match futures::executor::block_on(fetch_latest_release(&client)) {
+                Ok(release) if is_newer(&release.version, CURRENT_VERSION) => {
+                    let mut local_config = config.lock_or_recover();
+
+                    if local_config.available_update.as_ref() != Some(&release) {
+                        info!("A new version is available: {}", release.version);
+
+                        send_notification(
+                            &format!("Update available: v{}", release.version),
+                            &format!(
+                                "You are using v{}, the new version is in the menu.",
+                                CURRENT_VERSION
+                            ),
+                        );
+
+                        local_config.available_update = Some(release);
+
+                        drop(local_config);
+
+                        proxy.send_event(Events::UpdateAvailable).ok();
+                    }
+                }
+                Ok(release) => debug!("No new version (the latest is {})", release.version),
+                Err(e) => warn!("Couldn't check for updates: {}", e),
+            }
+        }
+
+        std::thread::sleep(CHECK_TIME);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_versions() {
+        // The latest release, the current version, and whether the release is newer.
+        let cases = [
+            ("1.0.4", "1.0.3", true),
+            ("v1.0.4", "1.0.3", true),
+            ("V2.0", "1.9.9", true),
+            ("1.10.0", "1.9.0", true),
+            ("1.0.3", "1.0.3", false),
+            ("v1.0.3", "1.0.3", false),
+            ("1.0", "1.0.0", false),
+            ("1.0.0.1", "1.0", true),
+            ("1.0.2", "1.0.3", false),
+            ("1.9.0", "1.10.0", false),
+            ("1.2.0", "1.2.0-beta", true),
+            ("1.2.0-beta", "1.2.0", false),
+            ("1.2.0-beta.2", "1.2.0-beta.1", false),
+            ("1.3.0-beta", "1.2.0", true),
+            ("1.0.3+ci", "1.0.3", false),
+            ("1.0.4+ci", "1.0.3", true),
+            ("latest", "1.0.3", false),
+            ("1.0.4", "unknown", false),
+            ("", "1.0.3", false),
+            ("1..4", "1.0.3", false),
+        ];
+
+        for (latest, current, expected) in cases {
+            assert_eq!(
+                is_newer(latest, current),
+                expected,
+                "{} > {}",
+                latest,
+                current
+            );
+        }
+    }
+}
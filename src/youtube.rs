@@ -0,0 +1,337 @@
+//! The channels on YouTube, as a handle (e.g. "@LinusTechTips") or a channel ID (e.g. "UCXuqSBlHAE6Xw-yeJA0Tunw").
+//! The "/live" page of a channel shows its stream while it's live, so that's how we find them (without any key).
+//! With a "youtube_api_key", the viewers come from the YouTube Data API, which only costs 1 unit for 50 streams.
+
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use log::debug;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::config::State;
+use crate::lock::RecoverLock;
+use crate::logging;
+use crate::provider::{Stream, StreamProvider};
+
+// Each channel is a whole page, so they are updated less often than the other platforms.
+pub const UPDATE_CHANNELS_TIME: Duration = Duration::from_secs(5 * 60);
+
+// The length of the channel IDs, all of them start with "UC".
+pub const CHANNEL_ID_LENGTH: usize = 24;
+
+pub const MIN_HANDLE_LENGTH: usize = 3;
+pub const MAX_HANDLE_LENGTH: usize = 30;
+
+// The most videos that the API returns in a request.
+pub const MAX_VIDEOS_PER_REQUEST: usize = 50;
+
+/// Everything that can go wrong when talking to YouTube.
+#[derive(Debug)]
+pub enum YouTubeError {
+    // Couldn't reach YouTube (e.g. we are offline).
+    Network(reqwest::Error),
+    // The handle (or the channel ID) doesn't exist.
+    NotFound(String),
+    // YouTube rejected the request, e.g. the API key is invalid or its quota is exhausted.
+    Rejected(StatusCode),
+    // The response isn't what we expected.
+    Decode(String),
+}
+
+impl Display for YouTubeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            YouTubeError::Network(e) => write!(f, "Couldn't reach YouTube: {}", e),
+            YouTubeError::NotFound(channel) => write!(
+                f,
+                "The YouTube channel {} doesn't exist (it should be a handle like \"@name\", or a channel ID).",
+                channel
+            ),
+            YouTubeError::Rejected(status) => {
+                write!(f, "YouTube rejected the request ({}).", status)
+            }
+            YouTubeError::Decode(message) => {
+                write!(f, "Invalid response from YouTube: {}", message)
+            }
+        }
+    }
+}
+
+impl From<reqwest::Error> for YouTubeError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            YouTubeError::Decode(e.to_string())
+        } else {
+            YouTubeError::Network(e)
+        }
+    }
+}
+
+/// Whether a channel is a channel ID (e.g. "UCXuqSBlHAE6Xw-yeJA0Tunw"), otherwise it's a handle.
+pub fn is_channel_id(channel: &str) -> bool {
+    channel.len() == CHANNEL_ID_LENGTH
+        && channel.starts_with("UC")
+        && channel
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// The page of a channel, e.g. "https://www.youtube.com/@name" or "https://www.youtube.com/channel/UC...".
+fn channel_url(channel: &str) -> String {
+    let mut result = String::from("https://www.youtube.com/");
+
+    if is_channel_id(channel) {
+        result.push_str("channel/");
+    }
+
+    result.push_str(channel);
+
+    result
+}
+
+/// What we found on the "/live" page of a channel that is live.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LivePage {
+    pub video_id: String,
+    pub title: String,
+    // e.g. "2023-05-14T18:03:27+00:00"
+    pub start_date: Option<String>,
+}
+
+/// The "/live" page of a live channel is its stream, otherwise it's the page of the channel
+///  (or an upcoming stream, which isn't "live now").
+pub fn parse_live_page(html: &str) -> Option<LivePage> {
+    if !html.contains("\"isLiveNow\":true") {
+        return None;
+    }
+
+    let canonical = between(html, "<link rel=\"canonical\" href=\"", "\"")?;
+    let video_id = canonical.split("watch?v=").nth(1)?.split('&').next()?;
+
+    let title = between(html, "<meta name=\"title\" content=\"", "\"")
+        .map(unescape_html)
+        .filter(|title| !title.trim().is_empty())
+        .unwrap_or_else(|| String::from("Unknown title"));
+
+    Some(LivePage {
+        video_id: String::from(video_id),
+        title: title.trim().to_string(),
+        start_date: between(html, "\"startDate\":\"", "\"").map(String::from),
+    })
+}
+
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let rest = &text[text.find(start)? + start.len()..];
+
+    Some(&rest[..rest.find(end)?])
+}
+
+/// The titles are escaped in the page (e.g. "Q&amp;A").
+fn unescape_html(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// The page writes its times with an offset (e.g. "2023-05-14T18:03:27+00:00"), the API with a "Z".
+fn parse_time(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+
+    match value.strip_suffix("+00:00") {
+        Some(utc) => logging::parse_time(&format!("{}Z", utc)),
+        None => logging::parse_time(value),
+    }
+}
+
+/// The fields that we use of "youtube/v3/videos?part=snippet,liveStreamingDetails".
+#[derive(Debug, Deserialize)]
+struct VideosResponse {
+    #[serde(default)]
+    items: Vec<Video>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Video {
+    id: String,
+    snippet: Option<VideoSnippet>,
+    #[serde(rename = "liveStreamingDetails")]
+    live_streaming_details: Option<LiveStreamingDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VideoSnippet {
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LiveStreamingDetails {
+    // It's a string, e.g. "1234", and it's missing when the channel hides it.
+    #[serde(rename = "concurrentViewers")]
+    concurrent_viewers: Option<String>,
+    #[serde(rename = "actualStartTime")]
+    actual_start_time: Option<String>,
+}
+
+/// The pages of the channels, with the YouTube Data API for the viewers when there's an API key.
+pub struct YouTubeProvider {
+    config: Arc<Mutex<State>>,
+}
+
+impl YouTubeProvider {
+    pub fn new(config: Arc<Mutex<State>>) -> Self {
+        YouTubeProvider { config }
+    }
+
+    async fn fetch_live_page(
+        &self,
+        client: &reqwest::Client,
+        channel: &str,
+    ) -> Result<Option<LivePage>, YouTubeError> {
+        let mut url = channel_url(channel);
+        url.push_str("/live");
+
+        debug!("Requesting the YouTube channel: {}", url);
+
+        // Otherwise, the page can be the cookie consent (e.g. in Europe).
+        let response = client
+            .get(url)
+            .header("Cookie", "SOCS=CAI")
+            .header("Accept-Language", "en")
+            .send()
+            .await?;
+
+        let status = response.status();
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(YouTubeError::NotFound(String::from(channel)));
+        }
+
+        if !status.is_success() {
+            return Err(YouTubeError::Rejected(status));
+        }
+
+        Ok(parse_live_page(&response.text().await?))
+    }
+
+    /// Updates the streams with what the API knows about their videos (the viewers, when they started).
+    async fn fetch_details(
+        &self,
+        client: &reqwest::Client,
+        api_key: &str,
+        streams: &mut [(String, Stream)],
+    ) -> Result<(), YouTubeError> {
+        for chunk in streams.chunks_mut(MAX_VIDEOS_PER_REQUEST) {
+            let ids: Vec<&str> = chunk
+                .iter()
+                .map(|(video_id, _)| video_id.as_str())
+                .collect();
+
+            let response = client
+                .get("https://www.googleapis.com/youtube/v3/videos")
+                .query(&[
+                    ("part", "snippet,liveStreamingDetails"),
+                    ("id", &ids.join(",")),
+                    ("key", api_key),
+                ])
+                .send()
+                .await?;
+
+            let status = response.status();
+
+            if !status.is_success() {
+                return Err(YouTubeError::Rejected(status));
+            }
+
+            let response: VideosResponse = serde_json::from_str(&response.text().await?)
+                .map_err(|e| YouTubeError::Decode(e.to_string()))?;
+
+            for video in response.items {
+                let stream = match chunk.iter_mut().find(|(video_id, _)| *video_id == video.id) {
+                    Some((_, stream)) => stream,
+                    None => continue,
+                };
+
+                if let Some(snippet) = video.snippet {
+                    stream.title = snippet.title;
+                }
+
+                if let Some(details) = video.live_streaming_details {
+                    if let Some(viewers) = details
+                        .concurrent_viewers
+                        .and_then(|viewers| viewers.parse().ok())
+                    {
+                        stream.viewers = viewers;
+                    }
+
+                    if let Some(started_at) =
+                        details.actual_start_time.as_deref().and_then(parse_time)
+                    {
+                        stream.started_at = Some(started_at);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl StreamProvider for YouTubeProvider {
+    type Error = YouTubeError;
+
+    async fn authenticate(&mut self, _client: &reqwest::Client) -> Result<(), YouTubeError> {
+        Ok(())
+    }
+
+    async fn fetch_streams(
+        &mut self,
+        client: &reqwest::Client,
+        channels: &[String],
+    ) -> Result<Vec<Stream>, YouTubeError> {
+        // With the video of each stream, for the API.
+        let mut streams: Vec<(String, Stream)> = Vec::new();
+
+        for channel in channels {
+            if let Some(page) = self.fetch_live_page(client, channel).await? {
+                let stream = Stream {
+                    name: channel.clone(),
+                    title: page.title,
+                    // Unless the API tells us.
+                    viewers: 0,
+                    started_at: page.start_date.as_deref().and_then(parse_time),
+                    tags: Vec::new(),
+                    game: None,
+                };
+
+                streams.push((page.video_id, stream));
+            }
+        }
+
+        let api_key = self
+            .config
+            .lock_or_recover()
+            .youtube_api_key
+            .clone()
+            .filter(|key| !key.expose().trim().is_empty());
+
+        if let Some(api_key) = api_key {
+            if !streams.is_empty() {
+                self.fetch_details(client, api_key.expose().trim(), &mut streams)
+                    .await?;
+            }
+        }
+
+        Ok(streams.into_iter().map(|(_, stream)| stream).collect())
+    }
+
+    fn watch_url(channel: &str) -> String {
+        let mut result = channel_url(channel);
+        result.push_str("/live");
+
+        result
+    }
+}